@@ -0,0 +1,373 @@
+use pinocchio::instruction::AccountMeta;
+use pinocchio::pubkey::Pubkey;
+use solana_pubkey::Pubkey as SolanaPubkey;
+
+// Off-chain helpers to locate pools, deriving the same addresses as
+// `Initialize`. PDA derivation through `pinocchio` is only available
+// on-chain, hence `solana-pubkey`.
+
+/// Addresses of the accounts making up a pool
+pub struct PoolAddresses {
+    pub config: Pubkey,
+    pub config_bump: u8,
+    pub mint_lp: Pubkey,
+    pub lp_bump: u8,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+}
+
+/// Orders a mint pair deterministically, lowest bytes first as X
+pub fn canonicalize_mints(a: Pubkey, b: Pubkey) -> (Pubkey, Pubkey) {
+    if a <= b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+fn find(seeds: &[&[u8]], program_id: &Pubkey) -> (Pubkey, u8) {
+    let (key, bump) =
+        SolanaPubkey::find_program_address(seeds, &SolanaPubkey::new_from_array(*program_id));
+    (key.to_bytes(), bump)
+}
+
+/// Vault ATAs of the pool at `config` and their bumps, the off-chain
+/// counterpart of `Config::vault_addresses`
+pub fn vault_addresses(
+    config: &Pubkey,
+    token_program: &Pubkey,
+    mint_x: &Pubkey,
+    mint_y: &Pubkey,
+) -> ((Pubkey, u8), (Pubkey, u8)) {
+    let vault = |mint: &Pubkey| {
+        find(
+            &[config, token_program, mint],
+            &pinocchio_associated_token_account::ID,
+        )
+    };
+    (vault(mint_x), vault(mint_y))
+}
+
+/// Derives the addresses of the pool of `program_id` for `seed` and the
+/// two mints, in the order given, see `canonicalize_mints`
+pub fn get_pool_by_mints(
+    program_id: &Pubkey,
+    seed: u64,
+    mint_x: &Pubkey,
+    mint_y: &Pubkey,
+) -> PoolAddresses {
+    let (config, config_bump) = find(
+        &[b"config", &seed.to_le_bytes(), mint_x, mint_y],
+        program_id,
+    );
+
+    let (mint_lp, lp_bump) = find(&[b"mint_lp", &config], program_id);
+
+    let ((vault_x, _), (vault_y, _)) =
+        vault_addresses(&config, &pinocchio_token::ID, mint_x, mint_y);
+
+    PoolAddresses {
+        config,
+        config_bump,
+        mint_lp,
+        lp_bump,
+        vault_x,
+        vault_y,
+    }
+}
+
+/// Derives the pool addresses for each seed, with the mints canonicalized
+pub fn get_pools_by_mints<'a>(
+    program_id: &'a Pubkey,
+    seeds: &'a [u64],
+    a: &Pubkey,
+    b: &Pubkey,
+) -> impl Iterator<Item = PoolAddresses> + 'a {
+    let (mint_x, mint_y) = canonicalize_mints(*a, *b);
+    seeds
+        .iter()
+        .map(move |&seed| get_pool_by_mints(program_id, seed, &mint_x, &mint_y))
+}
+
+/// Derives the registry counting the pools of a mint pair, in the order given
+pub fn get_pair_registry(program_id: &Pubkey, mint_x: &Pubkey, mint_y: &Pubkey) -> (Pubkey, u8) {
+    find(&[crate::PairRegistry::SEED, mint_x, mint_y], program_id)
+}
+
+/// Instruction data of `UpdateFee`, discriminator included. The
+/// discriminator is 18 and not the 4 first specified for the instruction,
+/// which `ReinitializeVaultAuthority` already used.
+pub fn update_fee_data(fee: u16) -> [u8; 3] {
+    let mut data = [*crate::UpdateFee::DISCRIMINATOR, 0, 0];
+    data[1..].copy_from_slice(&fee.to_le_bytes());
+    data
+}
+
+/// Instruction data of `AdminSetState`, discriminator included. The
+/// discriminator is 22 and not the 5 first specified for the instruction,
+/// which `UpdateConfig` already used.
+pub fn admin_set_state_data(state: u8) -> [u8; 2] {
+    [*crate::AdminSetState::DISCRIMINATOR, state]
+}
+
+/// Why an account list doesn't fit an instruction, see `validate_deposit_accounts`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccountListError {
+    /// Wrong number of accounts, `expected` lists the accepted counts
+    Count {
+        got: usize,
+        expected: &'static [usize],
+    },
+    /// The account at `index`, playing `role`, must sign
+    MissingSigner { index: usize, role: &'static str },
+    /// The account at `index`, playing `role`, must be writable
+    NotWritable { index: usize, role: &'static str },
+}
+
+impl core::fmt::Display for AccountListError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            AccountListError::Count { got, expected } => {
+                write!(f, "got {got} accounts, expected one of {expected:?}")
+            }
+            AccountListError::MissingSigner { index, role } => {
+                write!(f, "account {index} ({role}) must be a signer")
+            }
+            AccountListError::NotWritable { index, role } => {
+                write!(f, "account {index} ({role}) must be writable")
+            }
+        }
+    }
+}
+
+impl std::error::Error for AccountListError {}
+
+/// Role of an account position, and whether it must sign and be writable
+struct Slot(&'static str, bool, bool);
+
+const DEPOSIT_ACCOUNTS: [Slot; 13] = [
+    Slot("user", true, true),
+    Slot("mint_lp", false, true),
+    Slot("vault_x", false, true),
+    Slot("vault_y", false, true),
+    Slot("user_x_ata", false, true),
+    Slot("user_y_ata", false, true),
+    Slot("user_lp_ata", false, true),
+    Slot("config", false, true),
+    Slot("token_program", false, false),
+    Slot("global_config", false, false),
+    Slot("system_program", false, false),
+    Slot("associated_token_program", false, false),
+    Slot("lp_lock", false, true),
+];
+
+const WITHDRAW_ACCOUNTS: [Slot; 11] = [
+    Slot("user", true, true),
+    Slot("mint_lp", false, true),
+    Slot("vault_x", false, true),
+    Slot("vault_y", false, true),
+    Slot("user_x_ata", false, true),
+    Slot("user_y_ata", false, true),
+    Slot("user_lp_ata", false, true),
+    Slot("config", false, true),
+    Slot("token_program", false, false),
+    Slot("global_config", false, false),
+    Slot("lp_rent_destination", false, true),
+];
+
+const SWAP_ACCOUNTS: [Slot; 9] = [
+    Slot("user", true, true),
+    Slot("user_x_ata", false, true),
+    Slot("user_y_ata", false, true),
+    Slot("vault_x", false, true),
+    Slot("vault_y", false, true),
+    Slot("mint_lp", false, false),
+    Slot("config", false, true),
+    Slot("token_program", false, false),
+    Slot("global_config", false, false),
+];
+
+fn validate_accounts(
+    metas: &[AccountMeta],
+    slots: &[Slot],
+    counts: &'static [usize],
+) -> Result<(), AccountListError> {
+    if !counts.contains(&metas.len()) {
+        return Err(AccountListError::Count {
+            got: metas.len(),
+            expected: counts,
+        });
+    }
+
+    for (index, (meta, Slot(role, signer, writable))) in metas.iter().zip(slots).enumerate() {
+        if *signer && !meta.is_signer {
+            return Err(AccountListError::MissingSigner { index, role });
+        }
+
+        if *writable && !meta.is_writable {
+            return Err(AccountListError::NotWritable { index, role });
+        }
+    }
+
+    Ok(())
+}
+
+/// Checks the account list of a `Deposit` for count, signer and writable
+/// flags, before submitting. Keys aren't checked, see `get_pool_by_mints`.
+pub fn validate_deposit_accounts(metas: &[AccountMeta]) -> Result<(), AccountListError> {
+    validate_accounts(metas, &DEPOSIT_ACCOUNTS, &[10, 12, 13])
+}
+
+/// Checks the account list of a `Withdraw`, see `validate_deposit_accounts`
+pub fn validate_withdraw_accounts(metas: &[AccountMeta]) -> Result<(), AccountListError> {
+    validate_accounts(metas, &WITHDRAW_ACCOUNTS, &[10, 11])
+}
+
+/// Checks the account list of a `Swap`, see `validate_deposit_accounts`
+pub fn validate_swap_accounts(metas: &[AccountMeta]) -> Result<(), AccountListError> {
+    validate_accounts(metas, &SWAP_ACCOUNTS, &[9])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AmmState, SetStateInstructionData, UpdateFeeInstructionData};
+    use pinocchio::program_error::ProgramError;
+
+    #[test]
+    fn canonicalize_mints_ignores_the_order() {
+        let (low, high) = ([1; 32], [2; 32]);
+        assert_eq!(canonicalize_mints(low, high), (low, high));
+        assert_eq!(canonicalize_mints(high, low), (low, high));
+    }
+
+    #[test]
+    fn get_pools_by_mints_canonicalizes() {
+        let (low, high) = ([1; 32], [2; 32]);
+        let pools: Vec<_> = get_pools_by_mints(&crate::ID, &[1, 2], &high, &low).collect();
+
+        let expected = get_pool_by_mints(&crate::ID, 1, &low, &high);
+        assert_eq!(pools[0].config, expected.config);
+        assert_eq!(pools[0].mint_lp, expected.mint_lp);
+        assert_ne!(pools[1].config, expected.config);
+    }
+
+    #[test]
+    fn update_fee_data_round_trips() {
+        let data = update_fee_data(250);
+        assert_eq!(data[0], 18);
+
+        let parsed = UpdateFeeInstructionData::try_from(&data[1..]).unwrap();
+        assert_eq!(parsed.fee, 250);
+    }
+
+    #[test]
+    fn admin_set_state_data_round_trips() {
+        let data = admin_set_state_data(AmmState::WithdrawOnly as u8);
+        assert_eq!(data[0], 22);
+
+        let parsed = SetStateInstructionData::try_from(&data[1..]).unwrap();
+        assert_eq!(parsed.state, AmmState::WithdrawOnly as u8);
+    }
+
+    #[test]
+    fn admin_set_state_data_rejects_reserved_states() {
+        for state in [AmmState::Uninitialized, AmmState::Migrated] {
+            let data = admin_set_state_data(state as u8);
+            assert!(matches!(
+                SetStateInstructionData::try_from(&data[1..]),
+                Err(ProgramError::InvalidInstructionData)
+            ));
+        }
+    }
+
+    /// The metas the validators expect, one writable or not per slot
+    fn metas<'a>(key: &'a Pubkey, slots: &[Slot]) -> Vec<AccountMeta<'a>> {
+        slots
+            .iter()
+            .map(|Slot(_, signer, writable)| AccountMeta::new(key, *writable, *signer))
+            .collect()
+    }
+
+    #[test]
+    fn validators_accept_every_accepted_count() {
+        let key = [7; 32];
+
+        for count in [10, 12, 13] {
+            let list = metas(&key, &DEPOSIT_ACCOUNTS[..count]);
+            assert_eq!(validate_deposit_accounts(&list), Ok(()));
+        }
+        for count in [10, 11] {
+            let list = metas(&key, &WITHDRAW_ACCOUNTS[..count]);
+            assert_eq!(validate_withdraw_accounts(&list), Ok(()));
+        }
+        assert_eq!(validate_swap_accounts(&metas(&key, &SWAP_ACCOUNTS)), Ok(()));
+    }
+
+    #[test]
+    fn validators_reject_a_wrong_count() {
+        let key = [7; 32];
+
+        let list = metas(&key, &DEPOSIT_ACCOUNTS[..11]);
+        assert_eq!(
+            validate_deposit_accounts(&list),
+            Err(AccountListError::Count {
+                got: 11,
+                expected: &[10, 12, 13],
+            })
+        );
+
+        let list = metas(&key, &SWAP_ACCOUNTS[..8]);
+        assert!(matches!(
+            validate_swap_accounts(&list),
+            Err(AccountListError::Count { got: 8, .. })
+        ));
+    }
+
+    #[test]
+    fn validators_name_the_misplaced_account() {
+        let key = [7; 32];
+
+        // user and config swapped: the user slot doesn't sign
+        let mut list = metas(&key, &DEPOSIT_ACCOUNTS);
+        list.swap(0, 7);
+        assert_eq!(
+            validate_deposit_accounts(&list),
+            Err(AccountListError::MissingSigner {
+                index: 0,
+                role: "user",
+            })
+        );
+
+        // token_program and vault_x swapped: the vault slot is read-only
+        let mut list = metas(&key, &WITHDRAW_ACCOUNTS);
+        list.swap(2, 8);
+        assert_eq!(
+            validate_withdraw_accounts(&list),
+            Err(AccountListError::NotWritable {
+                index: 2,
+                role: "vault_x",
+            })
+        );
+
+        // mint_lp and config swapped: config is read-only
+        let mut list = metas(&key, &SWAP_ACCOUNTS);
+        list.swap(5, 6);
+        assert_eq!(
+            validate_swap_accounts(&list),
+            Err(AccountListError::NotWritable {
+                index: 6,
+                role: "config",
+            })
+        );
+    }
+
+    #[test]
+    fn account_list_errors_read_well() {
+        let err = AccountListError::MissingSigner {
+            index: 0,
+            role: "user",
+        };
+        assert_eq!(err.to_string(), "account 0 (user) must be a signer");
+    }
+}
@@ -0,0 +1,127 @@
+use crate::AmmError;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::Pubkey;
+use pinocchio::ProgramResult;
+
+// Sysvar1nstructions1111111111111111111111111
+pub const INSTRUCTIONS_SYSVAR_ID: Pubkey = [
+    0x06, 0xa7, 0xd5, 0x17, 0x18, 0x7b, 0xd1, 0x66, 0x35, 0xda, 0xd4, 0x04, 0x55, 0xfd, 0xc2, 0xc0,
+    0xc1, 0x24, 0xc6, 0x8f, 0x21, 0x56, 0x75, 0xa5, 0xdb, 0xba, 0xcb, 0x5f, 0x08, 0x00, 0x00, 0x00,
+];
+
+// Ed25519SigVerify111111111111111111111111111
+pub const ED25519_PROGRAM_ID: Pubkey = [
+    0x03, 0x7d, 0x46, 0xd6, 0x7c, 0x93, 0xfb, 0xbe, 0x12, 0xf9, 0x42, 0x8f, 0x83, 0x8d, 0x40, 0xff,
+    0x05, 0x70, 0x74, 0x49, 0x27, 0xf4, 0x8a, 0x64, 0xfc, 0xca, 0x70, 0x44, 0x80, 0x00, 0x00, 0x00,
+];
+
+/// Instruction index the Ed25519 program uses to refer to its own data
+const CURRENT_INSTRUCTION: u16 = u16::MAX;
+
+/// Size of the `Ed25519SignatureOffsets` header, 7 u16 fields
+const SIGNATURE_OFFSETS_LEN: usize = 14;
+
+#[inline(always)]
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, ProgramError> {
+    let bytes = data
+        .get(offset..offset + 2)
+        .ok_or(ProgramError::InvalidAccountData)?;
+    Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+/// Returns the program id and data of the instruction at `index`
+/// from the raw instructions sysvar
+fn instruction_at(data: &[u8], index: u16) -> Result<(&[u8], &[u8]), ProgramError> {
+    if index >= read_u16(data, 0)? {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let mut offset = read_u16(data, 2 + index as usize * 2)? as usize;
+
+    // Skip the account metas, a flags byte and a pubkey each
+    let num_accounts = read_u16(data, offset)? as usize;
+    offset += 2 + num_accounts * (1 + size_of::<Pubkey>());
+
+    let program_id = data
+        .get(offset..offset + size_of::<Pubkey>())
+        .ok_or(ProgramError::InvalidAccountData)?;
+    offset += size_of::<Pubkey>();
+
+    let data_len = read_u16(data, offset)? as usize;
+    offset += 2;
+
+    let ix_data = data
+        .get(offset..offset + data_len)
+        .ok_or(ProgramError::InvalidAccountData)?;
+
+    Ok((program_id, ix_data))
+}
+
+/// Checks that the instruction right before the current one verifies,
+/// through the Ed25519 program, a single signature of `message` by `signer`,
+/// with all of its inputs embedded in its own data.
+pub fn verify_preceding_ed25519(
+    instructions: &AccountInfo,
+    signer: &Pubkey,
+    message: &[u8],
+) -> ProgramResult {
+    if instructions.key().ne(&INSTRUCTIONS_SYSVAR_ID) {
+        return Err(ProgramError::UnsupportedSysvar);
+    }
+
+    let data = instructions.try_borrow_data()?;
+
+    // The current instruction index is stored in the last two bytes
+    let current = read_u16(
+        &data,
+        data.len()
+            .checked_sub(2)
+            .ok_or(ProgramError::InvalidAccountData)?,
+    )?;
+
+    let previous = current
+        .checked_sub(1)
+        .ok_or(AmmError::InvalidIntentSignature)?;
+
+    let (program_id, ix_data) = instruction_at(&data, previous)?;
+
+    if program_id.ne(&ED25519_PROGRAM_ID) {
+        return Err(AmmError::InvalidIntentSignature.into());
+    }
+
+    // num_signatures, padding, then the offsets
+    if ix_data.len() < 2 + SIGNATURE_OFFSETS_LEN || ix_data[0] != 1 {
+        return Err(AmmError::InvalidIntentSignature.into());
+    }
+
+    let signature_instruction_index = read_u16(ix_data, 4)?;
+    let public_key_offset = read_u16(ix_data, 6)? as usize;
+    let public_key_instruction_index = read_u16(ix_data, 8)?;
+    let message_data_offset = read_u16(ix_data, 10)? as usize;
+    let message_data_size = read_u16(ix_data, 12)? as usize;
+    let message_instruction_index = read_u16(ix_data, 14)?;
+
+    // Everything must live in the Ed25519 instruction itself, so that
+    // what was verified is exactly what is compared below
+    if signature_instruction_index != CURRENT_INSTRUCTION
+        || public_key_instruction_index != CURRENT_INSTRUCTION
+        || message_instruction_index != CURRENT_INSTRUCTION
+    {
+        return Err(AmmError::InvalidIntentSignature.into());
+    }
+
+    let public_key = ix_data
+        .get(public_key_offset..public_key_offset + size_of::<Pubkey>())
+        .ok_or(AmmError::InvalidIntentSignature)?;
+
+    let signed_message = ix_data
+        .get(message_data_offset..message_data_offset + message_data_size)
+        .ok_or(AmmError::InvalidIntentSignature)?;
+
+    if public_key.ne(signer) || signed_message.ne(message) {
+        return Err(AmmError::InvalidIntentSignature.into());
+    }
+
+    Ok(())
+}
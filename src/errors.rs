@@ -0,0 +1,325 @@
+use constant_product_curve::CurveError;
+#[cfg(feature = "return-data")]
+use pinocchio::cpi::set_return_data;
+use pinocchio::program_error::ProgramError;
+
+/// Program specific errors, surfaced to clients as `ProgramError::Custom(code)`.
+/// New variants are only ever appended so existing codes stay stable.
+#[repr(u32)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AmmError {
+    /// Instruction data is malformed or non-canonical
+    InvalidInstructionData = 0,
+    /// Swap moves more than the allowed share of a reserve
+    SwapExceedsReserveLimit = 1,
+    /// LP mint account passed to Initialize already holds data
+    MintAlreadyExists = 2,
+    /// Liquidity is locked until `Config::locked_until`
+    LiquidityLocked = 3,
+    /// `locked_until` can only be extended
+    LockCannotBeShortened = 4,
+    /// The preceding Ed25519 instruction doesn't verify the expected intent
+    InvalidIntentSignature = 5,
+    /// The signer isn't a delegate of the input token account, or not for enough
+    InvalidDelegate = 6,
+    /// The nonce doesn't match the next expected one
+    NonceMismatch = 7,
+    /// The operation is switched off in `Config::feature_flags`
+    FeatureDisabled = 8,
+    /// The same account was passed for two different roles
+    DuplicateAccount = 9,
+    /// Amount is below the pool's dust floor
+    AmountTooSmall = 10,
+    /// A pre-existing account at a PDA to be created can't be adopted
+    ConfigPrefunded = 11,
+    /// Swap output is below the requested minimum
+    SlippageExceeded = 12,
+    /// Target reserve ratio can't be reached without draining a reserve
+    RatioUnreachable = 13,
+    /// Timestamp before the unix epoch or outside the i64 range
+    InvalidTimestamp = 14,
+    /// Every pool is paused by the protocol admin
+    ProtocolPaused = 15,
+    /// Swap input exceeds the allowance delegated to the signing authority
+    InsufficientDelegation = 16,
+    /// Swap would leave the pool price outside the authority-set band
+    PriceOutOfBand = 17,
+    /// LP mint decimals differ from the ones recorded in the config
+    DecimalMismatch = 18,
+    /// Swap `min` is looser than the slippage protection the pool requires
+    SlippageProtectionRequired = 19,
+    /// Pool mints must be passed in canonical order, `mint_x < mint_y`
+    MintOrder = 20,
+    /// Vault balances are inconsistent with the LP supply or tracked reserves
+    StaleReserves = 21,
+    /// The flash swap callback didn't repay the input to the vault
+    FlashSwapNotRepaid = 22,
+    /// LP supply didn't move by exactly the minted or burned amount
+    SupplyMismatch = 23,
+    /// A vault received a different amount than was transferred to it
+    TransferShortfall = 24,
+    /// User token account holds less than the amount to transfer
+    InsufficientUserBalance = 25,
+    /// A token account to move funds through is frozen
+    AccountFrozen = 26,
+    /// The pair already has as many pools as the global config allows
+    PoolLimitReached = 27,
+    /// The curve overflowed, amounts too large for the reserves
+    CurveOverflow = 28,
+    /// The curve underflowed, amounts too small for the reserves
+    CurveUnderflow = 29,
+    /// The curve cannot price at the requested precision
+    CurvePrecisionLoss = 30,
+    /// Deposit needs more than the `max_x` or `max_y` allowed by the user
+    DepositSlippageExceeded = 31,
+    /// The instruction has no expiration but the pool requires one
+    DeadlineRequired = 32,
+    /// The Rent sysvar could not be read
+    RentSysvarUnavailable = 33,
+    /// A mint account is not an initialized mint of the token program, or not the pool mint
+    InvalidMint = 34,
+    /// The pool is disabled and rejects every instruction
+    PoolDisabled = 35,
+    /// The curve rejected the operation for another reason
+    CurveFailed = 36,
+    /// Amount is not a multiple of the pool lot size
+    InvalidLotSize = 37,
+    /// The same mint was passed as both mint_x and mint_y
+    IdenticalMints = 38,
+    /// A token account is not owned by the token program passed to the instruction
+    TokenProgramMismatch = 39,
+    /// Switching the curve of a live pool would move its price beyond the tolerance
+    CurveSwitchUnsafe = 40,
+    /// The config account still has the layout of the first release, see `MigrateConfig`
+    ConfigNeedsMigration = 41,
+}
+
+impl TryFrom<u32> for AmmError {
+    type Error = ProgramError;
+
+    fn try_from(code: u32) -> Result<Self, Self::Error> {
+        match code {
+            0 => Ok(AmmError::InvalidInstructionData),
+            1 => Ok(AmmError::SwapExceedsReserveLimit),
+            2 => Ok(AmmError::MintAlreadyExists),
+            3 => Ok(AmmError::LiquidityLocked),
+            4 => Ok(AmmError::LockCannotBeShortened),
+            5 => Ok(AmmError::InvalidIntentSignature),
+            6 => Ok(AmmError::InvalidDelegate),
+            7 => Ok(AmmError::NonceMismatch),
+            8 => Ok(AmmError::FeatureDisabled),
+            9 => Ok(AmmError::DuplicateAccount),
+            10 => Ok(AmmError::AmountTooSmall),
+            11 => Ok(AmmError::ConfigPrefunded),
+            12 => Ok(AmmError::SlippageExceeded),
+            13 => Ok(AmmError::RatioUnreachable),
+            14 => Ok(AmmError::InvalidTimestamp),
+            15 => Ok(AmmError::ProtocolPaused),
+            16 => Ok(AmmError::InsufficientDelegation),
+            17 => Ok(AmmError::PriceOutOfBand),
+            18 => Ok(AmmError::DecimalMismatch),
+            19 => Ok(AmmError::SlippageProtectionRequired),
+            20 => Ok(AmmError::MintOrder),
+            21 => Ok(AmmError::StaleReserves),
+            22 => Ok(AmmError::FlashSwapNotRepaid),
+            23 => Ok(AmmError::SupplyMismatch),
+            24 => Ok(AmmError::TransferShortfall),
+            25 => Ok(AmmError::InsufficientUserBalance),
+            26 => Ok(AmmError::AccountFrozen),
+            27 => Ok(AmmError::PoolLimitReached),
+            28 => Ok(AmmError::CurveOverflow),
+            29 => Ok(AmmError::CurveUnderflow),
+            30 => Ok(AmmError::CurvePrecisionLoss),
+            31 => Ok(AmmError::DepositSlippageExceeded),
+            32 => Ok(AmmError::DeadlineRequired),
+            33 => Ok(AmmError::RentSysvarUnavailable),
+            34 => Ok(AmmError::InvalidMint),
+            35 => Ok(AmmError::PoolDisabled),
+            36 => Ok(AmmError::CurveFailed),
+            37 => Ok(AmmError::InvalidLotSize),
+            38 => Ok(AmmError::IdenticalMints),
+            39 => Ok(AmmError::TokenProgramMismatch),
+            40 => Ok(AmmError::CurveSwitchUnsafe),
+            41 => Ok(AmmError::ConfigNeedsMigration),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+}
+
+impl AmmError {
+    /// Variant name, for logs
+    pub fn name(&self) -> &'static str {
+        match self {
+            AmmError::InvalidInstructionData => "InvalidInstructionData",
+            AmmError::SwapExceedsReserveLimit => "SwapExceedsReserveLimit",
+            AmmError::MintAlreadyExists => "MintAlreadyExists",
+            AmmError::LiquidityLocked => "LiquidityLocked",
+            AmmError::LockCannotBeShortened => "LockCannotBeShortened",
+            AmmError::InvalidIntentSignature => "InvalidIntentSignature",
+            AmmError::InvalidDelegate => "InvalidDelegate",
+            AmmError::NonceMismatch => "NonceMismatch",
+            AmmError::FeatureDisabled => "FeatureDisabled",
+            AmmError::DuplicateAccount => "DuplicateAccount",
+            AmmError::AmountTooSmall => "AmountTooSmall",
+            AmmError::ConfigPrefunded => "ConfigPrefunded",
+            AmmError::SlippageExceeded => "SlippageExceeded",
+            AmmError::RatioUnreachable => "RatioUnreachable",
+            AmmError::InvalidTimestamp => "InvalidTimestamp",
+            AmmError::ProtocolPaused => "ProtocolPaused",
+            AmmError::InsufficientDelegation => "InsufficientDelegation",
+            AmmError::PriceOutOfBand => "PriceOutOfBand",
+            AmmError::DecimalMismatch => "DecimalMismatch",
+            AmmError::SlippageProtectionRequired => "SlippageProtectionRequired",
+            AmmError::MintOrder => "MintOrder",
+            AmmError::StaleReserves => "StaleReserves",
+            AmmError::FlashSwapNotRepaid => "FlashSwapNotRepaid",
+            AmmError::SupplyMismatch => "SupplyMismatch",
+            AmmError::TransferShortfall => "TransferShortfall",
+            AmmError::InsufficientUserBalance => "InsufficientUserBalance",
+            AmmError::AccountFrozen => "AccountFrozen",
+            AmmError::PoolLimitReached => "PoolLimitReached",
+            AmmError::CurveOverflow => "CurveOverflow",
+            AmmError::CurveUnderflow => "CurveUnderflow",
+            AmmError::CurvePrecisionLoss => "CurvePrecisionLoss",
+            AmmError::DepositSlippageExceeded => "DepositSlippageExceeded",
+            AmmError::DeadlineRequired => "DeadlineRequired",
+            AmmError::RentSysvarUnavailable => "RentSysvarUnavailable",
+            AmmError::InvalidMint => "InvalidMint",
+            AmmError::PoolDisabled => "PoolDisabled",
+            AmmError::CurveFailed => "CurveFailed",
+            AmmError::InvalidLotSize => "InvalidLotSize",
+            AmmError::IdenticalMints => "IdenticalMints",
+            AmmError::TokenProgramMismatch => "TokenProgramMismatch",
+            AmmError::CurveSwitchUnsafe => "CurveSwitchUnsafe",
+            AmmError::ConfigNeedsMigration => "ConfigNeedsMigration",
+        }
+    }
+}
+
+/// Logs the failed instruction discriminator and the `AmmError` variant behind
+/// a custom error code, compiled out without the `debug-errors` feature
+macro_rules! log_error {
+    ($discriminator:expr, $result:expr) => {{
+        #[cfg(feature = "debug-errors")]
+        if let Err(pinocchio::program_error::ProgramError::Custom(code)) = $result {
+            if let Ok(error) = $crate::AmmError::try_from(*code) {
+                pinocchio_log::log!(
+                    "instruction {} failed: AmmError::{}",
+                    $discriminator,
+                    error.name()
+                );
+            }
+        }
+
+        #[cfg(not(feature = "debug-errors"))]
+        let _ = ($discriminator, $result);
+    }};
+}
+pub(crate) use log_error;
+
+impl From<AmmError> for ProgramError {
+    fn from(e: AmmError) -> Self {
+        ProgramError::Custom(e as u32)
+    }
+}
+
+/// Keeps the cause of the curve errors clients can act on,
+/// the others share `AmmError::CurveFailed`
+impl From<CurveError> for AmmError {
+    fn from(e: CurveError) -> Self {
+        match e {
+            CurveError::Overflow => AmmError::CurveOverflow,
+            CurveError::Underflow => AmmError::CurveUnderflow,
+            CurveError::InvalidPrecision => AmmError::CurvePrecisionLoss,
+            _ => AmmError::CurveFailed,
+        }
+    }
+}
+
+/// What the numbers of an error detail refer to
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ErrorDetailKind {
+    /// Computed amount against the slippage bound requested by the user
+    Slippage = 0,
+    /// Current timestamp against the expiration, both as `i64` bits
+    Deadline = 1,
+    /// Amount moved against the reserve percentage limit
+    ReserveLimit = 2,
+    /// Swap input against the allowance delegated to the authority
+    Delegation = 3,
+    /// Position of the frozen account in the instruction's check list, against 0
+    FrozenAccount = 4,
+    /// Deposit amount of X against `max_x`
+    DepositSlippageX = 5,
+    /// Deposit amount of Y against `max_y`
+    DepositSlippageY = 6,
+}
+
+impl TryFrom<u8> for ErrorDetailKind {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ErrorDetailKind::Slippage),
+            1 => Ok(ErrorDetailKind::Deadline),
+            2 => Ok(ErrorDetailKind::ReserveLimit),
+            3 => Ok(ErrorDetailKind::Delegation),
+            4 => Ok(ErrorDetailKind::FrozenAccount),
+            5 => Ok(ErrorDetailKind::DepositSlippageX),
+            6 => Ok(ErrorDetailKind::DepositSlippageY),
+            _ => Err(ProgramError::InvalidArgument),
+        }
+    }
+}
+
+/// Numbers behind a rejection, written to the return data under the
+/// `return-data` feature so front-ends can show what went wrong
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ErrorDetail {
+    pub kind: ErrorDetailKind,
+    pub computed: u64,
+    pub bound: u64,
+}
+
+impl ErrorDetail {
+    pub const LEN: usize = size_of::<u8>() + size_of::<u64>() * 2;
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut data = [0u8; Self::LEN];
+        data[0] = self.kind as u8;
+        data[1..9].copy_from_slice(&self.computed.to_le_bytes());
+        data[9..17].copy_from_slice(&self.bound.to_le_bytes());
+        data
+    }
+
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN {
+            return None;
+        }
+
+        Some(Self {
+            kind: ErrorDetailKind::try_from(data[0]).ok()?,
+            computed: u64::from_le_bytes(data[1..9].try_into().ok()?),
+            bound: u64::from_le_bytes(data[9..17].try_into().ok()?),
+        })
+    }
+}
+
+/// Reports the detail of an upcoming rejection, compiled out
+/// without the `return-data` feature
+#[inline(always)]
+pub fn report_error_detail(kind: ErrorDetailKind, computed: u64, bound: u64) {
+    #[cfg(feature = "return-data")]
+    set_return_data(
+        &ErrorDetail {
+            kind,
+            computed,
+            bound,
+        }
+        .to_bytes(),
+    );
+
+    #[cfg(not(feature = "return-data"))]
+    let _ = (kind, computed, bound);
+}
@@ -0,0 +1,134 @@
+use pinocchio::log::sol_log_data;
+
+/// Emitted on every swap through `sol_log_data`
+pub struct SwapEvent {
+    /// Whether token X was swapped in
+    pub is_x: bool,
+    /// Principal of the input, the part priced by the curve
+    pub amount_in: u64,
+    /// Fee credited to the reserves as an LP rebate, in the output token
+    /// for pools taking the fee on the output, in the input token otherwise
+    pub fee: u64,
+    /// Amount sent out to the user
+    pub amount_out: u64,
+    /// Tracked X reserve once the swap settled, see `Config::lp_reserves`
+    pub reserve_x_after: u64,
+    /// Tracked Y reserve once the swap settled
+    pub reserve_y_after: u64,
+}
+
+impl SwapEvent {
+    pub const DISCRIMINATOR: u8 = 0;
+
+    pub const LEN: usize = size_of::<u8>() + size_of::<u8>() + size_of::<u64>() * 5;
+
+    pub fn to_bytes(&self) -> [u8; Self::LEN] {
+        let mut data = [0u8; Self::LEN];
+        data[0] = Self::DISCRIMINATOR;
+        data[1] = self.is_x as u8;
+        data[2..10].copy_from_slice(&self.amount_in.to_le_bytes());
+        data[10..18].copy_from_slice(&self.fee.to_le_bytes());
+        data[18..26].copy_from_slice(&self.amount_out.to_le_bytes());
+        data[26..34].copy_from_slice(&self.reserve_x_after.to_le_bytes());
+        data[34..42].copy_from_slice(&self.reserve_y_after.to_le_bytes());
+        data
+    }
+
+    /// Decodes an event previously logged by `emit`
+    pub fn decode(data: &[u8]) -> Option<Self> {
+        if data.len() != Self::LEN || data[0] != Self::DISCRIMINATOR || data[1] > 1 {
+            return None;
+        }
+
+        Some(Self {
+            is_x: data[1] == 1,
+            amount_in: u64::from_le_bytes(data[2..10].try_into().ok()?),
+            fee: u64::from_le_bytes(data[10..18].try_into().ok()?),
+            amount_out: u64::from_le_bytes(data[18..26].try_into().ok()?),
+            reserve_x_after: u64::from_le_bytes(data[26..34].try_into().ok()?),
+            reserve_y_after: u64::from_le_bytes(data[34..42].try_into().ok()?),
+        })
+    }
+
+    /// Price of X in Y left by the swap, Q32.32 like the pool price band,
+    /// `None` for an empty X side or a price beyond the u64 range
+    pub fn spot_price_after(&self) -> Option<u64> {
+        if self.reserve_x_after == 0 {
+            return None;
+        }
+        u64::try_from(((self.reserve_y_after as u128) << 32) / self.reserve_x_after as u128).ok()
+    }
+
+    #[inline(always)]
+    pub fn emit(&self) {
+        sol_log_data(&[&self.to_bytes()]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event() -> SwapEvent {
+        SwapEvent {
+            is_x: true,
+            amount_in: 99_700,
+            fee: 300,
+            amount_out: 362_644,
+            reserve_x_after: 10_100_000,
+            reserve_y_after: 39_637_356,
+        }
+    }
+
+    #[test]
+    fn swap_event_round_trips() {
+        let data = event().to_bytes();
+        let decoded = SwapEvent::decode(&data).unwrap();
+
+        assert!(decoded.is_x);
+        assert_eq!(decoded.amount_in, 99_700);
+        assert_eq!(decoded.fee, 300);
+        assert_eq!(decoded.amount_out, 362_644);
+        // The reserves close the event
+        assert_eq!(
+            data[SwapEvent::LEN - 16..SwapEvent::LEN - 8],
+            10_100_000u64.to_le_bytes()
+        );
+        assert_eq!(data[SwapEvent::LEN - 8..], 39_637_356u64.to_le_bytes());
+        assert_eq!(decoded.reserve_x_after, 10_100_000);
+        assert_eq!(decoded.reserve_y_after, 39_637_356);
+    }
+
+    #[test]
+    fn swap_event_rejects_other_data() {
+        let data = event().to_bytes();
+        assert!(SwapEvent::decode(&data[..SwapEvent::LEN - 1]).is_none());
+
+        let mut other = data;
+        other[0] = 1;
+        assert!(SwapEvent::decode(&other).is_none());
+        let mut other = data;
+        other[1] = 2;
+        assert!(SwapEvent::decode(&other).is_none());
+    }
+
+    #[test]
+    fn spot_price_after_is_q32() {
+        // 39_637_356 / 10_100_000 in Q32.32, rounded down
+        let price = event().spot_price_after().unwrap();
+        assert_eq!(price, ((39_637_356u128 << 32) / 10_100_000) as u64);
+        assert_eq!(price >> 32, 3);
+
+        let empty = SwapEvent {
+            reserve_x_after: 0,
+            ..event()
+        };
+        assert_eq!(empty.spot_price_after(), None);
+        let beyond = SwapEvent {
+            reserve_x_after: 1,
+            reserve_y_after: u64::MAX,
+            ..event()
+        };
+        assert_eq!(beyond.spot_price_after(), None);
+    }
+}
@@ -0,0 +1,74 @@
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction completes the authority transfer started by
+// `NominateAuthority`: the nominee signs to become the pool authority,
+// proving the key is usable before the old authority loses control.
+
+pub struct AcceptAuthorityAccounts<'a> {
+    /// Must be the pending authority of the config
+    pub pending_authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for AcceptAuthorityAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [pending_authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !pending_authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self {
+            pending_authority,
+            config,
+        })
+    }
+}
+
+pub struct AcceptAuthority<'a> {
+    pub accounts: AcceptAuthorityAccounts<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for AcceptAuthority<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        if !data.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let accounts = AcceptAuthorityAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> AcceptAuthority<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &20;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        // Also rejects when nobody was nominated, the pending key is zeroed
+        if config
+            .pending_authority()
+            .ne(self.accounts.pending_authority.key())
+        {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        config.set_authority(*self.accounts.pending_authority.key())?;
+        config.clear_pending_authority();
+
+        Ok(())
+    }
+}
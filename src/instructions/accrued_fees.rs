@@ -0,0 +1,75 @@
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::set_return_data;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This read-only instruction returns the swap fees the pool accrued since
+// inception, `(total_fee_x, total_fee_y)`, through the return data.
+// Needs no signer.
+
+pub struct AccruedFeesAccounts<'a> {
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for AccruedFeesAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        Ok(Self { config })
+    }
+}
+
+pub struct AccruedFees<'a> {
+    pub accounts: AccruedFeesAccounts<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for AccruedFees<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        if !data.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let accounts = AccruedFeesAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> AccruedFees<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &16;
+
+    pub const RETURN_DATA_LEN: usize = size_of::<u64>() * 2;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        let mut data = [0u8; Self::RETURN_DATA_LEN];
+        data[0..8].copy_from_slice(&config.total_fee_x().to_le_bytes());
+        data[8..16].copy_from_slice(&config.total_fee_y().to_le_bytes());
+
+        set_return_data(&data);
+
+        Ok(())
+    }
+}
+
+/// Decodes the `(total_fee_x, total_fee_y)` returned by `AccruedFees`
+pub fn decode_accrued_fees(data: &[u8]) -> Option<(u64, u64)> {
+    if data.len() != AccruedFees::RETURN_DATA_LEN {
+        return None;
+    }
+
+    Some((
+        u64::from_le_bytes(data[0..8].try_into().ok()?),
+        u64::from_le_bytes(data[8..16].try_into().ok()?),
+    ))
+}
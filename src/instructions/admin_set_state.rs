@@ -0,0 +1,91 @@
+use crate::{AmmState, Config};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority move the pool between
+// `Initialized`, `WithdrawOnly` and `Disabled`, e.g. to stop trading
+// during an incident while still letting LPs withdraw.
+// Unlike the other authority instructions it also runs on a disabled
+// pool, so that the pool can be re-enabled. The old config of a migrated
+// pool is `Migrated` instead, which it can neither set nor leave.
+// Its discriminator is 22: the 5 it was first specified with was already
+// taken by `UpdateConfig`.
+
+pub struct AdminSetStateAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for AdminSetStateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct SetStateInstructionData {
+    /// `AmmState`, anything but `Uninitialized` and `Migrated`
+    pub state: u8,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetStateInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let [state] = data else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        // Going back to `Uninitialized` would let the config be initialized again,
+        // and only `MigrateSeed` tombstones a config
+        match AmmState::try_from(*state) {
+            Ok(AmmState::Uninitialized | AmmState::Migrated) | Err(_) => {
+                Err(ProgramError::InvalidInstructionData)
+            }
+            Ok(_) => Ok(Self { state: *state }),
+        }
+    }
+}
+
+pub struct AdminSetState<'a> {
+    pub accounts: AdminSetStateAccounts<'a>,
+    pub instruction_data: SetStateInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for AdminSetState<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = AdminSetStateAccounts::try_from(accounts)?;
+        let instruction_data = SetStateInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> AdminSetState<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &22;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        config.set_state(self.instruction_data.state)
+    }
+}
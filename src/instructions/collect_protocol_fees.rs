@@ -0,0 +1,155 @@
+use crate::utils::{ensure_not_frozen, ensure_spl_token_program};
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::instruction::{Seed, Signer};
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::instructions::Transfer;
+use pinocchio_token::state::TokenAccount;
+
+// This instruction lets the pool authority sweep the protocol share of
+// the swap fees out of the vaults, into token accounts of its choice
+// holding the pool mints. The protocol fees are whatever the vaults hold
+// above the tracked reserves, so direct donations to the vaults are
+// swept along with them.
+
+pub struct CollectProtocolFeesAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    pub vault_x: &'a AccountInfo,
+    pub vault_y: &'a AccountInfo,
+    /// Receive the X and Y fees, token accounts of the pool mints
+    pub destination_x: &'a AccountInfo,
+    pub destination_y: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for CollectProtocolFeesAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config, vault_x, vault_y, destination_x, destination_y, token_program] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        ensure_spl_token_program(token_program)?;
+
+        Ok(Self {
+            authority,
+            config,
+            vault_x,
+            vault_y,
+            destination_x,
+            destination_y,
+            token_program,
+        })
+    }
+}
+
+pub struct CollectProtocolFees<'a> {
+    pub accounts: CollectProtocolFeesAccounts<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for CollectProtocolFees<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        if !data.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let accounts = CollectProtocolFeesAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> CollectProtocolFees<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &23;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        // Check the vaults are the ATAs of the config
+        let (vault_x, vault_y) = config.vault_keys(
+            self.accounts.config.key(),
+            self.accounts.token_program.key(),
+        )?;
+
+        if vault_x.ne(self.accounts.vault_x.key()) || vault_y.ne(self.accounts.vault_y.key()) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Destinations must hold the pool mints, checks their owner and length
+        for (destination, mint) in [
+            (self.accounts.destination_x, config.mint_x()),
+            (self.accounts.destination_y, config.mint_y()),
+        ] {
+            if TokenAccount::from_account_info(destination)?
+                .mint()
+                .ne(mint)
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        let (fee_x, fee_y) = config.protocol_fees(
+            TokenAccount::from_account_info(self.accounts.vault_x)?.amount(),
+            TokenAccount::from_account_info(self.accounts.vault_y)?.amount(),
+        );
+
+        let seed_binding = config.seed().to_le_bytes();
+        let config_bump_binding = config.config_bump();
+        let mint_x = *config.mint_x();
+        let mint_y = *config.mint_y();
+        drop(config);
+
+        let config_seeds = [
+            Seed::from(b"config"),
+            Seed::from(&seed_binding),
+            Seed::from(&mint_x),
+            Seed::from(&mint_y),
+            Seed::from(&config_bump_binding),
+        ];
+        let signer = [Signer::from(&config_seeds)];
+
+        // Fail a frozen account clearly rather than inside a transfer
+        ensure_not_frozen(&[
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+            self.accounts.destination_x,
+            self.accounts.destination_y,
+        ])?;
+
+        for (vault, destination, amount) in [
+            (self.accounts.vault_x, self.accounts.destination_x, fee_x),
+            (self.accounts.vault_y, self.accounts.destination_y, fee_y),
+        ] {
+            if amount == 0 {
+                continue;
+            }
+
+            Transfer {
+                from: vault,
+                to: destination,
+                authority: self.accounts.config,
+                amount,
+            }
+            .invoke_signed(&signer)?;
+        }
+
+        Ok(())
+    }
+}
@@ -1,12 +1,24 @@
-use crate::{AmmState, Config};
-use constant_product_curve::ConstantProduct;
+#[cfg(feature = "exact-bootstrap-lp")]
+use crate::math::{bootstrap_lp, MINIMUM_LIQUIDITY};
+use crate::math::{deposit_amounts, deposit_lp_for_max};
+#[cfg(feature = "strict-invariants")]
+use crate::utils::ensure_supply;
+use crate::utils::{
+    ensure_distinct_accounts, ensure_not_frozen, ensure_received, ensure_spl_token_program,
+    read_array,
+};
+use crate::{
+    check_deadline, report_error_detail, AmmError, AmmState, ClockTimeSource, Config,
+    ErrorDetailKind, GlobalConfig, TimeSource, NO_DEADLINE,
+};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use pinocchio::account_info::AccountInfo;
 use pinocchio::instruction::{Seed, Signer};
 use pinocchio::program_error::ProgramError;
 use pinocchio::pubkey::find_program_address;
-use pinocchio::sysvars::clock::Clock;
-use pinocchio::sysvars::Sysvar;
 use pinocchio::ProgramResult;
+use pinocchio_associated_token_account::instructions::CreateIdempotent;
 use pinocchio_token::instructions::{MintTo, Transfer};
 use pinocchio_token::state::{Mint, TokenAccount};
 
@@ -20,24 +32,51 @@ pub struct DepositAccounts<'a> {
     pub user_lp_ata: &'a AccountInfo,
     pub config: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
+    /// Protocol global config, checked for the global pause
+    pub global_config: &'a AccountInfo,
+    /// Optional system program, when passed together with the ATA program
+    /// the user LP ATA is created idempotently before minting
+    pub system_program: Option<&'a AccountInfo>,
+    pub associated_token_program: Option<&'a AccountInfo>,
+    /// Optional LP ATA of the config, holding the `MINIMUM_LIQUIDITY` locked
+    /// by the first deposit under `exact-bootstrap-lp`. Needs the two above.
+    pub lp_lock: Option<&'a AccountInfo>,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for DepositAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program] =
+        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program, global_config, rest @ ..] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
-        // Basic Accounts Checks
-        // SignerAccount::check(initializer)?;
-        // MintInterface::check(mint_lp)?;
+        let (system_program, associated_token_program, lp_lock) = match rest {
+            [] => (None, None, None),
+            [system_program, associated_token_program] => {
+                (Some(system_program), Some(associated_token_program), None)
+            }
+            [system_program, associated_token_program, lp_lock] => (
+                Some(system_program),
+                Some(associated_token_program),
+                Some(lp_lock),
+            ),
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+
+        // Token accounts must all be distinct, a user ATA aliasing a vault
+        // would turn transfers into no-ops
+        ensure_distinct_accounts(&[user_x_ata, user_y_ata, user_lp_ata, vault_x, vault_y])?;
 
-        // check initializer is signer
-        // but it will be checked downstream maybe?
+        // The user authorizes the input transfers, reject a missing
+        // signature here rather than through a failed CPI
+        if !user.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        ensure_spl_token_program(token_program)?;
 
         Ok(Self {
             user,
@@ -49,13 +88,19 @@ impl<'a> TryFrom<&'a [AccountInfo]> for DepositAccounts<'a> {
             user_lp_ata,
             config,
             token_program,
+            global_config,
+            system_program,
+            associated_token_program,
+            lp_lock,
         })
     }
 }
 
 #[repr(C)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub struct DepositInstructionData {
-    /// Amount the user wishes to receive
+    /// Amount the user wishes to receive, 0 to mint as much as
+    /// `max_x` and `max_y` allow at the current ratio
     pub amount: u64,
     pub max_x: u64,
     pub max_y: u64,
@@ -66,25 +111,30 @@ impl<'a> TryFrom<&'a [u8]> for DepositInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if data.len().ne(&(size_of::<u64>() * 4)) {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        Self::parse(data, &ClockTimeSource)
+    }
+}
 
-        let amount = u64::from_le_bytes(data[0..8].try_into().unwrap());
+impl DepositInstructionData {
+    /// Parses the instruction data, checking the expiration against `time`
+    pub fn parse<T: TimeSource>(data: &[u8], time: &T) -> Result<Self, ProgramError> {
+        const DATA_LEN: usize = size_of::<u64>() * 4;
 
-        if amount == 0 {
+        // The expiration is omittable, see `NO_DEADLINE`
+        if data.len().ne(&DATA_LEN) && data.len().ne(&(DATA_LEN - size_of::<i64>())) {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        let max_x = u64::from_le_bytes(data[8..16].try_into().unwrap());
-        let max_y = u64::from_le_bytes(data[16..24].try_into().unwrap());
-        let expiration = i64::from_le_bytes(data[24..32].try_into().unwrap());
+        let amount = u64::from_le_bytes(read_array(data, 0)?);
+        let max_x = u64::from_le_bytes(read_array(data, 8)?);
+        let max_y = u64::from_le_bytes(read_array(data, 16)?);
+        let expiration = match data.len() {
+            DATA_LEN => i64::from_le_bytes(read_array(data, 24)?),
+            _ => NO_DEADLINE,
+        };
 
         // Check signature expiration
-        let now = Clock::get()?.unix_timestamp;
-        if now > expiration {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        check_deadline(expiration, time)?;
 
         Ok(Self {
             amount,
@@ -119,37 +169,29 @@ impl<'a> Deposit<'a> {
     pub const DISCRIMINATOR: &'a u8 = &1;
 
     pub fn process(&mut self) -> ProgramResult {
+        GlobalConfig::check_not_paused(self.accounts.global_config)?;
+
         let config = Config::load(self.accounts.config)?;
 
+        config.require_not_disabled()?;
+
+        config.check_deadline_required(self.instruction_data.expiration)?;
+
         if config.state().ne(&(AmmState::Initialized as u8)) {
             return Err(ProgramError::InvalidAccountData);
         }
 
-        // Check if the vault_x ATA is valid
-        let (vault_x, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_x(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
-
-        if vault_x.ne(self.accounts.vault_x.key()) {
-            return Err(ProgramError::InvalidAccountData);
+        if !config.feature_enabled(Config::FEATURE_DEPOSIT) {
+            return Err(AmmError::FeatureDisabled.into());
         }
 
-        // Check if the vault_y ATA is valid
-        let (vault_y, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_y(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
-
-        if vault_y.ne(self.accounts.vault_y.key()) {
+        // Check the vaults are the ATAs of the config
+        let (vault_x, vault_y) = config.vault_keys(
+            self.accounts.config.key(),
+            self.accounts.token_program.key(),
+        )?;
+
+        if vault_x.ne(self.accounts.vault_x.key()) || vault_y.ne(self.accounts.vault_y.key()) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -158,28 +200,114 @@ impl<'a> Deposit<'a> {
         let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
         let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
 
+        // LP amounts are only meaningful at the decimals the pool was created with
+        config.check_lp_decimals(mint_lp.decimals())?;
+
+        // The LP mint of this pool, and not another one minted by the config
+        config.check_lp_mint(self.accounts.mint_lp.key(), &mint_lp)?;
+
+        // Uncollected protocol fees aren't liquidity
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+
+        let bootstrap = mint_lp.supply() == 0 && reserve_x == 0 && reserve_y == 0;
+
+        // LP to mint, derived from the binding side when not requested.
+        // The first deposit mints the geometric mean under `exact-bootstrap-lp`,
+        // whatever the requested amount.
+        let lp = match (bootstrap, self.instruction_data.amount) {
+            #[cfg(feature = "exact-bootstrap-lp")]
+            (true, _) => bootstrap_lp(self.instruction_data.max_x, self.instruction_data.max_y)?,
+            // Within the maxes once the amounts are rounded up to the lot size
+            (_, 0) => deposit_lp_for_max(
+                reserve_x,
+                reserve_y,
+                mint_lp.supply(),
+                config.lot_floor(self.instruction_data.max_x),
+                config.lot_floor(self.instruction_data.max_y),
+            )?,
+            (_, amount) => amount,
+        };
+
+        // LP locked on top of the user's by the first deposit
+        #[cfg(feature = "exact-bootstrap-lp")]
+        let lp_locked = match bootstrap {
+            true => MINIMUM_LIQUIDITY,
+            false => 0,
+        };
+        #[cfg(not(feature = "exact-bootstrap-lp"))]
+        let lp_locked = 0;
+
+        if lp == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Dust floor
+        if lp < config.min_deposit_lp() {
+            return Err(AmmError::AmountTooSmall.into());
+        }
+
         // Grab the amounts to deposit
-        let (x, y) = match mint_lp.supply() == 0 && vault_x.amount() == 0 && vault_y.amount() == 0 {
+        let (x, y) = match bootstrap {
             true => (self.instruction_data.max_x, self.instruction_data.max_y),
             false => {
-                let amounts = ConstantProduct::xy_deposit_amounts_from_l(
-                    vault_x.amount(),
-                    vault_y.amount(),
-                    mint_lp.supply(),
-                    self.instruction_data.amount,
-                    6,
-                )
-                .map_err(|_| ProgramError::InvalidArgument)?;
-
-                (amounts.x, amounts.y)
+                // Proportional amounts, rounded up in favor of the pool, then
+                // up to the lot size, the remainder accruing to the holders
+                let (x, y) = deposit_amounts(reserve_x, reserve_y, mint_lp.supply(), lp)?;
+                (config.lot_ceil(x)?, config.lot_ceil(y)?)
             }
         };
 
+        // Deposit fee, LP kept unminted so its share accrues to the other
+        // holders, rounded up in favor of the pool. Nobody to accrue to
+        // on the first deposit.
+        let lp_fee = match bootstrap {
+            true => 0,
+            false => config.deposit_fee(lp)?,
+        };
+        let lp_out = lp - lp_fee;
+
+        // Only the bootstrap amounts are taken as given
+        config.check_lot(x)?;
+        config.check_lot(y)?;
+
         // Check for slippage
-        if !(x <= self.instruction_data.max_x && y <= self.instruction_data.max_y) {
-            return Err(ProgramError::InvalidArgument);
+        if x > self.instruction_data.max_x {
+            report_error_detail(
+                ErrorDetailKind::DepositSlippageX,
+                x,
+                self.instruction_data.max_x,
+            );
+            return Err(AmmError::DepositSlippageExceeded.into());
+        }
+        if y > self.instruction_data.max_y {
+            report_error_detail(
+                ErrorDetailKind::DepositSlippageY,
+                y,
+                self.instruction_data.max_y,
+            );
+            return Err(AmmError::DepositSlippageExceeded.into());
+        }
+
+        // Both sides are covered before anything moves, so a short user
+        // fails with a clear error rather than inside the second transfer
+        let user_x =
+            unsafe { TokenAccount::from_account_info_unchecked(self.accounts.user_x_ata)? };
+        let user_y =
+            unsafe { TokenAccount::from_account_info_unchecked(self.accounts.user_y_ata)? };
+        if user_x.amount() < x || user_y.amount() < y {
+            return Err(AmmError::InsufficientUserBalance.into());
         }
 
+        // Fail a frozen account clearly rather than inside a transfer
+        ensure_not_frozen(&[
+            self.accounts.user_x_ata,
+            self.accounts.user_y_ata,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        ])?;
+
+        let (vault_x_before, vault_y_before) = (vault_x.amount(), vault_y.amount());
+
         // Transfer the amounts from the token accounts of the user to the vaults
         Transfer {
             from: self.accounts.user_x_ata,
@@ -197,6 +325,10 @@ impl<'a> Deposit<'a> {
         }
         .invoke()?;
 
+        // The vaults received exactly the transferred amounts
+        ensure_received(vault_x_before, vault_x.amount(), x)?;
+        ensure_received(vault_y_before, vault_y.amount(), y)?;
+
         // and mint the appropriate amount of LP tokens to the user token account
         let seed_binding = config.seed().to_le_bytes();
         let config_bump_binding = config.config_bump();
@@ -210,16 +342,92 @@ impl<'a> Deposit<'a> {
 
         let signer = [Signer::from(&config_seeds)];
 
+        // First deposit for this user, create the LP ATA if asked to
+        if let (Some(system_program), Some(_)) = (
+            self.accounts.system_program,
+            self.accounts.associated_token_program,
+        ) {
+            CreateIdempotent {
+                funding_account: self.accounts.user,
+                account: self.accounts.user_lp_ata,
+                wallet: self.accounts.user,
+                mint: self.accounts.mint_lp,
+                system_program,
+                token_program: self.accounts.token_program,
+            }
+            .invoke()?;
+        }
+
+        #[cfg(feature = "strict-invariants")]
+        let supply_before = mint_lp.supply();
+
+        // Lock the minimum liquidity in the LP ATA of the config,
+        // which never signs for its LP
+        if lp_locked > 0 {
+            let (Some(system_program), Some(lp_lock)) =
+                (self.accounts.system_program, self.accounts.lp_lock)
+            else {
+                return Err(ProgramError::NotEnoughAccountKeys);
+            };
+
+            let (lp_lock_key, _) = find_program_address(
+                &[
+                    self.accounts.config.key(),
+                    self.accounts.token_program.key(),
+                    self.accounts.mint_lp.key(),
+                ],
+                &pinocchio_associated_token_account::ID,
+            );
+
+            if lp_lock_key.ne(lp_lock.key()) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            CreateIdempotent {
+                funding_account: self.accounts.user,
+                account: lp_lock,
+                wallet: self.accounts.config,
+                mint: self.accounts.mint_lp,
+                system_program,
+                token_program: self.accounts.token_program,
+            }
+            .invoke()?;
+
+            MintTo {
+                account: lp_lock,
+                amount: lp_locked,
+                mint: self.accounts.mint_lp,
+                mint_authority: self.accounts.config,
+            }
+            .invoke_signed(&signer)?;
+        }
+
         MintTo {
             // minting happens to the User LP ATA
             account: self.accounts.user_lp_ata,
-            amount: self.instruction_data.amount,
+            amount: lp_out,
             mint: self.accounts.mint_lp,
             // the authority is still the pool
             mint_authority: self.accounts.config,
         }
         .invoke_signed(&signer)?;
 
+        // The LP supply grew by exactly the minted amount
+        #[cfg(feature = "strict-invariants")]
+        ensure_supply(
+            mint_lp.supply(),
+            supply_before
+                .checked_add(lp_out)
+                .and_then(|supply| supply.checked_add(lp_locked)),
+        )?;
+
+        drop(config);
+
+        let mut config = Config::load_mut(self.accounts.config)?;
+        config.accrue_liquidity_seconds(ClockTimeSource.now()?);
+        config.credit_reserves(x, y)?;
+        config.credit_lp_supply(lp_out + lp_locked)?;
+
         Ok(())
     }
 }
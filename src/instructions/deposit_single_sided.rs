@@ -0,0 +1,219 @@
+use crate::math::single_sided_swap_in;
+use crate::utils::read_array;
+use crate::{
+    check_deadline, report_error_detail, AmmError, AmmState, ClockTimeSource, Config, Deposit,
+    DepositAccounts, DepositInstructionData, ErrorDetailKind, Swap, SwapAccounts,
+    SwapInstructionData, TimeSource,
+};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::state::TokenAccount;
+
+// This instruction adds liquidity from a single token: it swaps the part
+// of the input that balances the rest against the post-swap reserves,
+// then runs a regular deposit of both legs. Takes the same accounts as
+// `Deposit`.
+//
+// The swapped part pays the pool fee like any swap. The split is solved
+// on the constant product with the fee on the input, so weighted pools
+// are rejected, and pools taking the fee on the output leave a little
+// more of the input with the user.
+
+pub struct DepositSingleSidedInstructionData {
+    /// Amount of the input token offered
+    pub amount_in: u64,
+    /// Whether the input is X, otherwise Y
+    pub is_x: bool,
+    /// Minimum LP minted to the user
+    pub min_lp_out: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for DepositSingleSidedInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&(size_of::<u64>() * 3 + size_of::<bool>())) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount_in = u64::from_le_bytes(read_array(data, 0)?);
+
+        if amount_in == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let is_x = match data[8] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        let min_lp_out = u64::from_le_bytes(read_array(data, 9)?);
+        let expiration = i64::from_le_bytes(read_array(data, 17)?);
+
+        // Check signature expiration
+        check_deadline(expiration, &ClockTimeSource)?;
+
+        Ok(Self {
+            amount_in,
+            is_x,
+            min_lp_out,
+            expiration,
+        })
+    }
+}
+
+pub struct DepositSingleSided<'a> {
+    pub accounts: DepositAccounts<'a>,
+    pub instruction_data: DepositSingleSidedInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for DepositSingleSided<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = DepositAccounts::try_from(accounts)?;
+        let instruction_data = DepositSingleSidedInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> DepositSingleSided<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &24;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let user_out = match self.instruction_data.is_x {
+            true => self.accounts.user_y_ata,
+            false => self.accounts.user_x_ata,
+        };
+
+        // Size the swap against the reserves it will run on
+        let swap_in = {
+            let config = Config::load(self.accounts.config)?;
+
+            if config.state().ne(&(AmmState::Initialized as u8)) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            if config.curve_kind() != Config::CURVE_CONSTANT_PRODUCT {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            let (reserve_x, reserve_y) = config.lp_reserves(
+                TokenAccount::from_account_info(self.accounts.vault_x)?.amount(),
+                TokenAccount::from_account_info(self.accounts.vault_y)?.amount(),
+            );
+            let reserve_in = match self.instruction_data.is_x {
+                true => reserve_x,
+                false => reserve_y,
+            };
+
+            let swap_in = single_sided_swap_in(
+                reserve_in,
+                self.instruction_data.amount_in,
+                config.effective_fee(ClockTimeSource.now()?),
+            )?;
+
+            // Swap inputs must be whole lots
+            match config.lot_size() {
+                0 => swap_in,
+                lot => swap_in - swap_in % lot,
+            }
+        };
+
+        let out_before = TokenAccount::from_account_info(user_out)?.amount();
+        let lp_before = self.user_lp_amount()?;
+
+        // Slippage is enforced on the minted LP below
+        if swap_in > 0 {
+            Swap {
+                accounts: SwapAccounts {
+                    user: self.accounts.user,
+                    user_x_ata: self.accounts.user_x_ata,
+                    user_y_ata: self.accounts.user_y_ata,
+                    vault_x: self.accounts.vault_x,
+                    vault_y: self.accounts.vault_y,
+                    mint_lp: self.accounts.mint_lp,
+                    config: self.accounts.config,
+                    token_program: self.accounts.token_program,
+                    global_config: self.accounts.global_config,
+                },
+                instruction_data: SwapInstructionData {
+                    is_x: self.instruction_data.is_x,
+                    amount: swap_in,
+                    min: 0,
+                    expiration: self.instruction_data.expiration,
+                    partial_fill: false,
+                },
+            }
+            .process()?;
+        }
+
+        // Deposit what's left of the input along with the swap output
+        let leg_in = self.instruction_data.amount_in - swap_in;
+        let leg_out = TokenAccount::from_account_info(user_out)?
+            .amount()
+            .checked_sub(out_before)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        let (max_x, max_y) = match self.instruction_data.is_x {
+            true => (leg_in, leg_out),
+            false => (leg_out, leg_in),
+        };
+
+        Deposit {
+            accounts: DepositAccounts {
+                user: self.accounts.user,
+                mint_lp: self.accounts.mint_lp,
+                vault_x: self.accounts.vault_x,
+                vault_y: self.accounts.vault_y,
+                user_x_ata: self.accounts.user_x_ata,
+                user_y_ata: self.accounts.user_y_ata,
+                user_lp_ata: self.accounts.user_lp_ata,
+                config: self.accounts.config,
+                token_program: self.accounts.token_program,
+                global_config: self.accounts.global_config,
+                system_program: self.accounts.system_program,
+                associated_token_program: self.accounts.associated_token_program,
+                lp_lock: self.accounts.lp_lock,
+            },
+            instruction_data: DepositInstructionData {
+                amount: 0,
+                max_x,
+                max_y,
+                expiration: self.instruction_data.expiration,
+            },
+        }
+        .process()?;
+
+        let lp_out = self
+            .user_lp_amount()?
+            .checked_sub(lp_before)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if lp_out < self.instruction_data.min_lp_out {
+            report_error_detail(
+                ErrorDetailKind::Slippage,
+                lp_out,
+                self.instruction_data.min_lp_out,
+            );
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        Ok(())
+    }
+
+    /// LP held by the user, none before `Deposit` creates the LP ATA
+    fn user_lp_amount(&self) -> Result<u64, ProgramError> {
+        match self.accounts.user_lp_ata.data_is_empty() {
+            true => Ok(0),
+            false => Ok(TokenAccount::from_account_info(self.accounts.user_lp_ata)?.amount()),
+        }
+    }
+}
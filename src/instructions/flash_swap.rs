@@ -0,0 +1,196 @@
+use crate::{AmmError, Config, Swap, SwapAccounts, SwapInstructionData};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::slice_invoke;
+use pinocchio::instruction::{AccountMeta, Instruction, Seed, Signer};
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::instructions::Transfer;
+use pinocchio_token::state::TokenAccount;
+
+// This instruction is a swap delivering the output first: in order
+// - prices and checks the swap exactly like `Swap`
+// - transfers the output to the user
+// - invokes the callback program with the remaining accounts and data
+// - checks the input reached the vault, fee included, reverting otherwise
+
+pub struct FlashSwapAccounts<'a> {
+    pub swap: SwapAccounts<'a>,
+    /// Program invoked between the output and the repayment
+    pub callback_program: &'a AccountInfo,
+    /// Forwarded to the callback, in order
+    pub callback_accounts: &'a [AccountInfo],
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for FlashSwapAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        const SWAP_ACCOUNTS_LEN: usize = 9;
+
+        let (swap, rest) = accounts
+            .split_at_checked(SWAP_ACCOUNTS_LEN)
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let [callback_program, callback_accounts @ ..] = rest else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // The pool itself can't be its own callback
+        if callback_program.key().eq(&crate::ID) {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        if callback_accounts.len() > FlashSwap::MAX_CALLBACK_ACCOUNTS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        Ok(Self {
+            swap: SwapAccounts::try_from(swap)?,
+            callback_program,
+            callback_accounts,
+        })
+    }
+}
+
+pub struct FlashSwapInstructionData<'a> {
+    pub swap: SwapInstructionData,
+    /// Instruction data passed to the callback as is
+    pub callback_data: &'a [u8],
+}
+
+impl<'a> TryFrom<&'a [u8]> for FlashSwapInstructionData<'a> {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        const SWAP_DATA_LEN: usize = size_of::<u64>() * 3 + size_of::<bool>();
+
+        let (swap, callback_data) = data
+            .split_at_checked(SWAP_DATA_LEN)
+            .ok_or(ProgramError::InvalidInstructionData)?;
+
+        Ok(Self {
+            swap: SwapInstructionData::try_from(swap)?,
+            callback_data,
+        })
+    }
+}
+
+pub struct FlashSwap<'a> {
+    pub accounts: FlashSwapAccounts<'a>,
+    pub instruction_data: FlashSwapInstructionData<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for FlashSwap<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = FlashSwapAccounts::try_from(accounts)?;
+        let instruction_data = FlashSwapInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> FlashSwap<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &14;
+
+    /// Most accounts forwarded to the callback
+    pub const MAX_CALLBACK_ACCOUNTS: usize = 16;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let accounts = &self.accounts.swap;
+
+        let swap = Swap {
+            accounts: SwapAccounts {
+                user: accounts.user,
+                user_x_ata: accounts.user_x_ata,
+                user_y_ata: accounts.user_y_ata,
+                vault_x: accounts.vault_x,
+                vault_y: accounts.vault_y,
+                mint_lp: accounts.mint_lp,
+                config: accounts.config,
+                token_program: accounts.token_program,
+                global_config: accounts.global_config,
+            },
+            instruction_data: SwapInstructionData {
+                is_x: self.instruction_data.swap.is_x,
+                amount: self.instruction_data.swap.amount,
+                min: self.instruction_data.swap.min,
+                expiration: self.instruction_data.swap.expiration,
+                partial_fill: false,
+            },
+        };
+
+        let swap_result = swap.quote()?;
+
+        let (vault_in, vault_out, user_out) = match self.instruction_data.swap.is_x {
+            true => (accounts.vault_x, accounts.vault_y, accounts.user_y_ata),
+            false => (accounts.vault_y, accounts.vault_x, accounts.user_x_ata),
+        };
+
+        let vault_in_before = TokenAccount::from_account_info(vault_in)?.amount();
+
+        // Output first
+        let config = Config::load(accounts.config)?;
+
+        let seed_binding = config.seed().to_le_bytes();
+        let config_bump_binding = config.config_bump();
+        let config_seeds = [
+            Seed::from(b"config"),
+            Seed::from(&seed_binding),
+            Seed::from(config.mint_x()),
+            Seed::from(config.mint_y()),
+            Seed::from(&config_bump_binding),
+        ];
+
+        Transfer {
+            amount: swap_result.withdraw,
+            authority: accounts.config,
+            from: vault_out,
+            to: user_out,
+        }
+        .invoke_signed(&[Signer::from(&config_seeds)])?;
+
+        drop(config);
+
+        // Callback, with the signer and writable flags of this transaction.
+        // Fixed size buffers, the unused tail is never passed
+        let callback_program = self.accounts.callback_program;
+        let callback_accounts = self.accounts.callback_accounts;
+        let mut callback_metas: [AccountMeta; Self::MAX_CALLBACK_ACCOUNTS] =
+            core::array::from_fn(|_| AccountMeta::readonly(callback_program.key()));
+        let mut callback_infos = [callback_program; Self::MAX_CALLBACK_ACCOUNTS];
+
+        for ((meta, info), account) in callback_metas
+            .iter_mut()
+            .zip(callback_infos.iter_mut())
+            .zip(callback_accounts)
+        {
+            *meta = AccountMeta::new(account.key(), account.is_writable(), account.is_signer());
+            *info = account;
+        }
+
+        slice_invoke(
+            &Instruction {
+                program_id: callback_program.key(),
+                data: self.instruction_data.callback_data,
+                accounts: &callback_metas[..callback_accounts.len()],
+            },
+            &callback_infos[..callback_accounts.len()],
+        )?;
+
+        // The whole input, fee included, must have been repaid
+        let repaid = TokenAccount::from_account_info(vault_in)?
+            .amount()
+            .saturating_sub(vault_in_before);
+
+        if repaid < swap_result.deposit {
+            return Err(AmmError::FlashSwapNotRepaid.into());
+        }
+
+        swap.settle(&swap_result)
+    }
+}
@@ -1,15 +1,17 @@
-use crate::Config;
+use crate::utils::{create_pda_account, get_rent};
+use crate::{AmmError, AmmState, ClockTimeSource, Config, GlobalConfig, PairRegistry, TimeSource};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use pinocchio::account_info::AccountInfo;
 use pinocchio::instruction::Seed;
 use pinocchio::instruction::Signer;
 use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::{create_program_address, find_program_address};
 use pinocchio::sysvars::rent::Rent;
-use pinocchio::sysvars::Sysvar;
 use pinocchio::ProgramResult;
-use pinocchio_system::instructions::CreateAccount;
+use pinocchio_system::instructions::Transfer;
 use pinocchio_token::id as token_program_id;
 use pinocchio_token::instructions::InitializeMint2;
-use std::mem::MaybeUninit;
 // use pinocchio::msg;
 use pinocchio_log::log;
 use pinocchio_token::state::Mint;
@@ -19,6 +21,8 @@ use pinocchio_token::state::Mint;
 // - it initializes the Config state
 // - creates the Mint account `mint_lp` for the pool tokens
 // - assigns the mint authority
+// Once the global config is initialized, the pool starts with its default
+// bounds and the initializer pays the creation fee.
 
 pub struct InitializeAccounts<'a> {
     /// Creator, not necessarily the authority over it
@@ -26,17 +30,33 @@ pub struct InitializeAccounts<'a> {
     /// Mint representing pool liquidity tokens
     pub mint_lp: &'a AccountInfo,
     pub config: &'a AccountInfo,
+    /// Global config singleton, possibly not initialized yet
+    pub global_config: &'a AccountInfo,
+    /// Receives the creation fee, required when the global config charges one
+    pub protocol_fee_recipient: Option<&'a AccountInfo>,
+    /// Registry of the pair, required when the global config caps the pools
+    /// per pair
+    pub pair_registry: Option<&'a AccountInfo>,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for InitializeAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [initializer, mint_lp, config, _, _] = accounts else {
+        let [initializer, mint_lp, config, _, _, global_config, rest @ ..] = accounts else {
             log!("{}", format!("accounts: {:?}", accounts.len()).as_str());
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
+        let (protocol_fee_recipient, pair_registry) = match rest {
+            [] => (None, None),
+            [protocol_fee_recipient] => (Some(protocol_fee_recipient), None),
+            [protocol_fee_recipient, pair_registry] => {
+                (Some(protocol_fee_recipient), Some(pair_registry))
+            }
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+
         // Basic Accounts Checks
         // SignerAccount::check(initializer)?;
         // MintInterface::check(mint_lp)?;
@@ -48,6 +68,9 @@ impl<'a> TryFrom<&'a [AccountInfo]> for InitializeAccounts<'a> {
             initializer,
             mint_lp,
             config,
+            global_config,
+            protocol_fee_recipient,
+            pair_registry,
         })
     }
 }
@@ -62,35 +85,90 @@ pub struct InitializeInstructionData {
     pub lp_bump: [u8; 1],
     /// omittable for immutable pool
     pub authority: [u8; 32],
+    /// `AmmState` the pool starts in, omittable for `Initialized`,
+    /// only with the authority
+    pub initial_state: u8,
 }
 
 impl TryFrom<&[u8]> for InitializeInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &[u8]) -> Result<Self, Self::Error> {
-        const INITIALIZE_DATA_LEN_WITH_AUTHORITY: usize = size_of::<InitializeInstructionData>();
+        const INITIALIZE_DATA_LEN_WITH_STATE: usize = size_of::<InitializeInstructionData>();
+        const INITIALIZE_DATA_LEN_WITH_AUTHORITY: usize =
+            INITIALIZE_DATA_LEN_WITH_STATE - size_of::<u8>();
         const INITIALIZE_DATA_LEN: usize =
             INITIALIZE_DATA_LEN_WITH_AUTHORITY - size_of::<[u8; 32]>();
 
-        match data.len() {
-            INITIALIZE_DATA_LEN_WITH_AUTHORITY => {
-                Ok(unsafe { (data.as_ptr() as *const Self).read_unaligned() })
-            }
-            INITIALIZE_DATA_LEN => {
-                // If the authority is not present, we need to build the buffer and add it at the end before transmuting to the struct
-                let mut raw: MaybeUninit<[u8; INITIALIZE_DATA_LEN]> = MaybeUninit::uninit();
-                let raw_ptr = raw.as_mut_ptr() as *mut u8;
-                unsafe {
-                    // Copy the provided data
-                    core::ptr::copy_nonoverlapping(data.as_ptr(), raw_ptr, INITIALIZE_DATA_LEN);
-                    // Add the authority to the end of the buffer
-                    core::ptr::write_bytes(raw_ptr.add(INITIALIZE_DATA_LEN), 0, 32);
-                    // Now transmute to the struct
-                    Ok((raw.as_ptr() as *const Self).read_unaligned())
-                }
-            }
-            _ => Err(ProgramError::InvalidInstructionData),
+        if !matches!(
+            data.len(),
+            INITIALIZE_DATA_LEN_WITH_STATE
+                | INITIALIZE_DATA_LEN_WITH_AUTHORITY
+                | INITIALIZE_DATA_LEN
+        ) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // Omitted trailing fields default to a zeroed authority
+        // and the `Initialized` state
+        let mut raw = [0u8; INITIALIZE_DATA_LEN_WITH_STATE];
+        raw[..data.len()].copy_from_slice(data);
+        if data.len() < INITIALIZE_DATA_LEN_WITH_STATE {
+            raw[INITIALIZE_DATA_LEN_WITH_AUTHORITY] = AmmState::Initialized as u8;
+        }
+
+        let instruction_data = unsafe { (raw.as_ptr() as *const Self).read_unaligned() };
+
+        // Reject unknown states before anything is created, and the
+        // `Migrated` tombstone, which only `MigrateSeed` writes
+        if let Ok(AmmState::Migrated) | Err(_) = AmmState::try_from(instruction_data.initial_state)
+        {
+            return Err(ProgramError::InvalidInstructionData);
         }
+
+        // Nobody could ever move an immutable pool out of a paused state
+        if instruction_data.authority == [0u8; 32]
+            && instruction_data.initial_state != AmmState::Initialized as u8
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(instruction_data)
+    }
+}
+
+// Manual impls, the derive can't borrow the fields of a packed struct.
+// The encoding always carries the authority, zeroed for immutable pools,
+// and the initial state, which is byte-identical to the full on-chain layout.
+#[cfg(feature = "borsh")]
+impl BorshSerialize for InitializeInstructionData {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        let seed = self.seed;
+        let fee = self.fee;
+        writer.write_all(&seed.to_le_bytes())?;
+        writer.write_all(&fee.to_le_bytes())?;
+        writer.write_all(&self.mint_x)?;
+        writer.write_all(&self.mint_y)?;
+        writer.write_all(&self.config_bump)?;
+        writer.write_all(&self.lp_bump)?;
+        writer.write_all(&self.authority)?;
+        writer.write_all(&[self.initial_state])
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for InitializeInstructionData {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        Ok(Self {
+            seed: u64::deserialize_reader(reader)?,
+            fee: u16::deserialize_reader(reader)?,
+            mint_x: <[u8; 32]>::deserialize_reader(reader)?,
+            mint_y: <[u8; 32]>::deserialize_reader(reader)?,
+            config_bump: <[u8; 1]>::deserialize_reader(reader)?,
+            lp_bump: <[u8; 1]>::deserialize_reader(reader)?,
+            authority: <[u8; 32]>::deserialize_reader(reader)?,
+            initial_state: u8::deserialize_reader(reader)?,
+        })
     }
 }
 
@@ -118,7 +196,123 @@ impl<'a> Initialize<'a> {
     pub const DISCRIMINATOR: &'a u8 = &0;
 
     pub fn process(&mut self) -> ProgramResult {
-        let seed_binding = self.instruction_data.seed.to_le_bytes();
+        // Everything is validated before the first CreateAccount, so that
+        // nothing past it is expected to fail and leave a half-built pool
+        if !self.accounts.initializer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // The LP mint is created here, fail before creating anything
+        // if the account was already taken
+        if !self.accounts.mint_lp.data_is_empty()
+            || self.accounts.mint_lp.owner().ne(&pinocchio_system::ID)
+        {
+            return Err(AmmError::MintAlreadyExists.into());
+        }
+
+        if !self.accounts.config.data_is_empty() {
+            return Err(ProgramError::AccountAlreadyInitialized);
+        }
+
+        // Mirror the checks performed by `Config::set_inner`
+        let seed = self.instruction_data.seed;
+        let fee = self.instruction_data.fee;
+        if seed == 0
+            || fee >= 10_000
+            || self.instruction_data.mint_x == self.instruction_data.mint_y
+            || self.instruction_data.initial_state == AmmState::Uninitialized as u8
+            || self.instruction_data.initial_state == AmmState::Migrated as u8
+        {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        // One pool per pair and seed, see `client::canonicalize_mints`
+        if self.instruction_data.mint_x > self.instruction_data.mint_y {
+            return Err(AmmError::MintOrder.into());
+        }
+
+        let seed_binding = seed.to_le_bytes();
+
+        // Both PDAs must match the provided bumps
+        let config_key = create_program_address(
+            &[
+                b"config",
+                &seed_binding,
+                &self.instruction_data.mint_x,
+                &self.instruction_data.mint_y,
+                &self.instruction_data.config_bump,
+            ],
+            &crate::ID,
+        )?;
+
+        if config_key.ne(self.accounts.config.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        let mint_lp_key = create_program_address(
+            &[
+                b"mint_lp",
+                self.accounts.config.key(),
+                &self.instruction_data.lp_bump,
+            ],
+            &crate::ID,
+        )?;
+
+        if mint_lp_key.ne(self.accounts.mint_lp.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Protocol defaults and creation fee, none before the global config
+        // is initialized
+        let (defaults, creation_fee, max_pools_per_pair) =
+            match GlobalConfig::load_if_initialized(self.accounts.global_config)? {
+                Some(global) => {
+                    // The creation fee can't be skipped by leaving out its recipient
+                    match self.accounts.protocol_fee_recipient {
+                        Some(recipient) if recipient.key().ne(global.protocol_fee_recipient()) => {
+                            return Err(ProgramError::InvalidAccountData);
+                        }
+                        None if global.creation_fee() > 0 => {
+                            return Err(ProgramError::NotEnoughAccountKeys);
+                        }
+                        _ => {}
+                    }
+
+                    (
+                        Some((
+                            global.default_max_swap_pct_bps(),
+                            global.default_min_deposit_lp(),
+                            global.default_min_swap_amount(),
+                        )),
+                        global.creation_fee(),
+                        global.max_pools_per_pair(),
+                    )
+                }
+                None => (None, 0, 0),
+            };
+
+        // Get required lamports for rent, and make sure both accounts are affordable.
+        // Fetched once, `register_pool` reuses it
+        let rent = get_rent()?;
+        let config_lamports = rent.minimum_balance(Config::LEN);
+        let mint_lamports = rent.minimum_balance(Mint::LEN);
+
+        // Pre-funded accounts are adopted, only the shortfall is needed
+        let required = config_lamports
+            .saturating_sub(self.accounts.config.lamports())
+            .checked_add(mint_lamports.saturating_sub(self.accounts.mint_lp.lamports()))
+            .and_then(|required| required.checked_add(creation_fee))
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if self.accounts.initializer.lamports() < required {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        // Count the pool against the cap of its pair
+        if max_pools_per_pair > 0 {
+            self.register_pool(&rent, max_pools_per_pair)?;
+        }
+
         let config_seeds = &[
             Seed::from(b"config"),
             Seed::from(&seed_binding),
@@ -127,21 +321,46 @@ impl<'a> Initialize<'a> {
             Seed::from(&self.instruction_data.config_bump),
         ];
 
-        // Get required lamports for rent
-        let lamports = Rent::get()?.minimum_balance(Config::LEN);
-
         // Create signer with seeds slice
         let signer = [Signer::from(config_seeds)];
 
-        // Create the account
-        CreateAccount {
-            from: self.accounts.initializer,
-            to: self.accounts.config,
-            lamports,
-            space: Config::LEN as u64,
-            owner: &crate::ID,
+        // Create the account, adopting it if someone pre-funded the address
+        create_pda_account(
+            self.accounts.initializer,
+            self.accounts.config,
+            config_lamports,
+            Config::LEN as u64,
+            &crate::ID,
+            &signer,
+        )?;
+
+        let mut config = Config::load_mut(self.accounts.config)?;
+        config.set_inner(
+            seed,
+            self.instruction_data.authority,
+            self.instruction_data.mint_x,
+            self.instruction_data.mint_y,
+            fee,
+            self.instruction_data.config_bump,
+            AmmState::try_from(self.instruction_data.initial_state)?,
+        )?;
+        config.set_lp_decimals(Config::LP_DECIMALS);
+        config.set_mint_lp(*self.accounts.mint_lp.key());
+        config.set_created_at(ClockTimeSource.now()?);
+
+        // The vaults are plain ATAs of the config and may not exist yet,
+        // but their addresses are already fixed: grind their bumps once here
+        let ((_, vault_x_bump), (_, vault_y_bump)) =
+            config.vault_addresses(self.accounts.config.key(), &token_program_id());
+        config.set_vault_bumps([vault_x_bump], [vault_y_bump]);
+
+        if let Some((max_swap_pct_bps, min_deposit_lp, min_swap_amount)) = defaults {
+            config.set_max_swap_pct_bps(max_swap_pct_bps)?;
+            config.set_min_deposit_lp(min_deposit_lp);
+            config.set_min_swap_amount(min_swap_amount);
         }
-        .invoke_signed(&signer)?;
+
+        drop(config);
 
         let mint_lp_seeds = &[
             Seed::from(b"mint_lp"),
@@ -153,25 +372,78 @@ impl<'a> Initialize<'a> {
         let mint_signer = [Signer::from(mint_lp_seeds)];
 
         // Create the LP mint account
-        let lamports = Rent::get()?.minimum_balance(Mint::LEN);
-
-        CreateAccount {
-            from: self.accounts.initializer,
-            to: self.accounts.mint_lp,
-            lamports,
-            space: Mint::LEN as u64,
-            owner: &token_program_id(),
-        }
-        .invoke_signed(&mint_signer)?;
+        create_pda_account(
+            self.accounts.initializer,
+            self.accounts.mint_lp,
+            mint_lamports,
+            Mint::LEN as u64,
+            &token_program_id(),
+            &mint_signer,
+        )?;
 
         InitializeMint2 {
             mint: self.accounts.mint_lp,
-            decimals: 6,
+            decimals: Config::LP_DECIMALS,
             mint_authority: self.accounts.config.key(),
             freeze_authority: None,
         }
         .invoke_signed(&mint_signer)?;
 
+        if let (Some(protocol_fee_recipient), true) =
+            (self.accounts.protocol_fee_recipient, creation_fee > 0)
+        {
+            Transfer {
+                from: self.accounts.initializer,
+                to: protocol_fee_recipient,
+                lamports: creation_fee,
+            }
+            .invoke()?;
+        }
+
         Ok(())
     }
+
+    /// Registers the pool in the registry of its pair, created on first use
+    fn register_pool(&self, rent: &Rent, max_pools_per_pair: u16) -> ProgramResult {
+        let pair_registry = self
+            .accounts
+            .pair_registry
+            .ok_or(ProgramError::NotEnoughAccountKeys)?;
+
+        let (registry_key, bump) = find_program_address(
+            &[
+                PairRegistry::SEED,
+                &self.instruction_data.mint_x,
+                &self.instruction_data.mint_y,
+            ],
+            &crate::ID,
+        );
+
+        if registry_key.ne(pair_registry.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if pair_registry.data_is_empty() {
+            let bump_binding = [bump];
+            let registry_seeds = [
+                Seed::from(PairRegistry::SEED),
+                Seed::from(&self.instruction_data.mint_x),
+                Seed::from(&self.instruction_data.mint_y),
+                Seed::from(&bump_binding),
+            ];
+
+            create_pda_account(
+                self.accounts.initializer,
+                pair_registry,
+                rent.minimum_balance(PairRegistry::LEN),
+                PairRegistry::LEN as u64,
+                &crate::ID,
+                &[Signer::from(&registry_seeds)],
+            )?;
+
+            PairRegistry::load_mut(pair_registry)?.set_bump(bump_binding);
+        }
+
+        PairRegistry::load_mut(pair_registry)?.register(max_pools_per_pair)
+    }
 }
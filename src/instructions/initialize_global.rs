@@ -0,0 +1,147 @@
+use crate::utils::{create_pda_account, read_array};
+use crate::GlobalConfig;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::instruction::{Seed, Signer};
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::{find_program_address, Pubkey};
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+
+// This instruction lets the protocol admin write the global config,
+// creating it on first use. Running it again overwrites the settings.
+
+pub struct InitializeGlobalAccounts<'a> {
+    /// Must be `GlobalConfig::ADMIN`
+    pub admin: &'a AccountInfo,
+    pub global_config: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for InitializeGlobalAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [admin, global_config, system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !admin.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if admin.key().ne(&GlobalConfig::ADMIN) {
+            return Err(ProgramError::IncorrectAuthority);
+        }
+
+        Ok(Self {
+            admin,
+            global_config,
+            system_program,
+        })
+    }
+}
+
+pub struct InitializeGlobalInstructionData {
+    pub bump: [u8; 1],
+    pub protocol_fee_recipient: Pubkey,
+    pub creation_fee: u64,
+    pub default_max_swap_pct_bps: u16,
+    pub default_min_deposit_lp: u64,
+    pub default_min_swap_amount: u64,
+    /// Pools allowed per mint pair, no limit when 0
+    pub max_pools_per_pair: u16,
+}
+
+impl<'a> TryFrom<&'a [u8]> for InitializeGlobalInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        const DATA_LEN: usize = size_of::<u8>()
+            + size_of::<Pubkey>()
+            + size_of::<u64>()
+            + size_of::<u16>()
+            + size_of::<u64>() * 2;
+
+        // The pool cap per pair is omittable, no cap by default
+        let max_pools_per_pair = match data.len() {
+            DATA_LEN => 0,
+            len if len == DATA_LEN + size_of::<u16>() => {
+                u16::from_le_bytes(read_array(data, DATA_LEN)?)
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        Ok(Self {
+            bump: [data[0]],
+            protocol_fee_recipient: read_array(data, 1)?,
+            creation_fee: u64::from_le_bytes(read_array(data, 33)?),
+            default_max_swap_pct_bps: u16::from_le_bytes(read_array(data, 41)?),
+            default_min_deposit_lp: u64::from_le_bytes(read_array(data, 43)?),
+            default_min_swap_amount: u64::from_le_bytes(read_array(data, 51)?),
+            max_pools_per_pair,
+        })
+    }
+}
+
+pub struct InitializeGlobal<'a> {
+    pub accounts: InitializeGlobalAccounts<'a>,
+    pub instruction_data: InitializeGlobalInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for InitializeGlobal<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = InitializeGlobalAccounts::try_from(accounts)?;
+        let instruction_data = InitializeGlobalInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> InitializeGlobal<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &10;
+
+    pub fn process(&mut self) -> ProgramResult {
+        // Canonical bump only, a single global config can ever exist
+        let (global_key, bump) = find_program_address(&[GlobalConfig::SEED], &crate::ID);
+
+        if global_key.ne(self.accounts.global_config.key()) || self.instruction_data.bump != [bump]
+        {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        if self.accounts.global_config.data_is_empty() {
+            let global_seeds = [
+                Seed::from(GlobalConfig::SEED),
+                Seed::from(&self.instruction_data.bump),
+            ];
+
+            create_pda_account(
+                self.accounts.admin,
+                self.accounts.global_config,
+                Rent::get()?.minimum_balance(GlobalConfig::LEN),
+                GlobalConfig::LEN as u64,
+                &crate::ID,
+                &[Signer::from(&global_seeds)],
+            )?;
+        }
+
+        let mut global = GlobalConfig::load_mut(self.accounts.global_config)?;
+        global.set_inner(
+            self.instruction_data.bump,
+            self.instruction_data.protocol_fee_recipient,
+            self.instruction_data.creation_fee,
+            self.instruction_data.default_max_swap_pct_bps,
+            self.instruction_data.default_min_deposit_lp,
+            self.instruction_data.default_min_swap_amount,
+        )?;
+        global.set_max_pools_per_pair(self.instruction_data.max_pools_per_pair);
+
+        Ok(())
+    }
+}
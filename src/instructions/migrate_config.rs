@@ -0,0 +1,163 @@
+use crate::utils::ensure_vaults;
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::create_program_address;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+use pinocchio_system::instructions::Transfer;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+// This instruction upgrades a config account written by the first release
+// (v0, `Config::V0_LEN` bytes) to the current layout, which every other
+// instruction requires. v0 accounts are a prefix of the current layout, so:
+// - tops up the config rent and grows it to `Config::LEN`, zeroed past v0
+// - checks the LP mint is the pool PDA and the vaults the config ATAs
+// - fills in the appended fields, mirroring the LP supply and the vault
+//   balances, see `Config::upgrade_from_v0`
+// Everything it writes is derived from the chain, so anyone can run it.
+
+pub struct MigrateConfigAccounts<'a> {
+    /// Pays for the rent of the grown config
+    pub payer: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    pub mint_lp: &'a AccountInfo,
+    /// Config ATAs, still empty if the pool never had a deposit
+    pub vault_x: &'a AccountInfo,
+    pub vault_y: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for MigrateConfigAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [payer, config, mint_lp, vault_x, vault_y, system_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !payer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self {
+            payer,
+            config,
+            mint_lp,
+            vault_x,
+            vault_y,
+            system_program,
+        })
+    }
+}
+
+pub struct MigrateConfigInstructionData {
+    /// Bump of the LP mint PDA, which v0 didn't store
+    pub lp_bump: [u8; 1],
+}
+
+impl<'a> TryFrom<&'a [u8]> for MigrateConfigInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let [lp_bump] = data else {
+            return Err(ProgramError::InvalidInstructionData);
+        };
+
+        Ok(Self {
+            lp_bump: [*lp_bump],
+        })
+    }
+}
+
+pub struct MigrateConfig<'a> {
+    pub accounts: MigrateConfigAccounts<'a>,
+    pub instruction_data: MigrateConfigInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for MigrateConfig<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = MigrateConfigAccounts::try_from(accounts)?;
+        let instruction_data = MigrateConfigInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> MigrateConfig<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &25;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config_account = self.accounts.config;
+
+        if config_account.owner().ne(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        // Already migrated, or not a config
+        if config_account.data_len() != Config::V0_LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let lamports = Rent::get()?
+            .minimum_balance(Config::LEN)
+            .saturating_sub(config_account.lamports());
+        if lamports > 0 {
+            Transfer {
+                from: self.accounts.payer,
+                to: config_account,
+                lamports,
+            }
+            .invoke()?;
+        }
+
+        config_account.resize(Config::LEN)?;
+        config_account.try_borrow_mut_data()?[Config::V0_LEN..].fill(0);
+
+        let mut config = Config::load_mut(config_account)?;
+
+        let mint_lp_key = create_program_address(
+            &[
+                b"mint_lp",
+                config_account.key(),
+                &self.instruction_data.lp_bump,
+            ],
+            &crate::ID,
+        )?;
+        if mint_lp_key.ne(self.accounts.mint_lp.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        let mint_lp = Mint::from_account_info(self.accounts.mint_lp)?;
+
+        let ((vault_x, vault_x_bump), (vault_y, vault_y_bump)) =
+            config.vault_addresses(config_account.key(), &pinocchio_token::ID);
+        ensure_vaults(
+            &(vault_x, vault_y),
+            self.accounts.vault_x.key(),
+            self.accounts.vault_y.key(),
+        )?;
+        let balance = |vault: &AccountInfo| -> Result<u64, ProgramError> {
+            if vault.data_is_empty() {
+                return Ok(0);
+            }
+            Ok(TokenAccount::from_account_info(vault)?.amount())
+        };
+
+        // v0 took no protocol fees, the vaults hold nothing but liquidity
+        config.upgrade_from_v0(
+            mint_lp_key,
+            mint_lp.decimals(),
+            mint_lp.supply(),
+            (
+                balance(self.accounts.vault_x)?,
+                balance(self.accounts.vault_y)?,
+            ),
+            ([vault_x_bump], [vault_y_bump]),
+        )
+    }
+}
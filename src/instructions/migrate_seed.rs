@@ -0,0 +1,272 @@
+use crate::utils::{create_pda_account, ensure_pool_mint, read_array};
+use crate::{AmmError, AmmState, Config};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::instruction::{Seed, Signer};
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::create_program_address;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+use pinocchio_associated_token_account::instructions::CreateIdempotent;
+use pinocchio_token::instructions::{AuthorityType, CloseAccount, SetAuthority, Transfer};
+use pinocchio_token::state::TokenAccount;
+
+// This instruction re-homes a pool under a new seed, for recovery.
+// In order:
+// - checks the signer is the pool authority
+// - creates the new config PDA, signed by its seeds, as a copy of the old one
+// - creates the vaults of the new config and moves the balances over,
+//   closing the old vaults, signed by the old config
+// - hands the LP mint authority to the new config
+// - tombstones the old config as `Migrated`, for good
+
+pub struct MigrateSeedAccounts<'a> {
+    /// Pool authority, pays for the new accounts and gets the old vaults rent
+    pub authority: &'a AccountInfo,
+    pub old_config: &'a AccountInfo,
+    pub new_config: &'a AccountInfo,
+    pub mint_lp: &'a AccountInfo,
+    pub mint_x: &'a AccountInfo,
+    pub mint_y: &'a AccountInfo,
+    pub old_vault_x: &'a AccountInfo,
+    pub old_vault_y: &'a AccountInfo,
+    pub new_vault_x: &'a AccountInfo,
+    pub new_vault_y: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    /// Needed by the CPIs creating the new vaults
+    pub associated_token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for MigrateSeedAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, old_config, new_config, mint_lp, mint_x, mint_y, old_vault_x, old_vault_y, new_vault_x, new_vault_y, system_program, token_program, associated_token_program] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // A pool never pairs a mint with itself
+        if mint_x.key().eq(mint_y.key()) {
+            return Err(AmmError::IdenticalMints.into());
+        }
+
+        Ok(Self {
+            authority,
+            old_config,
+            new_config,
+            mint_lp,
+            mint_x,
+            mint_y,
+            old_vault_x,
+            old_vault_y,
+            new_vault_x,
+            new_vault_y,
+            system_program,
+            token_program,
+            associated_token_program,
+        })
+    }
+}
+
+pub struct MigrateSeedInstructionData {
+    pub seed: u64,
+    pub config_bump: [u8; 1],
+}
+
+impl<'a> TryFrom<&'a [u8]> for MigrateSeedInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u64>() + size_of::<u8>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let seed = u64::from_le_bytes(read_array(data, 0)?);
+
+        if seed == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            seed,
+            config_bump: [data[8]],
+        })
+    }
+}
+
+pub struct MigrateSeed<'a> {
+    pub accounts: MigrateSeedAccounts<'a>,
+    pub instruction_data: MigrateSeedInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for MigrateSeed<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = MigrateSeedAccounts::try_from(accounts)?;
+        let instruction_data = MigrateSeedInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> MigrateSeed<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &15;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.old_config)?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        // A disabled config has nothing left to move
+        config.require_not_disabled()?;
+
+        if config.seed() == self.instruction_data.seed {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // The new vaults are created for these mints
+        ensure_pool_mint(self.accounts.mint_x, config.mint_x())?;
+        ensure_pool_mint(self.accounts.mint_y, config.mint_y())?;
+
+        // The new config must match the provided bump
+        let new_seed_binding = self.instruction_data.seed.to_le_bytes();
+        let new_config_key = create_program_address(
+            &[
+                b"config",
+                &new_seed_binding,
+                config.mint_x(),
+                config.mint_y(),
+                &self.instruction_data.config_bump,
+            ],
+            &crate::ID,
+        )?;
+
+        if new_config_key.ne(self.accounts.new_config.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // Both vault pairs sit at the ATAs of their config
+        let (old_vault_x, old_vault_y) = config.vault_keys(
+            self.accounts.old_config.key(),
+            self.accounts.token_program.key(),
+        )?;
+        let ((new_vault_x, new_vault_x_bump), (new_vault_y, new_vault_y_bump)) =
+            config.vault_addresses(&new_config_key, self.accounts.token_program.key());
+
+        if old_vault_x.ne(self.accounts.old_vault_x.key())
+            || old_vault_y.ne(self.accounts.old_vault_y.key())
+            || new_vault_x.ne(self.accounts.new_vault_x.key())
+            || new_vault_y.ne(self.accounts.new_vault_y.key())
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let old_seed_binding = config.seed().to_le_bytes();
+        let old_config_bump = config.config_bump();
+        let mint_x = *config.mint_x();
+        let mint_y = *config.mint_y();
+        drop(config);
+
+        let new_config_seeds = [
+            Seed::from(b"config"),
+            Seed::from(&new_seed_binding),
+            Seed::from(&mint_x),
+            Seed::from(&mint_y),
+            Seed::from(&self.instruction_data.config_bump),
+        ];
+
+        create_pda_account(
+            self.accounts.authority,
+            self.accounts.new_config,
+            Rent::get()?.minimum_balance(Config::LEN),
+            Config::LEN as u64,
+            &crate::ID,
+            &[Signer::from(&new_config_seeds)],
+        )?;
+
+        // Copy all state over, then re-home it under the new seed
+        self.accounts
+            .new_config
+            .try_borrow_mut_data()?
+            .copy_from_slice(&self.accounts.old_config.try_borrow_data()?);
+
+        Config::load_mut(self.accounts.new_config)?.rehome(
+            self.instruction_data.seed,
+            self.instruction_data.config_bump,
+            ([new_vault_x_bump], [new_vault_y_bump]),
+        )?;
+
+        let old_config_seeds = [
+            Seed::from(b"config"),
+            Seed::from(&old_seed_binding),
+            Seed::from(&mint_x),
+            Seed::from(&mint_y),
+            Seed::from(&old_config_bump),
+        ];
+        let signer = [Signer::from(&old_config_seeds)];
+
+        for (mint, old_vault, new_vault) in [
+            (
+                self.accounts.mint_x,
+                self.accounts.old_vault_x,
+                self.accounts.new_vault_x,
+            ),
+            (
+                self.accounts.mint_y,
+                self.accounts.old_vault_y,
+                self.accounts.new_vault_y,
+            ),
+        ] {
+            CreateIdempotent {
+                funding_account: self.accounts.authority,
+                account: new_vault,
+                wallet: self.accounts.new_config,
+                mint,
+                system_program: self.accounts.system_program,
+                token_program: self.accounts.token_program,
+            }
+            .invoke()?;
+
+            // Donations included, the new vaults hold everything the old ones did
+            let amount = unsafe { TokenAccount::from_account_info_unchecked(old_vault)? }.amount();
+
+            Transfer {
+                from: old_vault,
+                to: new_vault,
+                authority: self.accounts.old_config,
+                amount,
+            }
+            .invoke_signed(&signer)?;
+
+            CloseAccount {
+                account: old_vault,
+                destination: self.accounts.authority,
+                authority: self.accounts.old_config,
+            }
+            .invoke_signed(&signer)?;
+        }
+
+        SetAuthority {
+            account: self.accounts.mint_lp,
+            authority: self.accounts.old_config,
+            authority_type: AuthorityType::MintTokens,
+            new_authority: Some(&new_config_key),
+        }
+        .invoke_signed(&signer)?;
+
+        Config::load_mut(self.accounts.old_config)?.set_state(AmmState::Migrated as u8)
+    }
+}
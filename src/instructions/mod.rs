@@ -9,3 +9,69 @@ pub use withdraw::*;
 
 pub mod swap;
 pub use swap::*;
+
+pub mod reinitialize_vault_authority;
+pub use reinitialize_vault_authority::*;
+
+pub mod update_config;
+pub use update_config::*;
+
+pub mod set_label;
+pub use set_label::*;
+
+pub mod swap_with_delegate;
+pub use swap_with_delegate::*;
+
+pub mod simulate_withdraw;
+pub use simulate_withdraw::*;
+
+pub mod swap_to_ratio;
+pub use swap_to_ratio::*;
+
+pub mod initialize_global;
+pub use initialize_global::*;
+
+pub mod set_global_pause;
+pub use set_global_pause::*;
+
+pub mod withdraw_single_sided;
+pub use withdraw_single_sided::*;
+
+pub mod pool_health;
+pub use pool_health::*;
+
+pub mod flash_swap;
+pub use flash_swap::*;
+
+pub mod migrate_seed;
+pub use migrate_seed::*;
+
+pub mod accrued_fees;
+pub use accrued_fees::*;
+
+pub mod quote;
+pub use quote::*;
+
+pub mod update_fee;
+pub use update_fee::*;
+
+pub mod nominate_authority;
+pub use nominate_authority::*;
+
+pub mod accept_authority;
+pub use accept_authority::*;
+
+pub mod set_curve_kind;
+pub use set_curve_kind::*;
+
+pub mod admin_set_state;
+pub use admin_set_state::*;
+
+pub mod collect_protocol_fees;
+pub use collect_protocol_fees::*;
+
+pub mod deposit_single_sided;
+pub use deposit_single_sided::*;
+
+pub mod migrate_config;
+pub use migrate_config::*;
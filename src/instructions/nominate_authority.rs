@@ -0,0 +1,84 @@
+use crate::utils::read_array;
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::Pubkey;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority nominate its successor, first
+// step of the authority transfer. Nothing changes until the nominee runs
+// `AcceptAuthority`, a new nomination replaces the pending one.
+
+pub struct NominateAuthorityAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for NominateAuthorityAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct NominateAuthorityInstructionData {
+    pub pending_authority: Pubkey,
+}
+
+impl<'a> TryFrom<&'a [u8]> for NominateAuthorityInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&size_of::<Pubkey>()) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            pending_authority: read_array(data, 0)?,
+        })
+    }
+}
+
+pub struct NominateAuthority<'a> {
+    pub accounts: NominateAuthorityAccounts<'a>,
+    pub instruction_data: NominateAuthorityInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for NominateAuthority<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = NominateAuthorityAccounts::try_from(accounts)?;
+        let instruction_data = NominateAuthorityInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> NominateAuthority<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &19;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        config.set_pending_authority(self.instruction_data.pending_authority)
+    }
+}
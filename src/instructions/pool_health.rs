@@ -0,0 +1,91 @@
+use crate::math::imbalance_bps;
+use crate::utils::read_array;
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::set_return_data;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This read-only instruction returns how far the tracked reserves are
+// from balanced, in basis points, through the return data. Needs no signer.
+
+pub struct PoolHealthAccounts<'a> {
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for PoolHealthAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        Ok(Self { config })
+    }
+}
+
+pub struct PoolHealthInstructionData {
+    /// Price of X in Y, Q32.32, the reserves are valued 1:1 when 0
+    pub price: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for PoolHealthInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&size_of::<u64>()) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let price = u64::from_le_bytes(read_array(data, 0)?);
+
+        Ok(Self { price })
+    }
+}
+
+pub struct PoolHealth<'a> {
+    pub accounts: PoolHealthAccounts<'a>,
+    pub instruction_data: PoolHealthInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for PoolHealth<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = PoolHealthAccounts::try_from(accounts)?;
+        let instruction_data = PoolHealthInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> PoolHealth<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &13;
+
+    pub const RETURN_DATA_LEN: usize = size_of::<u64>();
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        let imbalance = imbalance_bps(
+            config.reserve_x(),
+            config.reserve_y(),
+            self.instruction_data.price,
+        );
+
+        set_return_data(&imbalance.to_le_bytes());
+
+        Ok(())
+    }
+}
+
+/// Decodes the imbalance, in basis points, returned by `PoolHealth`
+pub fn decode_pool_health(data: &[u8]) -> Option<u64> {
+    Some(u64::from_le_bytes(data.try_into().ok()?))
+}
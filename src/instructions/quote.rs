@@ -0,0 +1,186 @@
+use crate::utils::read_array;
+use crate::{price_swap, ClockTimeSource, Config, TimeSource};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::set_return_data;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+// This read-only instruction prices a swap against the current vault
+// balances, the way `Swap` would, and returns through the return data
+// the output, the fee, the price impact and the effective price.
+// Needs no signer, and runs none of the swap guards (pause, limits, ...).
+
+pub struct QuoteAccounts<'a> {
+    pub config: &'a AccountInfo,
+    pub mint_lp: &'a AccountInfo,
+    pub vault_x: &'a AccountInfo,
+    pub vault_y: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for QuoteAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [config, mint_lp, vault_x, vault_y] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        Ok(Self {
+            config,
+            mint_lp,
+            vault_x,
+            vault_y,
+        })
+    }
+}
+
+pub struct QuoteInstructionData {
+    pub is_x: bool,
+    pub amount: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for QuoteInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len() != size_of::<u8>() + size_of::<u64>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let is_x = match data[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        let amount = u64::from_le_bytes(read_array(data, 1)?);
+
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self { is_x, amount })
+    }
+}
+
+pub struct Quote<'a> {
+    pub accounts: QuoteAccounts<'a>,
+    pub instruction_data: QuoteInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for Quote<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = QuoteAccounts::try_from(accounts)?;
+        let instruction_data = QuoteInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+/// What `Quote` returns
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct SwapQuote {
+    pub amount_out: u64,
+    pub fee: u64,
+    /// How much worse than the pre-trade spot price the trade executes
+    pub price_impact_bps: u64,
+    /// Output per unit of input, Q32.32
+    pub effective_price: u64,
+}
+
+impl<'a> Quote<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &17;
+
+    pub const RETURN_DATA_LEN: usize = size_of::<u64>() * 4;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        let mint_lp = unsafe { Mint::from_account_info_unchecked(self.accounts.mint_lp)? };
+        let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
+        let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
+
+        // Vaults of the pool, holding its mints
+        for (vault, mint) in [(&vault_x, config.mint_x()), (&vault_y, config.mint_y())] {
+            if vault.mint().ne(mint) || vault.owner().ne(self.accounts.config.key()) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        if mint_lp.mint_authority() != Some(self.accounts.config.key()) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+        let amount = self.instruction_data.amount;
+
+        let swap_result = price_swap(
+            &config,
+            ClockTimeSource.now()?,
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            self.instruction_data.is_x,
+            amount,
+        )?;
+
+        // Spot price of the input in the output before the trade, weighted
+        // pools price each reserve by its weight
+        let (reserve_in, reserve_out, weight_in, weight_out) = match self.instruction_data.is_x {
+            true => (reserve_x, reserve_y, config.weight_x(), config.weight_y()),
+            false => (reserve_y, reserve_x, config.weight_y(), config.weight_x()),
+        };
+        let (weight_in, weight_out) = match config.curve_kind() {
+            Config::CURVE_WEIGHTED => (weight_in as u128, weight_out as u128),
+            _ => (1, 1),
+        };
+        let spot_price = ((reserve_out as u128 * weight_in) << 32)
+            .checked_div(reserve_in as u128 * weight_out)
+            .ok_or(ProgramError::InvalidAccountData)?;
+        let effective_price = ((swap_result.withdraw as u128) << 32) / amount as u128;
+
+        let price_impact_bps = match spot_price {
+            0 => 0,
+            _ => spot_price.saturating_sub(effective_price) * 10_000 / spot_price,
+        };
+
+        let quote = SwapQuote {
+            amount_out: swap_result.withdraw,
+            fee: swap_result.fee,
+            price_impact_bps: price_impact_bps as u64,
+            effective_price: u64::try_from(effective_price).unwrap_or(u64::MAX),
+        };
+
+        let mut data = [0u8; Self::RETURN_DATA_LEN];
+        data[0..8].copy_from_slice(&quote.amount_out.to_le_bytes());
+        data[8..16].copy_from_slice(&quote.fee.to_le_bytes());
+        data[16..24].copy_from_slice(&quote.price_impact_bps.to_le_bytes());
+        data[24..32].copy_from_slice(&quote.effective_price.to_le_bytes());
+
+        set_return_data(&data);
+
+        Ok(())
+    }
+}
+
+/// Decodes the `SwapQuote` returned by `Quote`
+pub fn decode_quote(data: &[u8]) -> Option<SwapQuote> {
+    if data.len() != Quote::RETURN_DATA_LEN {
+        return None;
+    }
+
+    Some(SwapQuote {
+        amount_out: u64::from_le_bytes(data[0..8].try_into().ok()?),
+        fee: u64::from_le_bytes(data[8..16].try_into().ok()?),
+        price_impact_bps: u64::from_le_bytes(data[16..24].try_into().ok()?),
+        effective_price: u64::from_le_bytes(data[24..32].try_into().ok()?),
+    })
+}
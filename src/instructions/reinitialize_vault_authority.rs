@@ -0,0 +1,124 @@
+use crate::utils::ensure_spl_token_program;
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::find_program_address;
+use pinocchio::ProgramResult;
+use pinocchio_token::instructions::{AuthorityType, SetAuthority};
+use pinocchio_token::state::TokenAccount;
+
+// This instruction recovers a vault whose owner is not the config PDA.
+// In order:
+// - checks the signer is the pool authority, and the token program
+// - checks the vault sits at the expected ATA address for the config
+// - rejects healthy vaults (already owned by the config)
+// - repoints the vault owner to the config, signed by the current owner
+
+pub struct ReinitializeVaultAuthorityAccounts<'a> {
+    /// Pool authority, must also be the current owner of the vault
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    /// Vault whose owner has to be repointed to the config
+    pub vault: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for ReinitializeVaultAuthorityAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config, vault, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        // The vault address is derived from it, as for the other vault
+        // instructions it can only be SPL Token
+        ensure_spl_token_program(token_program)?;
+
+        Ok(Self {
+            authority,
+            config,
+            vault,
+            token_program,
+        })
+    }
+}
+
+pub struct ReinitializeVaultAuthority<'a> {
+    pub accounts: ReinitializeVaultAuthorityAccounts<'a>,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for ReinitializeVaultAuthority<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        if !data.is_empty() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let accounts = ReinitializeVaultAuthorityAccounts::try_from(accounts)?;
+
+        Ok(Self { accounts })
+    }
+}
+
+impl<'a> ReinitializeVaultAuthority<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &4;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        {
+            let vault = TokenAccount::from_account_info(self.accounts.vault)?;
+
+            // The vault must belong to one of the two pool mints
+            if vault.mint().ne(config.mint_x()) && vault.mint().ne(config.mint_y()) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // and sit at the address the other instructions validate against
+            let (expected_vault, _) = find_program_address(
+                &[
+                    self.accounts.config.key(),
+                    self.accounts.token_program.key(),
+                    vault.mint(),
+                ],
+                &pinocchio_associated_token_account::ID,
+            );
+
+            if expected_vault.ne(self.accounts.vault.key()) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // A healthy vault is never repointed
+            if vault.owner().eq(self.accounts.config.key()) {
+                return Err(ProgramError::InvalidAccountData);
+            }
+
+            // Only a vault currently controlled by the authority is recoverable
+            if vault.owner().ne(self.accounts.authority.key()) {
+                return Err(ProgramError::IllegalOwner);
+            }
+        }
+
+        SetAuthority {
+            account: self.accounts.vault,
+            authority: self.accounts.authority,
+            authority_type: AuthorityType::AccountOwner,
+            new_authority: Some(self.accounts.config.key()),
+        }
+        .invoke()?;
+
+        Ok(())
+    }
+}
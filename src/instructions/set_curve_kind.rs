@@ -0,0 +1,117 @@
+use crate::utils::read_array;
+use crate::{AmmError, Config};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority switch the pool curve.
+// An empty pool can switch freely. On a live pool the switch reprices
+// the reserves at once: the spot price `y * w_x / (x * w_y)` moves by
+// `w_x' * w_y / (w_x * w_y')` whatever the reserves, so it's only allowed
+// within `Config::CURVE_SWITCH_TOLERANCE_BPS`.
+
+pub struct SetCurveKindAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for SetCurveKindAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct SetCurveKindInstructionData {
+    /// `Config::CURVE_*`
+    pub curve_kind: u8,
+    /// Weight of X in basis points, ignored by the constant product curve
+    pub weight_x: u16,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetCurveKindInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&(size_of::<u8>() + size_of::<u16>())) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let curve_kind = data[0];
+
+        // The constant product curve is stored as the 50/50 weighting
+        let weight_x = match curve_kind {
+            Config::CURVE_CONSTANT_PRODUCT => 5_000,
+            _ => u16::from_le_bytes(read_array(data, 1)?),
+        };
+
+        Ok(Self {
+            curve_kind,
+            weight_x,
+        })
+    }
+}
+
+pub struct SetCurveKind<'a> {
+    pub accounts: SetCurveKindAccounts<'a>,
+    pub instruction_data: SetCurveKindInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SetCurveKind<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SetCurveKindAccounts::try_from(accounts)?;
+        let instruction_data = SetCurveKindInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SetCurveKind<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &21;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let (old_x, old_y) = config.price_weights();
+        let live = config.reserve_x() > 0 || config.reserve_y() > 0;
+
+        config.set_curve(
+            self.instruction_data.curve_kind,
+            self.instruction_data.weight_x,
+        )?;
+
+        if live {
+            let (new_x, new_y) = config.price_weights();
+
+            // Spot price ratio new / old, as a / b
+            let a = new_x as u64 * old_y as u64;
+            let b = old_x as u64 * new_y as u64;
+
+            if a.abs_diff(b) * 10_000 > b * Config::CURVE_SWITCH_TOLERANCE_BPS {
+                return Err(AmmError::CurveSwitchUnsafe.into());
+            }
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,87 @@
+use crate::GlobalConfig;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the protocol admin pause or unpause
+// deposits, withdrawals and swaps on every pool at once.
+
+pub struct SetGlobalPauseAccounts<'a> {
+    /// Must be `GlobalConfig::ADMIN`
+    pub admin: &'a AccountInfo,
+    pub global_config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for SetGlobalPauseAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [admin, global_config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !admin.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        if admin.key().ne(&GlobalConfig::ADMIN) {
+            return Err(ProgramError::IncorrectAuthority);
+        }
+
+        Ok(Self {
+            admin,
+            global_config,
+        })
+    }
+}
+
+pub struct SetGlobalPauseInstructionData {
+    pub paused: bool,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetGlobalPauseInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        let paused = match data {
+            [0] => false,
+            [1] => true,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        Ok(Self { paused })
+    }
+}
+
+pub struct SetGlobalPause<'a> {
+    pub accounts: SetGlobalPauseAccounts<'a>,
+    pub instruction_data: SetGlobalPauseInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SetGlobalPause<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SetGlobalPauseAccounts::try_from(accounts)?;
+        let instruction_data = SetGlobalPauseInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SetGlobalPause<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &11;
+
+    pub fn process(&mut self) -> ProgramResult {
+        // Validates the singleton address and initialization
+        drop(GlobalConfig::load(self.accounts.global_config)?);
+
+        GlobalConfig::load_mut(self.accounts.global_config)?
+            .set_paused(self.instruction_data.paused);
+
+        Ok(())
+    }
+}
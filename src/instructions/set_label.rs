@@ -0,0 +1,88 @@
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority write the pool label,
+// an empty label clears it.
+
+pub struct SetLabelAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for SetLabelAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct SetLabelInstructionData {
+    /// Zero padded label
+    pub label: [u8; 16],
+    pub len: usize,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SetLabelInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        // Over-long labels are rejected rather than truncated
+        if data.len() > size_of::<[u8; 16]>() {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let mut label = [0u8; 16];
+        label[..data.len()].copy_from_slice(data);
+
+        Ok(Self {
+            label,
+            len: data.len(),
+        })
+    }
+}
+
+pub struct SetLabel<'a> {
+    pub accounts: SetLabelAccounts<'a>,
+    pub instruction_data: SetLabelInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SetLabel<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SetLabelAccounts::try_from(accounts)?;
+        let instruction_data = SetLabelInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SetLabel<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &6;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        config.set_label(&self.instruction_data.label[..self.instruction_data.len])
+    }
+}
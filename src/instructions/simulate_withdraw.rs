@@ -0,0 +1,148 @@
+use crate::math::withdraw_amounts;
+use crate::utils::{ensure_spl_token_program, ensure_vaults, read_array};
+use crate::Config;
+use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::set_return_data;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+// This read-only instruction previews a withdraw: it returns the
+// X and Y amounts released by burning `amount` LP tokens through
+// the return data, without moving anything.
+
+pub struct SimulateWithdrawAccounts<'a> {
+    pub mint_lp: &'a AccountInfo,
+    pub vault_x: &'a AccountInfo,
+    pub vault_y: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for SimulateWithdrawAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [mint_lp, vault_x, vault_y, config, token_program] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        // The vault addresses derive from the token program key
+        ensure_spl_token_program(token_program)?;
+
+        Ok(Self {
+            mint_lp,
+            vault_x,
+            vault_y,
+            config,
+            token_program,
+        })
+    }
+}
+
+pub struct SimulateWithdrawInstructionData {
+    /// LP amount to preview burning
+    pub amount: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SimulateWithdrawInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&size_of::<u64>()) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount = u64::from_le_bytes(read_array(data, 0)?);
+
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self { amount })
+    }
+}
+
+pub struct SimulateWithdraw<'a> {
+    pub accounts: SimulateWithdrawAccounts<'a>,
+    pub instruction_data: SimulateWithdrawInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SimulateWithdraw<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SimulateWithdrawAccounts::try_from(accounts)?;
+        let instruction_data = SimulateWithdrawInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SimulateWithdraw<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &8;
+
+    pub const RETURN_DATA_LEN: usize = size_of::<u64>() * 2;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        // Check the vaults are the ATAs of the config
+        ensure_vaults(
+            &config.vault_keys(
+                self.accounts.config.key(),
+                self.accounts.token_program.key(),
+            )?,
+            self.accounts.vault_x.key(),
+            self.accounts.vault_y.key(),
+        )?;
+
+        // Deserialize the token accounts, the LP mint must be the pool's
+        let mint_lp = Mint::from_account_info(self.accounts.mint_lp)?;
+        config.check_lp_mint(self.accounts.mint_lp.key(), &mint_lp)?;
+        let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
+        let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
+
+        if self.instruction_data.amount > mint_lp.supply() {
+            return Err(ProgramError::InsufficientFunds);
+        }
+
+        // Uncollected protocol fees aren't the LPs'
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+
+        // Same computation as `Withdraw`, full exit included
+        let (x, y) = withdraw_amounts(
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            self.instruction_data.amount,
+        )?;
+
+        let (x, y) =
+            config.withdraw_after_fee(x, y, self.instruction_data.amount, mint_lp.supply())?;
+
+        let mut return_data = [0u8; Self::RETURN_DATA_LEN];
+        return_data[0..8].copy_from_slice(&x.to_le_bytes());
+        return_data[8..16].copy_from_slice(&y.to_le_bytes());
+        set_return_data(&return_data);
+
+        Ok(())
+    }
+}
+
+/// Decodes the `(x, y)` return data of `SimulateWithdraw`
+pub fn decode_simulate_withdraw(data: &[u8]) -> Option<(u64, u64)> {
+    if data.len() != SimulateWithdraw::RETURN_DATA_LEN {
+        return None;
+    }
+
+    Some((
+        u64::from_le_bytes(data[0..8].try_into().ok()?),
+        u64::from_le_bytes(data[8..16].try_into().ok()?),
+    ))
+}
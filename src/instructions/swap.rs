@@ -1,14 +1,23 @@
-use crate::{AmmState, Config};
-use constant_product_curve::{ConstantProduct, LiquidityPair};
+use crate::math::{init_curve, mul_div_down, mul_div_up, weighted_swap_out};
+use crate::utils::{
+    ensure_distinct_accounts, ensure_not_frozen, ensure_spl_token_program, ensure_token_program,
+    ensure_vaults, read_array,
+};
+use crate::{
+    check_deadline, report_error_detail, AmmError, AmmState, ClockTimeSource, Config,
+    ErrorDetailKind, GlobalConfig, SwapEvent, TimeSource, NO_DEADLINE,
+};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+use constant_product_curve::{LiquidityPair, SwapResult};
+use core::num::NonZeroU64;
 use pinocchio::account_info::AccountInfo;
+use pinocchio::cpi::set_return_data;
 use pinocchio::instruction::{Seed, Signer};
 use pinocchio::program_error::ProgramError;
-use pinocchio::pubkey::find_program_address;
-use pinocchio::sysvars::clock::Clock;
-use pinocchio::sysvars::Sysvar;
 use pinocchio::ProgramResult;
 use pinocchio_token::instructions::Transfer;
-use pinocchio_token::state::TokenAccount;
+use pinocchio_token::state::{Mint, TokenAccount};
 
 pub struct SwapAccounts<'a> {
     pub user: &'a AccountInfo,
@@ -16,25 +25,35 @@ pub struct SwapAccounts<'a> {
     pub user_y_ata: &'a AccountInfo,
     pub vault_x: &'a AccountInfo,
     pub vault_y: &'a AccountInfo,
+    /// Pool LP mint, its supply scales the fee growth
+    pub mint_lp: &'a AccountInfo,
     pub config: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
+    /// Protocol global config, checked for the global pause
+    pub global_config: &'a AccountInfo,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for SwapAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [user, user_x_ata, user_y_ata, vault_x, vault_y, config, token_program] = accounts
+        let [user, user_x_ata, user_y_ata, vault_x, vault_y, mint_lp, config, token_program, global_config] =
+            accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
-        // Basic Accounts Checks
-        // SignerAccount::check(initializer)?;
-        // MintInterface::check(mint_lp)?;
+        // Token accounts must all be distinct, a user ATA aliasing a vault
+        // would turn transfers into no-ops
+        ensure_distinct_accounts(&[user_x_ata, user_y_ata, vault_x, vault_y])?;
 
-        // check initializer is signer
-        // but it will be checked downstream maybe?
+        // The user authorizes the input transfer, reject a missing
+        // signature here rather than through a failed CPI
+        if !user.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        ensure_spl_token_program(token_program)?;
 
         Ok(Self {
             user,
@@ -42,49 +61,84 @@ impl<'a> TryFrom<&'a [AccountInfo]> for SwapAccounts<'a> {
             user_y_ata,
             vault_x,
             vault_y,
+            mint_lp,
             config,
             token_program,
+            global_config,
         })
     }
 }
 
 #[repr(C)]
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub struct SwapInstructionData {
     pub is_x: bool,
     pub amount: u64,
     pub min: u64,
     pub expiration: i64,
+    /// Fills as much of `amount` as the reserve limits, the output reserve and
+    /// the average price `min` sets allow instead of failing, `min` being
+    /// prorated to the filled input
+    pub partial_fill: bool,
 }
 
 impl<'a> TryFrom<&'a [u8]> for SwapInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if data.len().ne(&(size_of::<u64>() * 3 + size_of::<bool>())) {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        Self::parse(data, &ClockTimeSource)
+    }
+}
 
-        let is_x = data[0] != 0;
-        let amount = u64::from_le_bytes(data[1..9].try_into().unwrap());
+impl SwapInstructionData {
+    /// Parses the instruction data, checking the expiration against `time`
+    pub fn parse<T: TimeSource>(data: &[u8], time: &T) -> Result<Self, ProgramError> {
+        const DATA_LEN: usize = size_of::<u64>() * 3 + size_of::<bool>();
+
+        // The expiration is omittable, see `NO_DEADLINE`, and an optional
+        // trailing byte opts into partial fills
+        let (expiration_len, partial_fill) = match data.len() {
+            DATA_LEN => (size_of::<i64>(), None),
+            len if len == DATA_LEN + size_of::<bool>() => (size_of::<i64>(), Some(data[DATA_LEN])),
+            len if len == DATA_LEN - size_of::<i64>() => (0, None),
+            len if len == DATA_LEN - size_of::<i64>() + size_of::<bool>() => {
+                (0, Some(data[DATA_LEN - size_of::<i64>()]))
+            }
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        // Only the canonical encodings of a boolean are accepted
+        let is_x = match data[0] {
+            0 => false,
+            1 => true,
+            _ => return Err(AmmError::InvalidInstructionData.into()),
+        };
+        let partial_fill = match partial_fill {
+            None | Some(0) => false,
+            Some(1) => true,
+            _ => return Err(AmmError::InvalidInstructionData.into()),
+        };
+        let amount = u64::from_le_bytes(read_array(data, 1)?);
 
         if amount == 0 {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        let min = u64::from_le_bytes(data[9..17].try_into().unwrap());
-        let expiration = i64::from_le_bytes(data[17..25].try_into().unwrap());
+        let min = u64::from_le_bytes(read_array(data, 9)?);
+        let expiration = match expiration_len {
+            0 => NO_DEADLINE,
+            _ => i64::from_le_bytes(read_array(data, 17)?),
+        };
 
         // Check signature expiration
-        let now = Clock::get()?.unix_timestamp;
-        if now > expiration {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        check_deadline(expiration, time)?;
 
         Ok(Self {
             is_x,
             amount,
             min,
             expiration,
+            partial_fill,
         })
     }
 }
@@ -112,68 +166,43 @@ impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for Swap<'a> {
 impl<'a> Swap<'a> {
     pub const DISCRIMINATOR: &'a u8 = &3;
 
-    pub fn process(&mut self) -> ProgramResult {
-        let config = Config::load(self.accounts.config)?;
-
-        if config.state().ne(&(AmmState::Initialized as u8)) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+    /// Input consumed and output delivered, returned by partial fills
+    pub const FILL_RETURN_DATA_LEN: usize = size_of::<u64>() * 2;
 
-        // Check if the vault_x is valid
-        let (vault_x, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_x(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
+    pub fn process(&mut self) -> ProgramResult {
+        let swap_result = self.quote()?;
 
-        if vault_x.ne(self.accounts.vault_x.key()) {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        // An authority other than the owner can only spend its allowance,
+        // fail here rather than on an opaque token program error
+        let user_in = match self.instruction_data.is_x {
+            true => self.accounts.user_x_ata,
+            false => self.accounts.user_y_ata,
+        };
+        let user_in = TokenAccount::from_account_info(user_in)?;
 
-        // Check if the vault_y is valid
-        let (vault_y, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_y(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
+        if user_in.owner().ne(self.accounts.user.key()) {
+            let allowance = match user_in.delegate() == Some(self.accounts.user.key()) {
+                true => user_in.delegated_amount(),
+                false => 0,
+            };
 
-        if vault_y.ne(self.accounts.vault_y.key()) {
-            return Err(ProgramError::InvalidAccountData);
+            if allowance < swap_result.deposit {
+                report_error_detail(ErrorDetailKind::Delegation, swap_result.deposit, allowance);
+                return Err(AmmError::InsufficientDelegation.into());
+            }
         }
 
-        // Deserialize the token accounts
-        let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
-        let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
-
-        // Swap Calculations
-        let mut curve = ConstantProduct::init(
-            vault_x.amount(),
-            vault_y.amount(),
-            vault_x.amount(),
-            config.fee(),
-            None,
-        )
-        .map_err(|_| ProgramError::Custom(1))?;
-
-        let p = match self.instruction_data.is_x {
-            true => LiquidityPair::X,
-            false => LiquidityPair::Y,
-        };
+        drop(user_in);
 
-        let swap_result = curve
-            .swap(p, self.instruction_data.amount, self.instruction_data.min)
-            .map_err(|_| ProgramError::Custom(1))?;
+        // Fail a frozen account clearly rather than inside a transfer
+        ensure_not_frozen(&[
+            self.accounts.user_x_ata,
+            self.accounts.user_y_ata,
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+        ])?;
 
-        // Check for correct values
-        if swap_result.deposit == 0 || swap_result.withdraw == 0 {
-            return Err(ProgramError::InvalidArgument);
-        }
+        let config = Config::load(self.accounts.config)?;
 
         let seed_binding = config.seed().to_le_bytes();
         let config_bump_binding = config.config_bump();
@@ -221,6 +250,621 @@ impl<'a> Swap<'a> {
             .invoke_signed(&signer_seeds)?;
         }
 
+        drop(config);
+
+        self.settle(&swap_result)?;
+
+        // What a partial fill actually swapped
+        if self.instruction_data.partial_fill {
+            let mut data = [0u8; Self::FILL_RETURN_DATA_LEN];
+            data[0..8].copy_from_slice(&swap_result.deposit.to_le_bytes());
+            data[8..16].copy_from_slice(&swap_result.withdraw.to_le_bytes());
+            set_return_data(&data);
+        }
+
         Ok(())
     }
+
+    /// Runs every check of the swap against the current pool state and
+    /// prices it, without moving anything
+    pub(crate) fn quote(&self) -> Result<SwapResult, ProgramError> {
+        GlobalConfig::check_not_paused(self.accounts.global_config)?;
+
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        config.check_deadline_required(self.instruction_data.expiration)?;
+
+        if config.state().ne(&(AmmState::Initialized as u8)) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        if !config.feature_enabled(Config::FEATURE_SWAP) {
+            return Err(AmmError::FeatureDisabled.into());
+        }
+
+        // Dust floor
+        if self.instruction_data.amount < config.min_swap_amount() {
+            return Err(AmmError::AmountTooSmall.into());
+        }
+
+        config.check_lot(self.instruction_data.amount)?;
+
+        // Check the vaults are the ATAs of the config, pinning their mint and
+        // owner alone would accept any other token account of the config
+        ensure_vaults(
+            &config.vault_keys(
+                self.accounts.config.key(),
+                self.accounts.token_program.key(),
+            )?,
+            self.accounts.vault_x.key(),
+            self.accounts.vault_y.key(),
+        )?;
+
+        // Tokens only move between accounts of the passed token program
+        ensure_token_program(
+            self.accounts.token_program,
+            &[
+                self.accounts.user_x_ata,
+                self.accounts.user_y_ata,
+                self.accounts.vault_x,
+                self.accounts.vault_y,
+            ],
+        )?;
+
+        // Deserialize the token accounts
+        let mint_lp = unsafe { Mint::from_account_info_unchecked(self.accounts.mint_lp)? };
+        let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
+        let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
+
+        // Single pass vault validation: real token accounts holding the
+        // pool mints, owned by the config
+        for (info, vault, mint) in [
+            (self.accounts.vault_x, &vault_x, config.mint_x()),
+            (self.accounts.vault_y, &vault_y, config.mint_y()),
+        ] {
+            if info.owner().ne(&pinocchio_token::ID)
+                || vault.mint().ne(mint)
+                || vault.owner().ne(self.accounts.config.key())
+            {
+                return Err(ProgramError::InvalidAccountData);
+            }
+        }
+
+        // The config only ever is the mint authority of the pool LP mint
+        if mint_lp.mint_authority() != Some(self.accounts.config.key()) {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        // Reserves snapshot: outstanding LP implies both sides are funded, and
+        // vaults never hold less than tracked, only more through protocol
+        // fees and donations
+        if vault_x.amount() < config.reserve_x() || vault_y.amount() < config.reserve_y() {
+            return Err(AmmError::StaleReserves.into());
+        }
+
+        // Whatever the vaults hold above the tracked reserves isn't liquidity
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+
+        if mint_lp.supply() > 0 && (reserve_x == 0 || reserve_y == 0) {
+            return Err(AmmError::StaleReserves.into());
+        }
+
+        // Swap Calculations, at the fee in force now
+        let now = ClockTimeSource.now()?;
+        let price = |amount| {
+            price_swap(
+                &config,
+                now,
+                reserve_x,
+                reserve_y,
+                mint_lp.supply(),
+                self.instruction_data.is_x,
+                amount,
+            )
+        };
+
+        // Reserve percentage circuit breaker, the first amount over its limit
+        let (reserve_in, reserve_out) = match self.instruction_data.is_x {
+            true => (reserve_x, reserve_y),
+            false => (reserve_y, reserve_x),
+        };
+        let over_limit = |result: &SwapResult| {
+            [(result.deposit, reserve_in), (result.withdraw, reserve_out)]
+                .into_iter()
+                .find_map(|(amount, reserve)| {
+                    config
+                        .max_swap_amount(reserve)
+                        .filter(|limit| amount > *limit)
+                        .map(|limit| (amount, limit))
+                })
+        };
+
+        // What a partial fill settles for: within the reserve limits, short
+        // of draining the output, and at no worse an average price than the
+        // one `min` sets for the whole `amount`
+        let (amount, mut min) = (self.instruction_data.amount, self.instruction_data.min);
+        let fits = |result: &SwapResult| {
+            over_limit(result).is_none()
+                && result.withdraw < reserve_out
+                && result.withdraw as u128 * amount as u128 >= min as u128 * result.deposit as u128
+        };
+
+        let mut swap_result = price(amount);
+
+        if self.instruction_data.partial_fill && !swap_result.as_ref().is_ok_and(fits) {
+            let fillable = fill_to_limit(amount, config.lot_size(), |input| {
+                price(input).is_ok_and(|result| fits(&result))
+            });
+
+            // Nothing fillable fails as the whole swap would, otherwise
+            // `min` is prorated to the filled input
+            if fillable > 0 {
+                swap_result = price(fillable);
+                min = mul_div_up(min, fillable, amount)?;
+            }
+        }
+
+        let swap_result = swap_result?;
+
+        if let Some((amount, limit)) = over_limit(&swap_result) {
+            report_error_detail(ErrorDetailKind::ReserveLimit, amount, limit);
+            return Err(AmmError::SwapExceedsReserveLimit.into());
+        }
+
+        // The pool may require a minimal protection, `min` must be within
+        // the required tolerance of the quoted output
+        if let Some(required) = config.required_min_out(swap_result.withdraw) {
+            if min < required {
+                report_error_detail(ErrorDetailKind::Slippage, min, required);
+                return Err(AmmError::SlippageProtectionRequired.into());
+            }
+        }
+
+        // Check for slippage
+        if swap_result.withdraw < min {
+            report_error_detail(ErrorDetailKind::Slippage, swap_result.withdraw, min);
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        // Check for correct values
+        if swap_result.deposit == 0 || swap_result.withdraw == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Price band, checked against the reserves the swap leaves behind
+        let (post_x, post_y) = match self.instruction_data.is_x {
+            true => (
+                reserve_x.checked_add(swap_result.deposit),
+                reserve_y.checked_sub(swap_result.withdraw),
+            ),
+            false => (
+                reserve_x.checked_sub(swap_result.withdraw),
+                reserve_y.checked_add(swap_result.deposit),
+            ),
+        };
+
+        if !config.price_in_band(
+            post_x.ok_or(ProgramError::ArithmeticOverflow)?,
+            post_y.ok_or(ProgramError::ArithmeticOverflow)?,
+        ) {
+            return Err(AmmError::PriceOutOfBand.into());
+        }
+
+        Ok(swap_result)
+    }
+
+    /// Books a swap whose tokens already moved into the tracked reserves,
+    /// fee growth, and emits its event
+    pub(crate) fn settle(&self, swap_result: &SwapResult) -> ProgramResult {
+        let mint_lp = unsafe { Mint::from_account_info_unchecked(self.accounts.mint_lp)? };
+        let mut config = Config::load_mut(self.accounts.config)?;
+        let now = ClockTimeSource.now()?;
+        config.accrue_liquidity_seconds(now);
+
+        // Split the input into the principal priced by the curve and the fee,
+        // the fee is explicitly credited to the reserves as a rebate to LPs.
+        // With the fee on the output the whole input is principal, and the
+        // fee is the part of the output kept in the reserves
+        let fee = swap_result.fee;
+        let (principal, fee_in, fee_out) = match config.fee_on_output() {
+            true => (swap_result.deposit, 0, fee),
+            false => (
+                swap_result
+                    .deposit
+                    .checked_sub(fee)
+                    .ok_or(ProgramError::ArithmeticOverflow)?,
+                fee,
+                0,
+            ),
+        };
+
+        // The protocol cut of the fee stays in the vault outside the reserves,
+        // until collected, rounded down in favor of the LPs
+        let protocol_bps = config.effective_protocol_fee_bps(now) as u64;
+        let protocol_in = mul_div_down(fee_in, protocol_bps, 10_000)?;
+        let protocol_out = mul_div_down(fee_out, protocol_bps, 10_000)?;
+        let (lp_fee_in, lp_fee_out) = (fee_in - protocol_in, fee_out - protocol_out);
+
+        if self.instruction_data.is_x {
+            config.credit_reserves(principal, 0)?;
+            config.credit_reserves(lp_fee_in, 0)?;
+            config.debit_reserves(0, swap_result.withdraw);
+            config.debit_reserves(0, protocol_out);
+            config.accrue_fee_growth(lp_fee_in, lp_fee_out, mint_lp.supply());
+            config.record_fees(fee_in, fee_out);
+        } else {
+            config.credit_reserves(0, principal)?;
+            config.credit_reserves(0, lp_fee_in)?;
+            config.debit_reserves(swap_result.withdraw, 0);
+            config.debit_reserves(protocol_out, 0);
+            config.accrue_fee_growth(lp_fee_out, lp_fee_in, mint_lp.supply());
+            config.record_fees(fee_out, fee_in);
+        }
+
+        // Post-swap reserves as tracked by the pool, the ones pricing the
+        // next swap, for price feeds. The vaults also hold the uncollected
+        // protocol fees and any donation.
+        let (reserve_x_after, reserve_y_after) = (config.reserve_x(), config.reserve_y());
+        drop(config);
+
+        SwapEvent {
+            is_x: self.instruction_data.is_x,
+            amount_in: principal,
+            fee,
+            amount_out: swap_result.withdraw,
+            reserve_x_after,
+            reserve_y_after,
+        }
+        .emit();
+
+        Ok(())
+    }
+}
+
+/// Prices a swap of `amount` against the given reserves with the pool's
+/// curve and fee mode, at the fee in force at `now`, shared by `Swap`
+/// and `Quote`
+pub fn price_swap(
+    config: &Config,
+    now: i64,
+    reserve_x: u64,
+    reserve_y: u64,
+    lp_supply: u64,
+    is_x: bool,
+    amount: u64,
+) -> Result<SwapResult, ProgramError> {
+    // The curve is fee free when the fee is taken on the output
+    let fee = config.effective_fee(now);
+    let curve_fee = match config.fee_on_output() {
+        true => 0,
+        false => fee,
+    };
+    let swap_result = match config.curve_kind() {
+        Config::CURVE_WEIGHTED => {
+            let (reserve_in, reserve_out, weight_in, weight_out) = match is_x {
+                true => (reserve_x, reserve_y, config.weight_x(), config.weight_y()),
+                false => (reserve_y, reserve_x, config.weight_y(), config.weight_x()),
+            };
+
+            // Output rounded down in favor of the pool, see `crate::math`
+            let (withdraw, fee) = weighted_swap_out(
+                reserve_in,
+                reserve_out,
+                weight_in,
+                weight_out,
+                amount,
+                curve_fee,
+            )?;
+
+            SwapResult {
+                deposit: amount,
+                withdraw,
+                fee,
+            }
+        }
+        _ => {
+            let mut curve = init_curve(
+                reserve_x,
+                reserve_y,
+                lp_supply,
+                curve_fee,
+                config.lp_decimals(),
+            )?;
+
+            let p = match is_x {
+                true => LiquidityPair::X,
+                false => LiquidityPair::Y,
+            };
+
+            // The curve prices the output with integer division, rounding it
+            // down in favor of the pool, see `crate::math`
+            curve.swap(p, amount, 0).map_err(AmmError::from)?
+        }
+    };
+
+    // Fee on the output, rounded up in favor of the pool and left in the
+    // output reserve, `k` grows as with a fee on the input
+    match config.fee_on_output() {
+        true => {
+            let fee = mul_div_up(swap_result.withdraw, fee as u64, 10_000)?;
+            SwapResult {
+                deposit: swap_result.deposit,
+                withdraw: swap_result.withdraw - fee,
+                fee,
+            }
+        }
+        false => swap_result,
+    }
+}
+
+/// Largest input up to `amount` that `fits`, on the `lot_size` grid, 0 when
+/// none does. The output grows with the input, so the inputs that fit are a
+/// prefix of the range and a binary search over the lots finds the last one.
+///
+/// Calls `fits` at most `2 + log2(amount / lot_size)` times, 66 at worst,
+/// each pricing the swap once: a partial fill costs that many curve
+/// evaluations on top of the swap, and the whole amount costs only one.
+pub fn fill_to_limit(amount: u64, lot_size: u64, fits: impl Fn(u64) -> bool) -> u64 {
+    let lot = NonZeroU64::new(lot_size).map_or(1, NonZeroU64::get);
+
+    // The whole amount, the common case
+    if fits(amount) {
+        return amount - amount % lot;
+    }
+
+    // Lots that don't fit, and that do. The last whole lot is only known
+    // not to fit when it is the whole amount.
+    let mut over = amount / lot;
+    if over * lot != amount && fits(over * lot) {
+        return over * lot;
+    }
+    let mut fillable = 0;
+
+    // The range halves on every step, from at most 2^64 lots
+    for _ in 0..u64::BITS {
+        if over - fillable <= 1 {
+            break;
+        }
+        let mid = fillable + (over - fillable) / 2;
+        match fits(mid * lot) {
+            true => fillable = mid,
+            false => over = mid,
+        }
+    }
+
+    fillable * lot
+}
+
+/// Decodes the input consumed and the output delivered by a partial fill `Swap`
+pub fn decode_swap_fill(data: &[u8]) -> Option<(u64, u64)> {
+    if data.len() != Swap::FILL_RETURN_DATA_LEN {
+        return None;
+    }
+
+    Some((
+        u64::from_le_bytes(data[0..8].try_into().ok()?),
+        u64::from_le_bytes(data[8..16].try_into().ok()?),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::math::quote_swap;
+    use crate::FixedTimeSource;
+
+    /// Constant product output of `amount` into 1_000_000 / 1_000_000
+    /// reserves, without fee
+    fn out(amount: u64) -> u64 {
+        1_000_000 * amount / (1_000_000 + amount)
+    }
+
+    #[test]
+    fn fill_to_limit_fills_whole_amount() {
+        assert_eq!(
+            fill_to_limit(10_000, 0, |amount| out(amount) <= 100_000),
+            10_000
+        );
+    }
+
+    #[test]
+    fn fill_to_limit_caps_at_output_limit() {
+        // 111_111 in takes 99_999 out, 111_112 in takes 100_000
+        let fillable = fill_to_limit(500_000, 0, |amount| out(amount) < 100_000);
+        assert_eq!(fillable, 111_111);
+        assert!(out(fillable) < 100_000 && out(fillable + 1) >= 100_000);
+    }
+
+    #[test]
+    fn fill_to_limit_caps_at_average_price() {
+        // At least 0.95 out per unit in, on average
+        let fillable = fill_to_limit(500_000, 0, |amount| out(amount) * 100 >= amount * 95);
+        assert!(fillable > 0 && fillable < 500_000);
+        assert!(out(fillable) * 100 >= fillable * 95);
+        assert!(out(fillable + 1) * 100 < (fillable + 1) * 95);
+    }
+
+    #[test]
+    fn fill_to_limit_stays_on_lot_grid() {
+        assert_eq!(
+            fill_to_limit(500_000, 1_000, |amount| out(amount) < 100_000),
+            111_000
+        );
+    }
+
+    #[test]
+    fn fill_to_limit_fills_nothing_below_a_lot() {
+        assert_eq!(fill_to_limit(500_000, 0, |_| false), 0);
+        assert_eq!(fill_to_limit(500_000, 1_000, |amount| amount < 1_000), 0);
+    }
+
+    #[test]
+    fn fill_to_limit_is_bounded() {
+        let calls = core::cell::Cell::new(0);
+        let counted = |limit: u64| {
+            let calls = &calls;
+            move |amount: u64| {
+                calls.set(calls.get() + 1);
+                amount <= limit
+            }
+        };
+
+        // One pricing when the whole amount fits
+        assert_eq!(fill_to_limit(u64::MAX, 0, counted(u64::MAX)), u64::MAX);
+        assert_eq!(calls.replace(0), 1);
+
+        // A search over the whole u64 range
+        assert_eq!(fill_to_limit(u64::MAX, 0, counted(12_345)), 12_345);
+        assert!(calls.replace(0) <= 66);
+
+        // Fewer steps over fewer lots
+        assert_eq!(fill_to_limit(500_000, 1_000, counted(111_111)), 111_000);
+        assert!(calls.replace(0) <= 2 + 9);
+    }
+
+    #[test]
+    fn swaps_use_the_old_fee_until_the_change_applies() {
+        let mut data = Config::new_test(
+            1,
+            [1; 32],
+            [2; 32],
+            [3; 32],
+            [4; 32],
+            30,
+            [255, 254, 253],
+            0,
+        );
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        config.set_fee_change_timelock_secs(3_600).unwrap();
+        config.schedule_fee(100, 1_000).unwrap();
+
+        let withdraw = |config: &Config, now| {
+            price_swap(config, now, 1_000_000, 1_000_000, 0, true, 10_000)
+                .unwrap()
+                .withdraw
+        };
+        let (old, new) = (
+            quote_swap(1_000_000, 1_000_000, 10_000, 30).unwrap(),
+            quote_swap(1_000_000, 1_000_000, 10_000, 100).unwrap(),
+        );
+        assert!(new < old);
+
+        assert_eq!(withdraw(config, 1_000), old);
+        assert_eq!(withdraw(config, 4_599), old);
+        assert_eq!(withdraw(config, 4_600), new);
+        assert_eq!(withdraw(config, 10_000), new);
+    }
+
+    #[test]
+    fn quote_swap_ignores_the_lp_supply() {
+        let data = Config::new_test(
+            1,
+            [1; 32],
+            [2; 32],
+            [3; 32],
+            [4; 32],
+            30,
+            [255, 254, 253],
+            0,
+        );
+        let config = unsafe { Config::from_bytes_unchecked(&data) };
+
+        // `quote_swap` prices with an empty supply, `Swap` with the pool's
+        for lp_supply in [0, 1_000, 1_000_000, 1_000_000_000] {
+            for amount in [1_000, 100_000, 1_000_000] {
+                let swap = price_swap(config, 0, 1_000_000, 4_000_000, lp_supply, true, amount);
+                assert_eq!(
+                    swap.unwrap().withdraw,
+                    quote_swap(1_000_000, 4_000_000, amount, 30).unwrap()
+                );
+            }
+        }
+    }
+
+    /// `Swap` data of 1_000 X in for at least 1 Y, expiring at `expiration`
+    fn swap_data(expiration: i64) -> [u8; 25] {
+        let mut data = [0u8; 25];
+        data[0] = 1;
+        data[1..9].copy_from_slice(&1_000u64.to_le_bytes());
+        data[9..17].copy_from_slice(&1u64.to_le_bytes());
+        data[17..25].copy_from_slice(&expiration.to_le_bytes());
+        data
+    }
+
+    fn parsed_expiration(data: &[u8], now: i64) -> Result<i64, ProgramError> {
+        SwapInstructionData::parse(data, &FixedTimeSource(now)).map(|data| data.expiration)
+    }
+
+    #[test]
+    fn expiration_second_is_still_valid() {
+        assert_eq!(parsed_expiration(&swap_data(1_000), 1_000), Ok(1_000));
+    }
+
+    #[test]
+    fn expiration_rejects_the_next_second() {
+        assert_eq!(
+            parsed_expiration(&swap_data(1_000), 1_001),
+            Err(ProgramError::InvalidInstructionData)
+        );
+    }
+
+    #[test]
+    fn expiration_accepts_no_deadline() {
+        // Sent explicitly or omitted, the pool decides whether to accept it
+        assert_eq!(
+            parsed_expiration(&swap_data(NO_DEADLINE), i64::MAX),
+            Ok(NO_DEADLINE)
+        );
+        assert_eq!(
+            parsed_expiration(&swap_data(NO_DEADLINE)[..17], 1_000),
+            Ok(NO_DEADLINE)
+        );
+    }
+
+    #[test]
+    fn expiration_rejects_negative_timestamps() {
+        assert_eq!(
+            parsed_expiration(&swap_data(-1), 1_000),
+            Err(AmmError::InvalidTimestamp.into())
+        );
+    }
+
+    #[test]
+    fn expiration_at_the_i64_extremes() {
+        // Before the epoch, on either side of the comparison
+        for (expiration, now) in [(i64::MIN, 1_000), (1_000, i64::MIN), (i64::MIN, i64::MIN)] {
+            assert_eq!(
+                parsed_expiration(&swap_data(expiration), now),
+                Err(AmmError::InvalidTimestamp.into())
+            );
+        }
+
+        // At the end of time, compared without overflowing
+        assert_eq!(
+            parsed_expiration(&swap_data(i64::MAX - 1), i64::MAX),
+            Err(ProgramError::InvalidInstructionData)
+        );
+        assert_eq!(parsed_expiration(&swap_data(i64::MAX), 0), Ok(i64::MAX));
+    }
+
+    #[test]
+    fn is_x_must_be_canonical() {
+        let parse = |data: &[u8]| SwapInstructionData::parse(data, &FixedTimeSource(0));
+
+        let mut data = swap_data(1_000);
+        assert!(parse(&data).unwrap().is_x);
+        data[0] = 0;
+        assert!(!parse(&data).unwrap().is_x);
+
+        // Any other byte would sign for the same swap under another encoding
+        for byte in [2, 0x80, u8::MAX] {
+            data[0] = byte;
+            assert_eq!(
+                parse(&data).err(),
+                Some(AmmError::InvalidInstructionData.into())
+            );
+        }
+    }
 }
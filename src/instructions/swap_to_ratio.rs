@@ -0,0 +1,123 @@
+use crate::math::swap_to_ratio_input;
+use crate::utils::read_array;
+use crate::{
+    check_deadline, ClockTimeSource, Config, Swap, SwapAccounts, SwapInstructionData, TimeSource,
+};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::state::TokenAccount;
+
+// This instruction computes the swap moving the pool reserves to a
+// target ratio along the curve, caps it to `max_in`, and executes it
+// through the regular swap. Takes the same accounts as `Swap`.
+
+pub struct SwapToRatioInstructionData {
+    /// Target `reserve_x / reserve_y`, in basis points
+    pub ratio_bps: u64,
+    /// Largest input the caller is willing to spend
+    pub max_in: u64,
+    /// Minimum output, as in `Swap`
+    pub min_out: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SwapToRatioInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&(size_of::<u64>() * 4)) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let ratio_bps = u64::from_le_bytes(read_array(data, 0)?);
+        let max_in = u64::from_le_bytes(read_array(data, 8)?);
+
+        if ratio_bps == 0 || max_in == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let min_out = u64::from_le_bytes(read_array(data, 16)?);
+        let expiration = i64::from_le_bytes(read_array(data, 24)?);
+
+        // Check signature expiration
+        check_deadline(expiration, &ClockTimeSource)?;
+
+        Ok(Self {
+            ratio_bps,
+            max_in,
+            min_out,
+            expiration,
+        })
+    }
+}
+
+pub struct SwapToRatio<'a> {
+    pub accounts: SwapAccounts<'a>,
+    pub instruction_data: SwapToRatioInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SwapToRatio<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SwapAccounts::try_from(accounts)?;
+        let instruction_data = SwapToRatioInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SwapToRatio<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &9;
+
+    pub fn process(&mut self) -> ProgramResult {
+        // Vaults are validated by the swap itself, before anything moves
+        let config = Config::load(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        // The target input is solved on the constant product invariant
+        if config.curve_kind() != Config::CURVE_CONSTANT_PRODUCT {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let fee = config.effective_fee(ClockTimeSource.now()?);
+        let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
+        let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+        drop(config);
+
+        let Some((is_x, amount)) =
+            swap_to_ratio_input(reserve_x, reserve_y, self.instruction_data.ratio_bps, fee)?
+        else {
+            // Already at the target ratio
+            return Ok(());
+        };
+
+        Swap {
+            accounts: SwapAccounts {
+                user: self.accounts.user,
+                user_x_ata: self.accounts.user_x_ata,
+                user_y_ata: self.accounts.user_y_ata,
+                vault_x: self.accounts.vault_x,
+                vault_y: self.accounts.vault_y,
+                mint_lp: self.accounts.mint_lp,
+                config: self.accounts.config,
+                token_program: self.accounts.token_program,
+                global_config: self.accounts.global_config,
+            },
+            instruction_data: SwapInstructionData {
+                is_x,
+                amount: amount.min(self.instruction_data.max_in),
+                min: self.instruction_data.min_out,
+                expiration: self.instruction_data.expiration,
+                partial_fill: false,
+            },
+        }
+        .process()
+    }
+}
@@ -0,0 +1,250 @@
+use crate::ed25519::verify_preceding_ed25519;
+use crate::utils::{ensure_distinct_accounts, read_array};
+use crate::{AmmError, Swap, SwapAccounts, SwapInstructionData};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::instruction::{Seed, Signer};
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::{find_program_address, Pubkey};
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+use pinocchio_system::instructions::CreateAccount;
+use pinocchio_token::state::TokenAccount;
+
+// This instruction lets a relayer execute a swap on behalf of a user.
+// In order:
+// - checks the preceding instruction verifies the user's signed intent
+// - checks and bumps the user's nonce, creating its account on first use
+// - checks the relayer is the delegate of the user's input token account
+// - runs the regular swap with the relayer as transfer authority
+
+pub struct SwapWithDelegateAccounts<'a> {
+    /// Pays for the transaction, delegate of the user's input token account
+    pub relayer: &'a AccountInfo,
+    /// Owner of the token accounts, signs the intent off-chain
+    pub user: &'a AccountInfo,
+    pub user_x_ata: &'a AccountInfo,
+    pub user_y_ata: &'a AccountInfo,
+    pub vault_x: &'a AccountInfo,
+    pub vault_y: &'a AccountInfo,
+    pub mint_lp: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+    /// PDA of [b"nonce", config, user] holding the next expected nonce
+    pub nonce: &'a AccountInfo,
+    pub instructions_sysvar: &'a AccountInfo,
+    pub token_program: &'a AccountInfo,
+    pub system_program: &'a AccountInfo,
+    pub global_config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for SwapWithDelegateAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [relayer, user, user_x_ata, user_y_ata, vault_x, vault_y, mint_lp, config, nonce, instructions_sysvar, token_program, system_program, global_config] =
+            accounts
+        else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !relayer.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        ensure_distinct_accounts(&[user_x_ata, user_y_ata, vault_x, vault_y])?;
+
+        Ok(Self {
+            relayer,
+            user,
+            user_x_ata,
+            user_y_ata,
+            vault_x,
+            vault_y,
+            mint_lp,
+            config,
+            nonce,
+            instructions_sysvar,
+            token_program,
+            system_program,
+            global_config,
+        })
+    }
+}
+
+pub struct SwapWithDelegateInstructionData {
+    pub swap: SwapInstructionData,
+    /// Must match the user's next nonce, prevents replays
+    pub nonce: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for SwapWithDelegateInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        const SWAP_DATA_LEN: usize = size_of::<u64>() * 3 + size_of::<bool>();
+
+        if data.len().ne(&(SWAP_DATA_LEN + size_of::<u64>())) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let swap = SwapInstructionData::try_from(&data[..SWAP_DATA_LEN])?;
+        let nonce = u64::from_le_bytes(read_array(data, SWAP_DATA_LEN)?);
+
+        Ok(Self { swap, nonce })
+    }
+}
+
+impl SwapWithDelegateInstructionData {
+    pub const MESSAGE_LEN: usize = size_of::<Pubkey>() + size_of::<u8>() + size_of::<u64>() * 4;
+
+    /// Message the user signs off-chain to authorize the swap on `config`
+    pub fn message(&self, config: &Pubkey) -> [u8; Self::MESSAGE_LEN] {
+        let mut message = [0u8; Self::MESSAGE_LEN];
+        message[0..32].copy_from_slice(config);
+        message[32] = self.swap.is_x as u8;
+        message[33..41].copy_from_slice(&self.swap.amount.to_le_bytes());
+        message[41..49].copy_from_slice(&self.swap.min.to_le_bytes());
+        message[49..57].copy_from_slice(&self.swap.expiration.to_le_bytes());
+        message[57..65].copy_from_slice(&self.nonce.to_le_bytes());
+        message
+    }
+}
+
+pub struct SwapWithDelegate<'a> {
+    pub accounts: SwapWithDelegateAccounts<'a>,
+    pub instruction_data: SwapWithDelegateInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for SwapWithDelegate<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = SwapWithDelegateAccounts::try_from(accounts)?;
+        let instruction_data = SwapWithDelegateInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> SwapWithDelegate<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &7;
+
+    pub const NONCE_LEN: usize = size_of::<u64>();
+
+    pub fn process(&mut self) -> ProgramResult {
+        // The user signed exactly these parameters for this pool
+        verify_preceding_ed25519(
+            self.accounts.instructions_sysvar,
+            self.accounts.user.key(),
+            &self.instruction_data.message(self.accounts.config.key()),
+        )?;
+
+        self.consume_nonce()?;
+
+        // Both token accounts belong to the user, and the relayer
+        // is allowed to move the input
+        let (user_in, user_out) = match self.instruction_data.swap.is_x {
+            true => (self.accounts.user_x_ata, self.accounts.user_y_ata),
+            false => (self.accounts.user_y_ata, self.accounts.user_x_ata),
+        };
+
+        let user_in = TokenAccount::from_account_info(user_in)?;
+        let user_out = TokenAccount::from_account_info(user_out)?;
+
+        if user_in.owner().ne(self.accounts.user.key())
+            || user_out.owner().ne(self.accounts.user.key())
+        {
+            return Err(ProgramError::IllegalOwner);
+        }
+
+        if user_in.delegate() != Some(self.accounts.relayer.key())
+            || user_in.delegated_amount() < self.instruction_data.swap.amount
+        {
+            return Err(AmmError::InvalidDelegate.into());
+        }
+
+        drop(user_in);
+        drop(user_out);
+
+        Swap {
+            accounts: SwapAccounts {
+                user: self.accounts.relayer,
+                user_x_ata: self.accounts.user_x_ata,
+                user_y_ata: self.accounts.user_y_ata,
+                vault_x: self.accounts.vault_x,
+                vault_y: self.accounts.vault_y,
+                mint_lp: self.accounts.mint_lp,
+                config: self.accounts.config,
+                token_program: self.accounts.token_program,
+                global_config: self.accounts.global_config,
+            },
+            instruction_data: SwapInstructionData {
+                is_x: self.instruction_data.swap.is_x,
+                amount: self.instruction_data.swap.amount,
+                min: self.instruction_data.swap.min,
+                expiration: self.instruction_data.swap.expiration,
+                partial_fill: false,
+            },
+        }
+        .process()
+    }
+
+    /// Checks the nonce against the user's nonce account and bumps it
+    fn consume_nonce(&self) -> ProgramResult {
+        let (nonce_key, bump) = find_program_address(
+            &[
+                b"nonce",
+                self.accounts.config.key(),
+                self.accounts.user.key(),
+            ],
+            &crate::ID,
+        );
+
+        if nonce_key.ne(self.accounts.nonce.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+
+        // First delegated swap of this user, the relayer pays for the account
+        if self.accounts.nonce.data_is_empty() {
+            let bump_binding = [bump];
+            let nonce_seeds = [
+                Seed::from(b"nonce"),
+                Seed::from(self.accounts.config.key()),
+                Seed::from(self.accounts.user.key()),
+                Seed::from(&bump_binding),
+            ];
+
+            CreateAccount {
+                from: self.accounts.relayer,
+                to: self.accounts.nonce,
+                lamports: Rent::get()?.minimum_balance(Self::NONCE_LEN),
+                space: Self::NONCE_LEN as u64,
+                owner: &crate::ID,
+            }
+            .invoke_signed(&[Signer::from(&nonce_seeds)])?;
+        }
+
+        if self.accounts.nonce.owner().ne(&crate::ID)
+            || self.accounts.nonce.data_len() != Self::NONCE_LEN
+        {
+            return Err(ProgramError::InvalidAccountData);
+        }
+
+        let mut data = self.accounts.nonce.try_borrow_mut_data()?;
+        let expected = u64::from_le_bytes(read_array(&data, 0)?);
+
+        if self.instruction_data.nonce != expected {
+            return Err(AmmError::NonceMismatch.into());
+        }
+
+        let next = expected
+            .checked_add(1)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        data.copy_from_slice(&next.to_le_bytes());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,237 @@
+use crate::utils::read_array;
+use crate::{AmmError, ClockTimeSource, Config, TimeSource};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority tune a single
+// numeric parameter of the Config per call.
+
+pub struct UpdateConfigAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for UpdateConfigAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+/// Parameters of the Config settable through `UpdateConfig`
+#[repr(u8)]
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum ConfigParam {
+    MaxSwapPctBps = 0,
+    /// Value is reinterpreted as an `i64` unix timestamp
+    LockedUntil = 1,
+    FeatureFlags = 2,
+    MinDepositLp = 3,
+    MinSwapAmount = 4,
+    /// Q32.32 price of X in Y, 0 disables the floor
+    PriceFloor = 5,
+    /// Q32.32 price of X in Y, 0 disables the ceiling
+    PriceCeiling = 6,
+    /// Basis points, 0 lets swaps run without slippage protection
+    RequireMinSlippageBps = 7,
+    /// Seconds, can only be extended
+    FeeChangeTimelockSecs = 8,
+    /// Basis points, applies after the fee change timelock
+    Fee = 9,
+    /// 1 takes the swap fee on the output, 0 on the input
+    FeeOnOutput = 10,
+    /// Basis points, up to `Config::MAX_LIQUIDITY_FEE_BPS`
+    DepositFeeBps = 11,
+    /// Basis points, up to `Config::MAX_LIQUIDITY_FEE_BPS`
+    WithdrawFeeBps = 12,
+    /// 1 accepts instructions without an expiration, 0 requires one
+    AllowNoDeadline = 13,
+    /// Granularity of swap inputs and deposited amounts, 0 for none
+    LotSize = 14,
+    /// Basis points of the swap fee, up to `Config::MAX_PROTOCOL_FEE_BPS`,
+    /// applies after the fee change timelock
+    ProtocolFeeBps = 15,
+}
+
+impl TryFrom<u8> for ConfigParam {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(ConfigParam::MaxSwapPctBps),
+            1 => Ok(ConfigParam::LockedUntil),
+            2 => Ok(ConfigParam::FeatureFlags),
+            3 => Ok(ConfigParam::MinDepositLp),
+            4 => Ok(ConfigParam::MinSwapAmount),
+            5 => Ok(ConfigParam::PriceFloor),
+            6 => Ok(ConfigParam::PriceCeiling),
+            7 => Ok(ConfigParam::RequireMinSlippageBps),
+            8 => Ok(ConfigParam::FeeChangeTimelockSecs),
+            9 => Ok(ConfigParam::Fee),
+            10 => Ok(ConfigParam::FeeOnOutput),
+            11 => Ok(ConfigParam::DepositFeeBps),
+            12 => Ok(ConfigParam::WithdrawFeeBps),
+            13 => Ok(ConfigParam::AllowNoDeadline),
+            14 => Ok(ConfigParam::LotSize),
+            15 => Ok(ConfigParam::ProtocolFeeBps),
+            _ => Err(ProgramError::InvalidInstructionData),
+        }
+    }
+}
+
+pub struct UpdateConfigInstructionData {
+    pub param: ConfigParam,
+    /// Little endian value, narrowed to the parameter's width
+    pub value: u64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for UpdateConfigInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&(size_of::<u8>() + size_of::<u64>())) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let param = ConfigParam::try_from(data[0])?;
+        let value = u64::from_le_bytes(read_array(data, 1)?);
+
+        Ok(Self { param, value })
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshSerialize for UpdateConfigInstructionData {
+    fn serialize<W: borsh::io::Write>(&self, writer: &mut W) -> borsh::io::Result<()> {
+        (self.param as u8).serialize(writer)?;
+        self.value.serialize(writer)
+    }
+}
+
+#[cfg(feature = "borsh")]
+impl BorshDeserialize for UpdateConfigInstructionData {
+    fn deserialize_reader<R: borsh::io::Read>(reader: &mut R) -> borsh::io::Result<Self> {
+        let param = ConfigParam::try_from(u8::deserialize_reader(reader)?).map_err(|_| {
+            borsh::io::Error::new(borsh::io::ErrorKind::InvalidData, "unknown config param")
+        })?;
+
+        Ok(Self {
+            param,
+            value: u64::deserialize_reader(reader)?,
+        })
+    }
+}
+
+pub struct UpdateConfig<'a> {
+    pub accounts: UpdateConfigAccounts<'a>,
+    pub instruction_data: UpdateConfigInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for UpdateConfig<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = UpdateConfigAccounts::try_from(accounts)?;
+        let instruction_data = UpdateConfigInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> UpdateConfig<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &5;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        if config.authority_opt() != Some(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        let value = self.instruction_data.value;
+
+        match self.instruction_data.param {
+            ConfigParam::MaxSwapPctBps => {
+                let pct = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_max_swap_pct_bps(pct)
+            }
+            ConfigParam::LockedUntil => {
+                let locked_until = i64::try_from(value).map_err(|_| AmmError::InvalidTimestamp)?;
+                config.set_locked_until(locked_until)
+            }
+            ConfigParam::FeatureFlags => {
+                let flags = u8::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_feature_flags(flags)
+            }
+            ConfigParam::MinDepositLp => {
+                config.set_min_deposit_lp(value);
+                Ok(())
+            }
+            ConfigParam::MinSwapAmount => {
+                config.set_min_swap_amount(value);
+                Ok(())
+            }
+            ConfigParam::PriceFloor => config.set_price_floor(value),
+            ConfigParam::PriceCeiling => config.set_price_ceiling(value),
+            ConfigParam::RequireMinSlippageBps => {
+                let bps = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_require_min_slippage_bps(bps)
+            }
+            ConfigParam::FeeChangeTimelockSecs => {
+                let secs = u32::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_fee_change_timelock_secs(secs)
+            }
+            ConfigParam::Fee => {
+                let fee = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.schedule_fee(fee, ClockTimeSource.now()?)
+            }
+            ConfigParam::FeeOnOutput => match value {
+                0 | 1 => {
+                    config.set_fee_on_output(value == 1);
+                    Ok(())
+                }
+                _ => Err(ProgramError::InvalidArgument),
+            },
+            ConfigParam::DepositFeeBps => {
+                let bps = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_deposit_fee_bps(bps)
+            }
+            ConfigParam::WithdrawFeeBps => {
+                let bps = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.set_withdraw_fee_bps(bps)
+            }
+            ConfigParam::AllowNoDeadline => match value {
+                0 | 1 => {
+                    config.set_allow_no_deadline(value == 1);
+                    Ok(())
+                }
+                _ => Err(ProgramError::InvalidArgument),
+            },
+            ConfigParam::LotSize => {
+                config.set_lot_size(value);
+                Ok(())
+            }
+            ConfigParam::ProtocolFeeBps => {
+                let bps = u16::try_from(value).map_err(|_| ProgramError::InvalidArgument)?;
+                config.schedule_protocol_fee(bps, ClockTimeSource.now()?)
+            }
+        }
+    }
+}
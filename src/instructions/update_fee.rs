@@ -0,0 +1,91 @@
+use crate::utils::read_array;
+use crate::{ClockTimeSource, Config, TimeSource};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+
+// This instruction lets the pool authority change the swap fee. It goes
+// through the fee change timelock like `UpdateConfig`, so the new fee
+// applies right away only on pools without one.
+// Its discriminator is 18: the 4 it was first specified with was already
+// taken by `ReinitializeVaultAuthority`.
+
+pub struct UpdateFeeAccounts<'a> {
+    pub authority: &'a AccountInfo,
+    pub config: &'a AccountInfo,
+}
+
+impl<'a> TryFrom<&'a [AccountInfo]> for UpdateFeeAccounts<'a> {
+    type Error = ProgramError;
+
+    fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
+        let [authority, config] = accounts else {
+            return Err(ProgramError::NotEnoughAccountKeys);
+        };
+
+        if !authority.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
+
+        Ok(Self { authority, config })
+    }
+}
+
+pub struct UpdateFeeInstructionData {
+    /// Basis points, below 10_000
+    pub fee: u16,
+}
+
+impl<'a> TryFrom<&'a [u8]> for UpdateFeeInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&size_of::<u16>()) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        Ok(Self {
+            fee: u16::from_le_bytes(read_array(data, 0)?),
+        })
+    }
+}
+
+pub struct UpdateFee<'a> {
+    pub accounts: UpdateFeeAccounts<'a>,
+    pub instruction_data: UpdateFeeInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for UpdateFee<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = UpdateFeeAccounts::try_from(accounts)?;
+        let instruction_data = UpdateFeeInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> UpdateFee<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &18;
+
+    pub fn process(&mut self) -> ProgramResult {
+        let mut config = Config::load_mut(self.accounts.config)?;
+
+        config.require_not_disabled()?;
+
+        // A pool without authority is immutable
+        let Some(authority) = config.authority_opt() else {
+            return Err(ProgramError::Immutable);
+        };
+
+        if authority.ne(self.accounts.authority.key()) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+
+        config.schedule_fee(self.instruction_data.fee, ClockTimeSource.now()?)
+    }
+}
@@ -1,14 +1,20 @@
-use crate::{AmmState, Config};
-use constant_product_curve::ConstantProduct;
+use crate::math::withdraw_amounts;
+#[cfg(feature = "strict-invariants")]
+use crate::utils::ensure_supply;
+use crate::utils::{
+    ensure_distinct_accounts, ensure_not_frozen, ensure_spl_token_program, read_array,
+};
+use crate::{
+    check_deadline, report_error_detail, AmmError, AmmState, ClockTimeSource, Config,
+    ErrorDetailKind, GlobalConfig, TimeSource, NO_DEADLINE,
+};
+#[cfg(feature = "borsh")]
+use borsh::{BorshDeserialize, BorshSerialize};
 use pinocchio::account_info::AccountInfo;
 use pinocchio::instruction::{Seed, Signer};
-use pinocchio::msg;
 use pinocchio::program_error::ProgramError;
-use pinocchio::pubkey::find_program_address;
-use pinocchio::sysvars::clock::Clock;
-use pinocchio::sysvars::Sysvar;
 use pinocchio::ProgramResult;
-use pinocchio_token::instructions::{Burn, Transfer};
+use pinocchio_token::instructions::{Burn, CloseAccount, Transfer};
 use pinocchio_token::state::{Mint, TokenAccount};
 
 pub struct WithdrawAccounts<'a> {
@@ -21,24 +27,40 @@ pub struct WithdrawAccounts<'a> {
     pub user_lp_ata: &'a AccountInfo,
     pub config: &'a AccountInfo,
     pub token_program: &'a AccountInfo,
+    /// Protocol global config, checked for the global pause
+    pub global_config: &'a AccountInfo,
+    /// Optional receiver of the LP ATA rent, when passed the LP ATA
+    /// is closed once a withdraw leaves it empty
+    pub lp_rent_destination: Option<&'a AccountInfo>,
 }
 
 impl<'a> TryFrom<&'a [AccountInfo]> for WithdrawAccounts<'a> {
     type Error = ProgramError;
 
     fn try_from(accounts: &'a [AccountInfo]) -> Result<Self, Self::Error> {
-        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program] =
+        let [user, mint_lp, vault_x, vault_y, user_x_ata, user_y_ata, user_lp_ata, config, token_program, global_config, rest @ ..] =
             accounts
         else {
             return Err(ProgramError::NotEnoughAccountKeys);
         };
 
-        // Basic Accounts Checks
-        // SignerAccount::check(initializer)?;
-        // MintInterface::check(mint_lp)?;
+        let lp_rent_destination = match rest {
+            [] => None,
+            [lp_rent_destination] => Some(lp_rent_destination),
+            _ => return Err(ProgramError::InvalidArgument),
+        };
+
+        // Token accounts must all be distinct, a user ATA aliasing a vault
+        // would turn transfers into no-ops
+        ensure_distinct_accounts(&[user_x_ata, user_y_ata, user_lp_ata, vault_x, vault_y])?;
+
+        // The user authorizes the LP burn, reject a missing signature
+        // here rather than through a failed CPI
+        if !user.is_signer() {
+            return Err(ProgramError::MissingRequiredSignature);
+        }
 
-        // check initializer is signer
-        // but it will be checked downstream maybe?
+        ensure_spl_token_program(token_program)?;
 
         Ok(Self {
             user,
@@ -50,10 +72,13 @@ impl<'a> TryFrom<&'a [AccountInfo]> for WithdrawAccounts<'a> {
             user_lp_ata,
             config,
             token_program,
+            global_config,
+            lp_rent_destination,
         })
     }
 }
 
+#[cfg_attr(feature = "borsh", derive(BorshSerialize, BorshDeserialize))]
 pub struct WithdrawInstructionData {
     pub amount: u64,
     pub min_x: u64,
@@ -65,25 +90,35 @@ impl<'a> TryFrom<&'a [u8]> for WithdrawInstructionData {
     type Error = ProgramError;
 
     fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
-        if data.len().ne(&(size_of::<u64>() * 4)) {
+        Self::parse(data, &ClockTimeSource)
+    }
+}
+
+impl WithdrawInstructionData {
+    /// Parses the instruction data, checking the expiration against `time`
+    pub fn parse<T: TimeSource>(data: &[u8], time: &T) -> Result<Self, ProgramError> {
+        const DATA_LEN: usize = size_of::<u64>() * 4;
+
+        // The expiration is omittable, see `NO_DEADLINE`
+        if data.len().ne(&DATA_LEN) && data.len().ne(&(DATA_LEN - size_of::<i64>())) {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        let amount = u64::from_le_bytes(data[0..8].try_into().unwrap());
+        let amount = u64::from_le_bytes(read_array(data, 0)?);
 
         if amount == 0 {
             return Err(ProgramError::InvalidInstructionData);
         }
 
-        let min_x = u64::from_le_bytes(data[8..16].try_into().unwrap());
-        let min_y = u64::from_le_bytes(data[16..24].try_into().unwrap());
-        let expiration = i64::from_le_bytes(data[24..32].try_into().unwrap());
+        let min_x = u64::from_le_bytes(read_array(data, 8)?);
+        let min_y = u64::from_le_bytes(read_array(data, 16)?);
+        let expiration = match data.len() {
+            DATA_LEN => i64::from_le_bytes(read_array(data, 24)?),
+            _ => NO_DEADLINE,
+        };
 
         // Check signature expiration
-        let now = Clock::get()?.unix_timestamp;
-        if now > expiration {
-            return Err(ProgramError::InvalidInstructionData);
-        }
+        check_deadline(expiration, time)?;
 
         Ok(Self {
             amount,
@@ -118,37 +153,34 @@ impl<'a> Withdraw<'a> {
     pub const DISCRIMINATOR: &'a u8 = &2;
 
     pub fn process(&mut self) -> ProgramResult {
+        GlobalConfig::check_not_paused(self.accounts.global_config)?;
+
         let config = Config::load(self.accounts.config)?;
 
-        if config.state().eq(&(AmmState::Disabled as u8)) {
-            return Err(ProgramError::InvalidAccountData);
+        config.require_not_disabled()?;
+
+        config.check_deadline_required(self.instruction_data.expiration)?;
+
+        if !config.feature_enabled(Config::FEATURE_WITHDRAW) {
+            return Err(AmmError::FeatureDisabled.into());
         }
 
-        // Check if the vault_x ATA is valid
-        let (vault_x, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_x(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
-
-        if vault_x.ne(self.accounts.vault_x.key()) {
-            return Err(ProgramError::InvalidAccountData);
+        // Liquidity lock, lifted when the pool is put in emergency withdraw only
+        // mode. `Disabled` can't be the emergency exit, it rejects withdrawals
+        // outright, see `Config::require_not_disabled`
+        if config.state().ne(&(AmmState::WithdrawOnly as u8))
+            && ClockTimeSource.now()? < config.locked_until()
+        {
+            return Err(AmmError::LiquidityLocked.into());
         }
 
-        // Check if the vault_y ATA is valid
-        let (vault_y, _) = find_program_address(
-            &[
-                self.accounts.config.key(),
-                self.accounts.token_program.key(),
-                config.mint_y(),
-            ],
-            &pinocchio_associated_token_account::ID,
-        );
-
-        if vault_y.ne(self.accounts.vault_y.key()) {
+        // Check the vaults are the ATAs of the config
+        let (vault_x, vault_y) = config.vault_keys(
+            self.accounts.config.key(),
+            self.accounts.token_program.key(),
+        )?;
+
+        if vault_x.ne(self.accounts.vault_x.key()) || vault_y.ne(self.accounts.vault_y.key()) {
             return Err(ProgramError::InvalidAccountData);
         }
 
@@ -157,26 +189,34 @@ impl<'a> Withdraw<'a> {
         let vault_x = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_x)? };
         let vault_y = unsafe { TokenAccount::from_account_info_unchecked(self.accounts.vault_y)? };
 
-        let (x, y) = match mint_lp.supply() == self.instruction_data.amount {
-            true => (vault_x.amount(), vault_y.amount()),
-            false => {
-                let amounts = ConstantProduct::xy_withdraw_amounts_from_l(
-                    vault_x.amount(),
-                    vault_y.amount(),
-                    mint_lp.supply(),
-                    self.instruction_data.amount,
-                    6,
-                )
-                .map_err(|_| ProgramError::InvalidArgument)?;
-
-                (amounts.x, amounts.y)
-            }
-        };
+        // LP amounts are only meaningful at the decimals the pool was created with
+        config.check_lp_decimals(mint_lp.decimals())?;
+
+        // The LP mint of this pool, and not another one minted by the config
+        config.check_lp_mint(self.accounts.mint_lp.key(), &mint_lp)?;
+
+        // Uncollected protocol fees aren't the LPs'
+        let (reserve_x, reserve_y) = config.lp_reserves(vault_x.amount(), vault_y.amount());
+
+        // Proportional amounts, rounded down in favor of the pool
+        let (x, y) = withdraw_amounts(
+            reserve_x,
+            reserve_y,
+            mint_lp.supply(),
+            self.instruction_data.amount,
+        )?;
 
-        msg!("Here 5!");
+        // Withdraw fee, left in the vaults for the remaining holders
+        let (x, y) =
+            config.withdraw_after_fee(x, y, self.instruction_data.amount, mint_lp.supply())?;
 
         // Check for slippage
-        if !(x >= self.instruction_data.min_x && y >= self.instruction_data.min_y) {
+        if x < self.instruction_data.min_x {
+            report_error_detail(ErrorDetailKind::Slippage, x, self.instruction_data.min_x);
+            return Err(ProgramError::InvalidArgument);
+        }
+        if y < self.instruction_data.min_y {
+            report_error_detail(ErrorDetailKind::Slippage, y, self.instruction_data.min_y);
             return Err(ProgramError::InvalidArgument);
         }
 
@@ -190,6 +230,15 @@ impl<'a> Withdraw<'a> {
             Seed::from(&config_bump_binding),
         ];
 
+        // Fail a frozen account clearly rather than inside a transfer
+        ensure_not_frozen(&[
+            self.accounts.vault_x,
+            self.accounts.vault_y,
+            self.accounts.user_x_ata,
+            self.accounts.user_y_ata,
+            self.accounts.user_lp_ata,
+        ])?;
+
         let signer = [Signer::from(&config_seeds)];
 
         // Transfer the amounts from the token accounts of the user to the vaults
@@ -209,6 +258,9 @@ impl<'a> Withdraw<'a> {
         }
         .invoke_signed(&signer)?;
 
+        #[cfg(feature = "strict-invariants")]
+        let supply_before = mint_lp.supply();
+
         Burn {
             account: self.accounts.user_lp_ata,
             mint: self.accounts.mint_lp,
@@ -217,6 +269,37 @@ impl<'a> Withdraw<'a> {
         }
         .invoke()?;
 
+        // The LP supply shrank by exactly the burned amount
+        #[cfg(feature = "strict-invariants")]
+        ensure_supply(
+            mint_lp.supply(),
+            supply_before.checked_sub(self.instruction_data.amount),
+        )?;
+
+        drop(config);
+
+        let mut config = Config::load_mut(self.accounts.config)?;
+        config.accrue_liquidity_seconds(ClockTimeSource.now()?);
+        config.debit_reserves(x, y);
+        config.debit_lp_supply(self.instruction_data.amount)?;
+        drop(config);
+
+        // Full exit, hand the LP ATA rent back if asked to
+        if let Some(lp_rent_destination) = self.accounts.lp_rent_destination {
+            let lp_left =
+                unsafe { TokenAccount::from_account_info_unchecked(self.accounts.user_lp_ata)? }
+                    .amount();
+
+            if lp_left == 0 {
+                CloseAccount {
+                    account: self.accounts.user_lp_ata,
+                    destination: lp_rent_destination,
+                    authority: self.accounts.user,
+                }
+                .invoke()?;
+            }
+        }
+
         Ok(())
     }
 }
@@ -0,0 +1,171 @@
+use crate::utils::read_array;
+use crate::{
+    check_deadline, report_error_detail, AmmError, ClockTimeSource, Config, ErrorDetailKind, Swap,
+    SwapAccounts, SwapInstructionData, Withdraw, WithdrawAccounts, WithdrawInstructionData,
+};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::program_error::ProgramError;
+use pinocchio::ProgramResult;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+// This instruction exits liquidity into a single token: it runs a
+// regular proportional withdraw, then swaps the other leg through the
+// pool. Takes the same accounts as `Withdraw`.
+//
+// The swapped leg pays the pool fee and moves the price like any swap,
+// so the payout is below the value of the proportional pair. The swap
+// also needs the pool to accept swaps, which excludes `WithdrawOnly`.
+
+pub struct WithdrawSingleSidedInstructionData {
+    /// LP amount to burn
+    pub amount: u64,
+    /// Whether the payout is in X, otherwise in Y
+    pub out_x: bool,
+    /// Minimum total payout, proportional share and swap output together
+    pub min_out: u64,
+    pub expiration: i64,
+}
+
+impl<'a> TryFrom<&'a [u8]> for WithdrawSingleSidedInstructionData {
+    type Error = ProgramError;
+
+    fn try_from(data: &'a [u8]) -> Result<Self, Self::Error> {
+        if data.len().ne(&(size_of::<u64>() * 3 + size_of::<bool>())) {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let amount = u64::from_le_bytes(read_array(data, 0)?);
+
+        if amount == 0 {
+            return Err(ProgramError::InvalidInstructionData);
+        }
+
+        let out_x = match data[8] {
+            0 => false,
+            1 => true,
+            _ => return Err(ProgramError::InvalidInstructionData),
+        };
+
+        let min_out = u64::from_le_bytes(read_array(data, 9)?);
+        let expiration = i64::from_le_bytes(read_array(data, 17)?);
+
+        // Check signature expiration
+        check_deadline(expiration, &ClockTimeSource)?;
+
+        Ok(Self {
+            amount,
+            out_x,
+            min_out,
+            expiration,
+        })
+    }
+}
+
+pub struct WithdrawSingleSided<'a> {
+    pub accounts: WithdrawAccounts<'a>,
+    pub instruction_data: WithdrawSingleSidedInstructionData,
+}
+
+impl<'a> TryFrom<(&'a [u8], &'a [AccountInfo])> for WithdrawSingleSided<'a> {
+    type Error = ProgramError;
+
+    fn try_from((data, accounts): (&'a [u8], &'a [AccountInfo])) -> Result<Self, Self::Error> {
+        let accounts = WithdrawAccounts::try_from(accounts)?;
+        let instruction_data = WithdrawSingleSidedInstructionData::try_from(data)?;
+
+        Ok(Self {
+            accounts,
+            instruction_data,
+        })
+    }
+}
+
+impl<'a> WithdrawSingleSided<'a> {
+    pub const DISCRIMINATOR: &'a u8 = &12;
+
+    pub fn process(&mut self) -> ProgramResult {
+        // The LP mint of this pool, checked ahead of both legs
+        Config::load(self.accounts.config)?.check_lp_mint(
+            self.accounts.mint_lp.key(),
+            &Mint::from_account_info(self.accounts.mint_lp)?,
+        )?;
+
+        let (user_out, user_in) = match self.instruction_data.out_x {
+            true => (self.accounts.user_x_ata, self.accounts.user_y_ata),
+            false => (self.accounts.user_y_ata, self.accounts.user_x_ata),
+        };
+
+        let out_before = TokenAccount::from_account_info(user_out)?.amount();
+        let in_before = TokenAccount::from_account_info(user_in)?.amount();
+
+        // Slippage is enforced on the total payout below
+        Withdraw {
+            accounts: WithdrawAccounts {
+                user: self.accounts.user,
+                mint_lp: self.accounts.mint_lp,
+                vault_x: self.accounts.vault_x,
+                vault_y: self.accounts.vault_y,
+                user_x_ata: self.accounts.user_x_ata,
+                user_y_ata: self.accounts.user_y_ata,
+                user_lp_ata: self.accounts.user_lp_ata,
+                config: self.accounts.config,
+                token_program: self.accounts.token_program,
+                global_config: self.accounts.global_config,
+                lp_rent_destination: self.accounts.lp_rent_destination,
+            },
+            instruction_data: WithdrawInstructionData {
+                amount: self.instruction_data.amount,
+                min_x: 0,
+                min_y: 0,
+                expiration: self.instruction_data.expiration,
+            },
+        }
+        .process()?;
+
+        // Only the leg released by the withdraw is swapped
+        let leg = TokenAccount::from_account_info(user_in)?
+            .amount()
+            .checked_sub(in_before)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if leg > 0 {
+            Swap {
+                accounts: SwapAccounts {
+                    user: self.accounts.user,
+                    user_x_ata: self.accounts.user_x_ata,
+                    user_y_ata: self.accounts.user_y_ata,
+                    vault_x: self.accounts.vault_x,
+                    vault_y: self.accounts.vault_y,
+                    mint_lp: self.accounts.mint_lp,
+                    config: self.accounts.config,
+                    token_program: self.accounts.token_program,
+                    global_config: self.accounts.global_config,
+                },
+                instruction_data: SwapInstructionData {
+                    is_x: !self.instruction_data.out_x,
+                    amount: leg,
+                    min: 0,
+                    expiration: self.instruction_data.expiration,
+                    partial_fill: false,
+                },
+            }
+            .process()?;
+        }
+
+        let payout = TokenAccount::from_account_info(user_out)?
+            .amount()
+            .checked_sub(out_before)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if payout < self.instruction_data.min_out {
+            report_error_detail(
+                ErrorDetailKind::Slippage,
+                payout,
+                self.instruction_data.min_out,
+            );
+            return Err(AmmError::SlippageExceeded.into());
+        }
+
+        Ok(())
+    }
+}
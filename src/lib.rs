@@ -6,12 +6,33 @@ use pinocchio::{
 };
 entrypoint!(process_instruction);
 
+#[cfg(feature = "client")]
+pub mod client;
+
+pub mod ed25519;
+
+pub mod errors;
+pub use errors::*;
+
+pub mod events;
+pub use events::*;
+
 pub mod instructions;
 pub use instructions::*;
 
+pub mod math;
+
 pub mod state;
 pub use state::*;
 
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
+
+pub mod time;
+pub use time::*;
+
+pub mod utils;
+
 // 22222222222222222222222222222222222222222222
 pub const ID: Pubkey = [
     0x0f, 0x1e, 0x6b, 0x14, 0x21, 0xc0, 0x4a, 0x07, 0x04, 0x31, 0x26, 0x5c, 0x19, 0xc5, 0xbb, 0xee,
@@ -23,6 +44,19 @@ fn process_instruction(
     accounts: &[AccountInfo],
     instruction_data: &[u8],
 ) -> ProgramResult {
+    let result = dispatch(accounts, instruction_data);
+
+    errors::log_error!(
+        instruction_data.first().copied().unwrap_or_default(),
+        &result
+    );
+
+    result
+}
+
+/// Routes the instruction to its handler by discriminator
+#[inline(always)]
+fn dispatch(accounts: &[AccountInfo], instruction_data: &[u8]) -> ProgramResult {
     match instruction_data.split_first() {
         Some((Initialize::DISCRIMINATOR, data)) => {
             Initialize::try_from((data, accounts))?.process()
@@ -30,6 +64,64 @@ fn process_instruction(
         Some((Deposit::DISCRIMINATOR, data)) => Deposit::try_from((data, accounts))?.process(),
         Some((Withdraw::DISCRIMINATOR, data)) => Withdraw::try_from((data, accounts))?.process(),
         Some((Swap::DISCRIMINATOR, data)) => Swap::try_from((data, accounts))?.process(),
+        Some((ReinitializeVaultAuthority::DISCRIMINATOR, data)) => {
+            ReinitializeVaultAuthority::try_from((data, accounts))?.process()
+        }
+        Some((UpdateConfig::DISCRIMINATOR, data)) => {
+            UpdateConfig::try_from((data, accounts))?.process()
+        }
+        Some((SetLabel::DISCRIMINATOR, data)) => SetLabel::try_from((data, accounts))?.process(),
+        Some((SwapWithDelegate::DISCRIMINATOR, data)) => {
+            SwapWithDelegate::try_from((data, accounts))?.process()
+        }
+        Some((SimulateWithdraw::DISCRIMINATOR, data)) => {
+            SimulateWithdraw::try_from((data, accounts))?.process()
+        }
+        Some((SwapToRatio::DISCRIMINATOR, data)) => {
+            SwapToRatio::try_from((data, accounts))?.process()
+        }
+        Some((InitializeGlobal::DISCRIMINATOR, data)) => {
+            InitializeGlobal::try_from((data, accounts))?.process()
+        }
+        Some((SetGlobalPause::DISCRIMINATOR, data)) => {
+            SetGlobalPause::try_from((data, accounts))?.process()
+        }
+        Some((WithdrawSingleSided::DISCRIMINATOR, data)) => {
+            WithdrawSingleSided::try_from((data, accounts))?.process()
+        }
+        Some((PoolHealth::DISCRIMINATOR, data)) => {
+            PoolHealth::try_from((data, accounts))?.process()
+        }
+        Some((FlashSwap::DISCRIMINATOR, data)) => FlashSwap::try_from((data, accounts))?.process(),
+        Some((MigrateSeed::DISCRIMINATOR, data)) => {
+            MigrateSeed::try_from((data, accounts))?.process()
+        }
+        Some((AccruedFees::DISCRIMINATOR, data)) => {
+            AccruedFees::try_from((data, accounts))?.process()
+        }
+        Some((Quote::DISCRIMINATOR, data)) => Quote::try_from((data, accounts))?.process(),
+        Some((UpdateFee::DISCRIMINATOR, data)) => UpdateFee::try_from((data, accounts))?.process(),
+        Some((NominateAuthority::DISCRIMINATOR, data)) => {
+            NominateAuthority::try_from((data, accounts))?.process()
+        }
+        Some((AcceptAuthority::DISCRIMINATOR, data)) => {
+            AcceptAuthority::try_from((data, accounts))?.process()
+        }
+        Some((SetCurveKind::DISCRIMINATOR, data)) => {
+            SetCurveKind::try_from((data, accounts))?.process()
+        }
+        Some((AdminSetState::DISCRIMINATOR, data)) => {
+            AdminSetState::try_from((data, accounts))?.process()
+        }
+        Some((CollectProtocolFees::DISCRIMINATOR, data)) => {
+            CollectProtocolFees::try_from((data, accounts))?.process()
+        }
+        Some((DepositSingleSided::DISCRIMINATOR, data)) => {
+            DepositSingleSided::try_from((data, accounts))?.process()
+        }
+        Some((MigrateConfig::DISCRIMINATOR, data)) => {
+            MigrateConfig::try_from((data, accounts))?.process()
+        }
         _ => Err(ProgramError::InvalidInstructionData),
     }
 }
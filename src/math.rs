@@ -0,0 +1,946 @@
+use crate::{AmmError, Config};
+use constant_product_curve::{ConstantProduct, LiquidityPair};
+use pinocchio::program_error::ProgramError;
+
+// Rounding policy: whenever an amount can't be represented exactly,
+// it is rounded in favor of the pool, so that `k` never decreases
+// because of rounding.
+// - deposits round the required inputs up
+// - withdrawals round the outputs down
+// - swaps round the output down (integer division in the curve)
+
+/// `a * b / c`, rounded down
+#[inline(always)]
+pub fn mul_div_down(a: u64, b: u64, c: u64) -> Result<u64, ProgramError> {
+    if c == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let result = (a as u128) * (b as u128) / (c as u128);
+    u64::try_from(result).map_err(|_| ProgramError::ArithmeticOverflow)
+}
+
+/// `a * b / c`, rounded up
+#[inline(always)]
+pub fn mul_div_up(a: u64, b: u64, c: u64) -> Result<u64, ProgramError> {
+    if c == 0 {
+        return Err(ProgramError::InvalidArgument);
+    }
+    let result = ((a as u128) * (b as u128)).div_ceil(c as u128);
+    u64::try_from(result).map_err(|_| ProgramError::ArithmeticOverflow)
+}
+
+/// Amounts of X and Y required to mint `lp` out of `supply`, rounded up
+#[inline(always)]
+pub fn deposit_amounts(
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+    lp: u64,
+) -> Result<(u64, u64), ProgramError> {
+    Ok((
+        mul_div_up(reserve_x, lp, supply)?,
+        mul_div_up(reserve_y, lp, supply)?,
+    ))
+}
+
+/// LP mintable with at most `max_x` and `max_y`, bound by whichever side is
+/// scarcer at the current ratio, rounded down. An empty pool mints the
+/// geometric mean of both amounts.
+#[inline(always)]
+pub fn deposit_lp_for_max(
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+    max_x: u64,
+    max_y: u64,
+) -> Result<u64, ProgramError> {
+    if supply == 0 {
+        // sqrt(u64::MAX * u64::MAX) fits back into a u64
+        return Ok(isqrt(max_x as u128 * max_y as u128) as u64);
+    }
+
+    Ok(mul_div_down(max_x, supply, reserve_x)?.min(mul_div_down(max_y, supply, reserve_y)?))
+}
+
+/// Amounts of X and Y released by burning `lp` out of `supply`, rounded down.
+/// Burning the whole supply releases the whole reserves.
+#[inline(always)]
+pub fn withdraw_amounts(
+    reserve_x: u64,
+    reserve_y: u64,
+    supply: u64,
+    lp: u64,
+) -> Result<(u64, u64), ProgramError> {
+    if lp == supply {
+        return Ok((reserve_x, reserve_y));
+    }
+
+    Ok((
+        mul_div_down(reserve_x, lp, supply)?,
+        mul_div_down(reserve_y, lp, supply)?,
+    ))
+}
+
+/// Integer square root, rounded down
+#[inline(always)]
+pub fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+    // Start from a power of two above the root, Newton's iterations
+    // then decrease monotonically towards it
+    let mut x = 1u128 << (128 - n.leading_zeros()).div_ceil(2);
+    loop {
+        let y = (x + n / x) / 2;
+        if y >= x {
+            return x;
+        }
+        x = y;
+    }
+}
+
+/// LP locked forever by the first deposit under `exact-bootstrap-lp`,
+/// so the pool can never be drained back to an empty supply
+pub const MINIMUM_LIQUIDITY: u64 = 1_000;
+
+/// LP minted to the first depositor under `exact-bootstrap-lp`, the geometric
+/// mean of the deposited amounts less `MINIMUM_LIQUIDITY`
+pub fn bootstrap_lp(x: u64, y: u64) -> Result<u64, ProgramError> {
+    // The square root of a product of two u64 always fits a u64
+    let lp = isqrt(x as u128 * y as u128) as u64;
+
+    if lp <= MINIMUM_LIQUIDITY {
+        return Err(AmmError::AmountTooSmall.into());
+    }
+
+    Ok(lp - MINIMUM_LIQUIDITY)
+}
+
+/// Input needed to move the reserves to `reserve_x / reserve_y == ratio_bps / 10_000`
+/// along the constant product, fee included.
+/// Returns whether X is the input and the amount, `None` when already there.
+pub fn swap_to_ratio_input(
+    reserve_x: u64,
+    reserve_y: u64,
+    ratio_bps: u64,
+    fee: u16,
+) -> Result<Option<(bool, u64)>, ProgramError> {
+    if reserve_x == 0 || reserve_y == 0 || ratio_bps == 0 || fee >= 10_000 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let k = (reserve_x as u128) * (reserve_y as u128);
+
+    // x' = sqrt(k * r), y' = sqrt(k / r)
+    let target_x = isqrt(
+        k.checked_mul(ratio_bps as u128)
+            .ok_or(ProgramError::ArithmeticOverflow)?
+            / 10_000,
+    );
+    let target_y = isqrt(
+        k.checked_mul(10_000)
+            .ok_or(ProgramError::ArithmeticOverflow)?
+            / ratio_bps as u128,
+    );
+
+    // Reaching the ratio would drain one side
+    if target_x == 0 || target_y == 0 {
+        return Err(AmmError::RatioUnreachable.into());
+    }
+
+    let (is_x, net) = if target_x > reserve_x as u128 {
+        (true, target_x - reserve_x as u128)
+    } else if target_y > reserve_y as u128 {
+        (false, target_y - reserve_y as u128)
+    } else {
+        return Ok(None);
+    };
+
+    // Gross the net input up for the fee taken on the input side
+    let gross = (net * 10_000).div_ceil(10_000 - fee as u128);
+
+    Ok(Some((
+        is_x,
+        u64::try_from(gross).map_err(|_| ProgramError::ArithmeticOverflow)?,
+    )))
+}
+
+/// Part of `amount_in` to swap so that the rest and the swap output
+/// match the post-swap reserves ratio, along the constant product with
+/// the fee on the input. Solves `gamma * s^2 + r * (1 + gamma) * s - a * r = 0`
+/// for `gamma = 1 - fee`, rounded down.
+pub fn single_sided_swap_in(
+    reserve_in: u64,
+    amount_in: u64,
+    fee: u16,
+) -> Result<u64, ProgramError> {
+    if reserve_in == 0 || fee >= 10_000 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    // Everything scaled by 10_000: s = (sqrt(b^2 + c) - b) / (2 * gamma)
+    let gamma = (10_000 - fee) as u128;
+    let b = reserve_in as u128 * (10_000 + gamma);
+    let ar = amount_in as u128 * reserve_in as u128;
+
+    // The radicand can exceed a u128, take the root of it scaled down by
+    // 4^k instead, for the smallest k that fits
+    let mut k = 0;
+    let root = loop {
+        let radicand = (b >> k)
+            .checked_mul(b >> k)
+            .zip((ar >> (2 * k)).checked_mul(4 * gamma * 10_000))
+            .and_then(|(b2, c)| b2.checked_add(c));
+        if let Some(radicand) = radicand {
+            break isqrt(radicand) << k;
+        }
+        k += 1;
+    };
+
+    let swap_in = root.saturating_sub(b) / (2 * gamma);
+
+    // Never more than the input, whatever the rounding of the scaled root
+    Ok(u64::try_from(swap_in).unwrap_or(u64::MAX).min(amount_in))
+}
+
+/// Constant product curve over the given reserves and LP supply.
+/// The last argument of `ConstantProduct::init` is the LP mint precision,
+/// which the curve uses to scale LP amounts and falls back to 6 decimals
+/// when `None`, so the pool's recorded LP decimals are always passed.
+#[inline(always)]
+pub fn init_curve(
+    reserve_x: u64,
+    reserve_y: u64,
+    lp_supply: u64,
+    fee: u16,
+    lp_decimals: u8,
+) -> Result<ConstantProduct, ProgramError> {
+    Ok(
+        ConstantProduct::init(reserve_x, reserve_y, lp_supply, fee, Some(lp_decimals))
+            .map_err(AmmError::from)?,
+    )
+}
+
+/// Output of a constant product swap of `amount_in` with the fee on the
+/// input, through the same curve as `Swap`, so off-chain quotes match
+/// on-chain execution to the unit. Pools taking the fee on the output or
+/// on the weighted curve are priced by `crate::price_swap` instead.
+pub fn quote_swap(
+    reserve_in: u64,
+    reserve_out: u64,
+    amount_in: u64,
+    fee: u16,
+) -> Result<u64, ProgramError> {
+    // The curve only uses the LP supply for deposits and withdrawals,
+    // `price_swap` passes the pool's along but it never moves the output,
+    // see `quote_swap_ignores_the_lp_supply`
+    let mut curve = init_curve(reserve_in, reserve_out, 0, fee, Config::LP_DECIMALS)?;
+
+    let swap_result = curve
+        .swap(LiquidityPair::X, amount_in, 0)
+        .map_err(AmmError::from)?;
+
+    Ok(swap_result.withdraw)
+}
+
+/// Imbalance of the reserves in basis points, `|x_value - y| / (x_value + y)`,
+/// with X valued in Y at `price` (Q32.32), or 1:1 when `price` is 0.
+/// Empty reserves are balanced.
+pub fn imbalance_bps(reserve_x: u64, reserve_y: u64, price: u64) -> u64 {
+    let x_value = match price {
+        0 => reserve_x as u128,
+        _ => (reserve_x as u128 * price as u128) >> 32,
+    };
+    let y_value = reserve_y as u128;
+
+    let total = x_value + y_value;
+    if total == 0 {
+        return 0;
+    }
+
+    // The difference never exceeds the total, so the result is at most 10_000
+    (x_value.abs_diff(y_value) * 10_000 / total) as u64
+}
+
+const ONE_Q64: u128 = 1 << 64;
+
+/// `2^(-2^-i)` in Q64.64 for `i` in `1..=63`, rounded up
+const EXP2_NEG_POW2: [u128; 63] = [
+    0xb504f333f9de6485,
+    0xd744fccad69d6af5,
+    0xeac0c6e7dd24392f,
+    0xf5257d152486cc2d,
+    0xfa83b2db722a033b,
+    0xfd3e0c0cf486c175,
+    0xfe9e115c7b8f884c,
+    0xff4ecb59511ec8a6,
+    0xffa756521c8daed2,
+    0xffd3a751c0f7e10c,
+    0xffe9d2b2f7db2756,
+    0xfff4e91bff1b8c3e,
+    0xfffa747ea0040665,
+    0xfffd3a3b7814eb54,
+    0xfffe9d1cc60ddab2,
+    0xffff4e8e25879bfb,
+    0xffffa7470363f452,
+    0xffffd3a37dda0314,
+    0xffffe9d1bdf703af,
+    0xfffff4e8debe025f,
+    0xfffffa746f4fa151,
+    0xfffffd3a37a3f8b1,
+    0xfffffe9d1bd1065b,
+    0xffffff4e8de845ae,
+    0xffffffa746f41377,
+    0xffffffd3a37a05e4,
+    0xffffffe9d1bd01fc,
+    0xfffffff4e8de80c1,
+    0xfffffffa746f4051,
+    0xfffffffd3a37a025,
+    0xfffffffe9d1bd012,
+    0xffffffff4e8de809,
+    0xffffffffa746f405,
+    0xffffffffd3a37a03,
+    0xffffffffe9d1bd02,
+    0xfffffffff4e8de81,
+    0xfffffffffa746f41,
+    0xfffffffffd3a37a1,
+    0xfffffffffe9d1bd1,
+    0xffffffffff4e8de9,
+    0xffffffffffa746f5,
+    0xffffffffffd3a37b,
+    0xffffffffffe9d1be,
+    0xfffffffffff4e8df,
+    0xfffffffffffa7470,
+    0xfffffffffffd3a38,
+    0xfffffffffffe9d1c,
+    0xffffffffffff4e8e,
+    0xffffffffffffa747,
+    0xffffffffffffd3a4,
+    0xffffffffffffe9d2,
+    0xfffffffffffff4e9,
+    0xfffffffffffffa75,
+    0xfffffffffffffd3b,
+    0xfffffffffffffe9e,
+    0xffffffffffffff4f,
+    0xffffffffffffffa8,
+    0xffffffffffffffd4,
+    0xffffffffffffffea,
+    0xfffffffffffffff5,
+    0xfffffffffffffffb,
+    0xfffffffffffffffe,
+    0xffffffffffffffff,
+];
+
+/// `log2(x)` in Q64.64 for `x >= 1`, rounded down, by repeated squaring
+/// of the mantissa
+fn log2_q64(x: u128) -> u128 {
+    let n = 127 - x.leading_zeros();
+    let mut result = (n as u128) << 64;
+
+    // Mantissa normalized into [1, 2) as Q2.62
+    let mut y = if n <= 62 {
+        x << (62 - n)
+    } else {
+        x >> (n - 62)
+    };
+
+    for i in 1..=64 {
+        y = (y * y) >> 62;
+        if y >= 1 << 63 {
+            y >>= 1;
+            result |= 1 << (64 - i);
+        }
+    }
+    result
+}
+
+/// `2^-r` in Q64.64 for `r` in Q64.64, rounded up, as the product of the
+/// table factors of the fractional bits
+fn exp2_neg_q64(r: u128) -> u128 {
+    let int = r >> 64;
+    if int >= 64 {
+        return 1;
+    }
+    let mut result = ONE_Q64;
+    for (i, factor) in EXP2_NEG_POW2.iter().enumerate() {
+        if r & (1 << (63 - i)) != 0 {
+            result = (result * factor).div_ceil(ONE_Q64);
+        }
+    }
+    (result >> int).max(1)
+}
+
+/// Output of a weighted constant product swap, `x^wx * y^wy = k`:
+/// `out = reserve_out * (1 - (reserve_in / (reserve_in + in))^(w_in / w_out))`,
+/// with the fee taken on the input. Rounded down, and one unit below to
+/// absorb the fixed point error, in favor of the pool.
+/// Returns the output and the fee.
+pub fn weighted_swap_out(
+    reserve_in: u64,
+    reserve_out: u64,
+    weight_in: u16,
+    weight_out: u16,
+    amount_in: u64,
+    fee: u16,
+) -> Result<(u64, u64), ProgramError> {
+    if reserve_in == 0 || reserve_out == 0 || weight_in == 0 || weight_out == 0 || fee >= 10_000 {
+        return Err(ProgramError::InvalidArgument);
+    }
+
+    let net_in = mul_div_down(amount_in, 10_000 - fee as u64, 10_000)?;
+    let fee_amount = amount_in - net_in;
+
+    // log2((reserve_in + net_in) / reserve_in), scaled by the weight ratio
+    let log = log2_q64(reserve_in as u128 + net_in as u128) - log2_q64(reserve_in as u128);
+    let exponent = log
+        .checked_mul(weight_in as u128)
+        .ok_or(ProgramError::ArithmeticOverflow)?
+        / weight_out as u128;
+
+    let remaining = exp2_neg_q64(exponent).min(ONE_Q64);
+    let out = ((reserve_out as u128 * (ONE_Q64 - remaining)) >> 64) as u64;
+
+    Ok((out.saturating_sub(1), fee_amount))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `(reserve_in, reserve_out, amount_in, fee_bps, uniswap_v2_out)`, the
+    /// output of `getAmountOut` computed by hand in exact integer arithmetic:
+    /// `a * (10_000 - fee) * r_out / (r_in * 10_000 + a * (10_000 - fee))`
+    const UNISWAP_V2_VECTORS: [(u64, u64, u64, u16, u64); 14] = [
+        // Balanced pools
+        (1_000_000, 1_000_000, 1_000, 30, 996),
+        (1_000_000, 1_000_000, 100_000, 30, 90_661),
+        (1_000_000, 1_000_000, 1_000_000, 30, 499_248),
+        (5_000_000_000, 5_000_000_000, 123_456_789, 30, 120_129_164),
+        (1_000_000, 1_000_000, 10_000, 100, 9_802),
+        (1_000_000, 1_000_000, 500_000, 100, 331_103),
+        (1_000_000, 1_000_000, 10_000, 300, 9_606),
+        (1_000_000, 1_000_000, 2_000_000, 300, 659_863),
+        // Imbalanced pools
+        (1_000_000, 50_000_000, 1_000, 30, 49_800),
+        (50_000_000, 1_000_000, 1_000_000, 30, 19_550),
+        (1_000_000_000, 3_000, 7_777_777, 100, 22),
+        (3_000, 1_000_000_000, 17, 300, 5_466_618),
+        (
+            10_000_000_000_000,
+            20_000_000_000_000,
+            1_000_000_000,
+            30,
+            1_993_801_218,
+        ),
+        (123_456, 654_321, 99_999, 100, 291_192),
+    ];
+
+    #[test]
+    fn quote_swap_matches_uniswap_v2_rounded_down() {
+        for (reserve_in, reserve_out, amount_in, fee, expected) in UNISWAP_V2_VECTORS {
+            let out = quote_swap(reserve_in, reserve_out, amount_in, fee).unwrap();
+
+            // Never more than the reference, the output rounds down in favor
+            // of the pool
+            assert!(
+                out <= expected,
+                "{reserve_in}/{reserve_out} in {amount_in} at {fee}: {out} > {expected}"
+            );
+
+            // Short by at most what truncating the fee off the input costs,
+            // under a unit of input at the spot price, plus the output rounding
+            let tolerance = reserve_out.div_ceil(reserve_in) + 1;
+            assert!(
+                expected - out <= tolerance,
+                "{reserve_in}/{reserve_out} in {amount_in} at {fee}: {out} < {expected} - {tolerance}"
+            );
+        }
+    }
+
+    #[test]
+    fn quote_swap_output_grows_with_input() {
+        let mut last = 0;
+        for amount_in in (1_000..=100_000).step_by(1_000) {
+            let out = quote_swap(1_000_000, 1_000_000, amount_in, 30).unwrap();
+            assert!(out >= last);
+            last = out;
+        }
+    }
+
+    #[test]
+    fn mul_div_rounds_each_way() {
+        assert_eq!(mul_div_down(7, 1, 2).unwrap(), 3);
+        assert_eq!(mul_div_up(7, 1, 2).unwrap(), 4);
+        // Exact quotients round to themselves
+        assert_eq!(mul_div_down(6, 5, 3).unwrap(), 10);
+        assert_eq!(mul_div_up(6, 5, 3).unwrap(), 10);
+        // The product is taken in 128 bits
+        assert_eq!(
+            mul_div_down(u64::MAX, u64::MAX, u64::MAX).unwrap(),
+            u64::MAX
+        );
+        assert_eq!(mul_div_up(u64::MAX, u64::MAX, u64::MAX).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn mul_div_rejects_zero_divisor_and_overflow() {
+        assert_eq!(mul_div_down(1, 1, 0), Err(ProgramError::InvalidArgument));
+        assert_eq!(mul_div_up(1, 1, 0), Err(ProgramError::InvalidArgument));
+        assert_eq!(
+            mul_div_down(u64::MAX, 2, 1),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+        assert_eq!(
+            mul_div_up(u64::MAX, 2, 1),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn withdraw_amounts_round_down() {
+        // 10 / 3 and 20 / 3
+        assert_eq!(withdraw_amounts(10, 20, 3, 1).unwrap(), (3, 6));
+        // The deposit for the same LP rounds up, a round trip never profits
+        assert_eq!(deposit_amounts(10, 20, 3, 1).unwrap(), (4, 7));
+    }
+
+    #[test]
+    fn withdraw_amounts_release_everything_on_full_burn() {
+        assert_eq!(withdraw_amounts(10, 20, 3, 3).unwrap(), (10, 20));
+
+        // Burning the supply piece by piece never releases more than the
+        // reserves, the last holder takes the rounding dust
+        let (mut reserve_x, mut reserve_y, mut supply) = (10, 20, 3);
+        let mut released = (0, 0);
+        while supply > 0 {
+            let (x, y) = withdraw_amounts(reserve_x, reserve_y, supply, 1).unwrap();
+            released = (released.0 + x, released.1 + y);
+            reserve_x -= x;
+            reserve_y -= y;
+            supply -= 1;
+        }
+        assert_eq!(released, (10, 20));
+    }
+
+    #[test]
+    fn swap_to_ratio_input_reaches_the_ratio() {
+        // 1:4 to 1:2, X in: the net input lands X on sqrt(k / 2)
+        let (is_x, amount) = swap_to_ratio_input(10_000_000, 40_000_000, 5_000, 30)
+            .unwrap()
+            .unwrap();
+        assert!(is_x);
+        let net = amount * 9_970 / 10_000;
+        assert_eq!(net, 14_142_135 - 10_000_000);
+
+        let out = quote_swap(10_000_000, 40_000_000, amount, 30).unwrap();
+        // Within a basis point, the output rounds down
+        let ratio = (10_000_000 + net) * 10_000 / (40_000_000 - out);
+        assert!((4_999..=5_000).contains(&ratio), "{ratio}");
+
+        // And back, Y in
+        let (is_x, _) = swap_to_ratio_input(14_142_135, 28_284_271, 2_500, 30)
+            .unwrap()
+            .unwrap();
+        assert!(!is_x);
+    }
+
+    #[test]
+    fn single_sided_swap_in_without_fee() {
+        // s^2 + 2rs - ar = 0 at r = 10M, a = 30M: s = r * (sqrt(4) - 1)
+        assert_eq!(
+            single_sided_swap_in(10_000_000, 30_000_000, 0),
+            Ok(10_000_000)
+        );
+    }
+
+    #[test]
+    fn single_sided_swap_in_balances_the_legs() {
+        for (reserve_in, reserve_out, amount_in) in [
+            (10_000_000u64, 40_000_000u64, 1_000_000u64),
+            (40_000_000, 10_000_000, 4_000_000),
+            (1_000_000, 1_000_000, 50_000_000),
+        ] {
+            let swap_in = single_sided_swap_in(reserve_in, amount_in, 30).unwrap();
+            let out = quote_swap(reserve_in, reserve_out, swap_in, 30).unwrap();
+
+            // The rest and the output sit at the post-swap reserves ratio,
+            // within a basis point
+            let rest = (amount_in - swap_in) as u128;
+            let lhs = rest * (reserve_out - out) as u128;
+            let rhs = out as u128 * (reserve_in + swap_in) as u128;
+            assert!(lhs.abs_diff(rhs) * 10_000 <= rhs, "{lhs} vs {rhs}");
+        }
+    }
+
+    #[test]
+    fn single_sided_swap_in_stays_within_the_input() {
+        assert_eq!(
+            single_sided_swap_in(0, 1_000, 30),
+            Err(ProgramError::InvalidArgument)
+        );
+        assert_eq!(
+            single_sided_swap_in(1_000, 1_000, 10_000),
+            Err(ProgramError::InvalidArgument)
+        );
+        // The radicand overflows a u128 and is scaled down
+        for (reserve_in, amount_in) in [(u64::MAX, u64::MAX), (1, u64::MAX), (u64::MAX, 1)] {
+            let swap_in = single_sided_swap_in(reserve_in, amount_in, 30).unwrap();
+            assert!(swap_in <= amount_in);
+        }
+    }
+
+    #[test]
+    fn swap_to_ratio_input_stops_at_the_ratio() {
+        assert_eq!(swap_to_ratio_input(1_000, 4_000, 2_500, 30).unwrap(), None);
+    }
+
+    #[test]
+    fn swap_to_ratio_input_rejects_draining_ratios() {
+        // Either target side would round down to nothing
+        for ratio_bps in [1, u32::MAX as u64] {
+            assert_eq!(
+                swap_to_ratio_input(1, 1, ratio_bps, 30),
+                Err(AmmError::RatioUnreachable.into())
+            );
+        }
+        assert!(swap_to_ratio_input(0, 1, 10_000, 30).is_err());
+    }
+
+    #[test]
+    fn init_curve_at_the_default_decimals_matches_none() {
+        let mut curve = init_curve(1_000_000, 4_000_000, 1_000_000, 30, 6).unwrap();
+        let mut default = ConstantProduct::init(1_000_000, 4_000_000, 1_000_000, 30, None).unwrap();
+
+        for amount in [1_000, 100_000, 1_000_000] {
+            let a = curve.swap(LiquidityPair::X, amount, 0).unwrap();
+            let b = default.swap(LiquidityPair::X, amount, 0).unwrap();
+            assert_eq!(
+                (a.deposit, a.withdraw, a.fee),
+                (b.deposit, b.withdraw, b.fee)
+            );
+        }
+    }
+
+    #[test]
+    fn curve_precision_follows_the_lp_decimals() {
+        // 0.1 LP out of 1_000_000 at 9 decimals, a share the default 6
+        // decimals of precision can't represent
+        let (x, y, supply, lp) = (
+            1_000_000_000_000,
+            1_000_000_000_000,
+            10u64.pow(15),
+            10u64.pow(8),
+        );
+
+        let precise = ConstantProduct::xy_deposit_amounts_from_l(x, y, supply, lp, 9).unwrap();
+        assert_eq!((precise.x, precise.y), (100_000, 100_000));
+
+        let coarse = ConstantProduct::xy_deposit_amounts_from_l(x, y, supply, lp, 6);
+        assert!(!matches!(coarse, Ok(amounts) if amounts.x == precise.x));
+    }
+
+    #[test]
+    fn weighted_swap_at_50_50_matches_the_constant_product() {
+        for amount in [1_000, 100_000, 1_000_000, 25_000_000] {
+            let (out, fee) =
+                weighted_swap_out(10_000_000, 40_000_000, 5_000, 5_000, amount, 30).unwrap();
+            let expected = quote_swap(10_000_000, 40_000_000, amount, 30).unwrap();
+            // Never above, within the unit given up to the fixed point error
+            assert!(
+                out <= expected && expected - out <= 2,
+                "{out} vs {expected}"
+            );
+            assert_eq!(fee, amount - amount * 9_970 / 10_000);
+        }
+    }
+
+    #[test]
+    fn weighted_swap_at_80_20() {
+        // 80% of the value in X, out = y * (1 - (x / (x + in))^4)
+        let (out, _) = weighted_swap_out(10_000_000, 40_000_000, 8_000, 2_000, 100_000, 0).unwrap();
+        let expected = 40_000_000.0 * (1.0 - (10_000_000.0f64 / 10_100_000.0).powi(4));
+        assert!(out as f64 <= expected && expected - (out as f64) < 3.0);
+
+        // Four times the output of the 50/50 pool, give or take the curvature
+        let (even, _) =
+            weighted_swap_out(10_000_000, 40_000_000, 5_000, 5_000, 100_000, 0).unwrap();
+        assert!(out > even * 3 && out < even * 4);
+
+        // And the other way around, a quarter
+        let (back, _) =
+            weighted_swap_out(10_000_000, 40_000_000, 2_000, 8_000, 100_000, 0).unwrap();
+        assert!(back < even / 3);
+    }
+
+    #[test]
+    fn curve_errors_keep_their_cause() {
+        use constant_product_curve::CurveError;
+
+        let cases = [
+            (CurveError::Overflow, AmmError::CurveOverflow, 28),
+            (CurveError::Underflow, AmmError::CurveUnderflow, 29),
+            (
+                CurveError::InvalidPrecision,
+                AmmError::CurvePrecisionLoss,
+                30,
+            ),
+            (CurveError::InvalidFeeAmount, AmmError::CurveFailed, 36),
+        ];
+        for (curve_error, error, code) in cases {
+            assert_eq!(AmmError::from(curve_error), error);
+            assert_eq!(ProgramError::from(error), ProgramError::Custom(code));
+        }
+    }
+
+    #[test]
+    fn swap_past_the_u64_reserves_is_a_curve_overflow() {
+        let mut curve = init_curve(u64::MAX - 1, 1_000_000, 1_000_000, 30, 6).unwrap();
+        let error = curve
+            .swap(LiquidityPair::X, u64::MAX, 0)
+            .map_err(AmmError::from);
+        assert_eq!(error.err(), Some(AmmError::CurveOverflow));
+    }
+
+    #[test]
+    fn curve_errors_surface_through_the_pricing() {
+        // Converted at the call site rather than flattened into `Custom(1)`,
+        // which is `SwapExceedsReserveLimit`
+        assert_eq!(
+            quote_swap(u64::MAX - 1, 1_000_000, u64::MAX, 30),
+            Err(AmmError::CurveOverflow.into())
+        );
+        assert_ne!(
+            ProgramError::from(AmmError::CurveFailed),
+            ProgramError::from(AmmError::SwapExceedsReserveLimit)
+        );
+    }
+
+    #[test]
+    fn bootstrap_lp_is_the_geometric_mean_less_the_minimum() {
+        assert_eq!(
+            bootstrap_lp(10_000_000, 40_000_000),
+            Ok(20_000_000 - MINIMUM_LIQUIDITY)
+        );
+        // Rounded down, sqrt(1_000 * 1_003) is 1_001.5
+        assert_eq!(bootstrap_lp(1_000, 1_003), Ok(1));
+        assert_eq!(
+            bootstrap_lp(u64::MAX, u64::MAX),
+            Ok(u64::MAX - MINIMUM_LIQUIDITY)
+        );
+    }
+
+    #[test]
+    fn bootstrap_lp_rejects_the_minimum_liquidity() {
+        for (x, y) in [
+            (1_000, 1_000),
+            (1_000, 1_002),
+            (1, 1_000_000),
+            (0, u64::MAX),
+        ] {
+            assert_eq!(bootstrap_lp(x, y), Err(AmmError::AmountTooSmall.into()));
+        }
+    }
+
+    /// xorshift64, deterministic so a failure replays
+    fn next(state: &mut u64) -> u64 {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        *state
+    }
+
+    #[test]
+    fn rounding_never_decreases_k() {
+        let (mut reserve_x, mut reserve_y, mut supply) =
+            (1_000_000_007u64, 3_000_000_019, 1_732_050_808);
+        let mut rng = 0x9e37_79b9_7f4a_7c15;
+
+        for _ in 0..10_000 {
+            let (x, y, lp) = (reserve_x as u128, reserve_y as u128, supply as u128);
+
+            match next(&mut rng) % 3 {
+                // Deposits and withdrawals of up to a tenth of the supply,
+                // neither may dilute the reserves backing each LP
+                0 if reserve_x < 1 << 48 && reserve_y < 1 << 48 => {
+                    let amount = next(&mut rng) % (supply / 10) + 1;
+                    let (dx, dy) = deposit_amounts(reserve_x, reserve_y, supply, amount).unwrap();
+                    reserve_x += dx;
+                    reserve_y += dy;
+                    supply += amount;
+                }
+                1 if supply > 1_000_000 => {
+                    let amount = next(&mut rng) % (supply / 10) + 1;
+                    let (dx, dy) = withdraw_amounts(reserve_x, reserve_y, supply, amount).unwrap();
+                    reserve_x -= dx;
+                    reserve_y -= dy;
+                    supply -= amount;
+                }
+                // Swaps of up to a tenth of the input reserve, either way,
+                // at a fee of 0 to 1%
+                _ => {
+                    let is_x = next(&mut rng) % 2 == 0;
+                    let fee = (next(&mut rng) % 101) as u16;
+                    let (reserve_in, reserve_out) = match is_x {
+                        true => (reserve_x, reserve_y),
+                        false => (reserve_y, reserve_x),
+                    };
+                    let amount = next(&mut rng) % (reserve_in / 10) + 1;
+                    let Ok(out) = quote_swap(reserve_in, reserve_out, amount, fee) else {
+                        continue;
+                    };
+                    let (reserve_in, reserve_out) = (reserve_in + amount, reserve_out - out);
+                    (reserve_x, reserve_y) = match is_x {
+                        true => (reserve_in, reserve_out),
+                        false => (reserve_out, reserve_in),
+                    };
+                }
+            }
+
+            // `k` never shrinks, and neither does each reserve per LP
+            let (new_x, new_y, new_lp) = (reserve_x as u128, reserve_y as u128, supply as u128);
+            if new_lp == lp {
+                assert!(new_x * new_y >= x * y, "{x} * {y} -> {new_x} * {new_y}");
+            } else {
+                assert!(new_x * lp >= x * new_lp, "{x} / {lp} -> {new_x} / {new_lp}");
+                assert!(new_y * lp >= y * new_lp, "{y} / {lp} -> {new_y} / {new_lp}");
+            }
+        }
+    }
+
+    #[test]
+    fn log2_q64_of_powers_of_two_is_exact() {
+        for n in 0..128 {
+            assert_eq!(log2_q64(1 << n), (n as u128) << 64);
+        }
+    }
+
+    #[test]
+    fn log2_q64_rounds_down() {
+        for x in [3u128, 5, 10, 1_000_000, u64::MAX as u128, u128::MAX] {
+            let expected = (x as f64).log2();
+            let got = log2_q64(x) as f64 / ONE_Q64 as f64;
+            assert!(got <= expected, "log2({x}): {got} > {expected}");
+            assert!(expected - got < 1e-12, "log2({x}): {got} < {expected}");
+        }
+    }
+
+    #[test]
+    fn exp2_neg_q64_of_integers_is_exact() {
+        assert_eq!(exp2_neg_q64(0), ONE_Q64);
+        for n in 1..64 {
+            assert_eq!(exp2_neg_q64((n as u128) << 64), ONE_Q64 >> n);
+        }
+        // Below the Q64.64 resolution, kept at the smallest positive value
+        assert_eq!(exp2_neg_q64(64 << 64), 1);
+        assert_eq!(exp2_neg_q64(u128::MAX), 1);
+    }
+
+    #[test]
+    fn exp2_neg_q64_rounds_up() {
+        // 2^-0.5, the first table entry
+        assert_eq!(exp2_neg_q64(1 << 63), EXP2_NEG_POW2[0]);
+
+        for r in [0.25f64, 0.3, 0.75, 1.5, 3.1415, 10.9] {
+            let expected = (-r).exp2();
+            let got = exp2_neg_q64((r * ONE_Q64 as f64) as u128) as f64 / ONE_Q64 as f64;
+            assert!(
+                (got - expected).abs() < 1e-12,
+                "2^-{r}: {got} != {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn exp2_neg_q64_inverts_log2_q64() {
+        // 2^-log2(x) = 1 / x, with log2 rounded down and 2^-r rounded up
+        for x in [3u128, 7, 1_000, 123_456_789] {
+            let got = exp2_neg_q64(log2_q64(x));
+            let expected = ONE_Q64 / x;
+            assert!(got >= expected, "1/{x}: {got} < {expected}");
+            assert!(
+                got - expected <= expected >> 40,
+                "1/{x}: {got} > {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_swap_out_at_equal_weights_is_the_constant_product() {
+        for (reserve_in, reserve_out, amount_in, fee, _) in UNISWAP_V2_VECTORS {
+            let (out, fee_amount) =
+                weighted_swap_out(reserve_in, reserve_out, 5_000, 5_000, amount_in, fee).unwrap();
+
+            let net_in = amount_in - fee_amount;
+            let expected = (reserve_out as u128 * net_in as u128
+                / (reserve_in as u128 + net_in as u128)) as u64;
+            // Below the exact output, by the unit absorbing the fixed point error
+            assert!(
+                out <= expected,
+                "{reserve_in}/{reserve_out} in {amount_in}: {out} > {expected}"
+            );
+            assert!(
+                expected - out <= 2,
+                "{reserve_in}/{reserve_out} in {amount_in}: {out} < {expected} - 2"
+            );
+        }
+    }
+
+    #[test]
+    fn weighted_swap_out_follows_the_weights() {
+        let (reserve_in, reserve_out, amount_in) = (1_000_000u64, 1_000_000u64, 100_000u64);
+
+        // 80/20: out = reserve_out * (1 - (reserve_in / (reserve_in + in))^4)
+        let (out, _) =
+            weighted_swap_out(reserve_in, reserve_out, 8_000, 2_000, amount_in, 0).unwrap();
+        let ratio = reserve_in as f64 / (reserve_in + amount_in) as f64;
+        let expected = (reserve_out as f64 * (1.0 - ratio.powi(4))) as u64;
+        assert!(
+            out <= expected && expected - out <= 2,
+            "{out} != {expected}"
+        );
+
+        // The heavier the input side, the more the same input is worth
+        let mut last = 0;
+        for weight_in in (1_000..=9_000).step_by(1_000) {
+            let (out, _) = weighted_swap_out(
+                reserve_in,
+                reserve_out,
+                weight_in,
+                10_000 - weight_in,
+                amount_in,
+                30,
+            )
+            .unwrap();
+            assert!(out > last);
+            last = out;
+        }
+    }
+
+    #[test]
+    fn weighted_swap_out_returns_the_fee() {
+        // 1_000 at 0.3%, the fee is what doesn't reach the curve
+        let (_, fee) = weighted_swap_out(1_000_000, 1_000_000, 5_000, 5_000, 1_000, 30).unwrap();
+        assert_eq!(fee, 3);
+        // The net input rounds down, the fee up
+        let (_, fee) = weighted_swap_out(1_000_000, 1_000_000, 5_000, 5_000, 1_001, 30).unwrap();
+        assert_eq!(fee, 4);
+        let (_, fee) = weighted_swap_out(1_000_000, 1_000_000, 5_000, 5_000, 1_000, 0).unwrap();
+        assert_eq!(fee, 0);
+    }
+
+    #[test]
+    fn weighted_swap_out_rejects_degenerate_pools() {
+        for (reserve_in, reserve_out, weight_in, weight_out, fee) in [
+            (0, 1_000_000, 5_000, 5_000, 30),
+            (1_000_000, 0, 5_000, 5_000, 30),
+            (1_000_000, 1_000_000, 0, 10_000, 30),
+            (1_000_000, 1_000_000, 10_000, 0, 30),
+            (1_000_000, 1_000_000, 5_000, 5_000, 10_000),
+        ] {
+            assert_eq!(
+                weighted_swap_out(reserve_in, reserve_out, weight_in, weight_out, 1_000, fee),
+                Err(ProgramError::InvalidArgument)
+            );
+        }
+    }
+}
@@ -1,6 +1,11 @@
+use crate::math::{isqrt, mul_div_up};
+use crate::utils::pubkey_from_base58;
+use crate::{AmmError, NO_DEADLINE};
 use core::mem::size_of;
 use pinocchio::account_info::{Ref, RefMut};
+use pinocchio::pubkey::{create_program_address, find_program_address};
 use pinocchio::{account_info::AccountInfo, program_error::ProgramError, pubkey::Pubkey};
+use pinocchio_token::state::Mint;
 
 #[repr(C)]
 pub struct Config {
@@ -18,6 +23,87 @@ pub struct Config {
     fee: [u8; 2],
     /// Bump seed for PDA derivation
     config_bump: [u8; 1],
+    /// Max share of a reserve a single swap can move, in basis points, disabled when 0
+    max_swap_pct_bps: [u8; 2],
+    /// Tracked amount of token X owned by liquidity providers, fees included
+    reserve_x: [u8; 8],
+    /// Tracked amount of token Y owned by liquidity providers, fees included
+    reserve_y: [u8; 8],
+    /// Unix timestamp before which liquidity can't be withdrawn
+    locked_until: [u8; 8],
+    /// Human readable label, zero padded
+    label: [u8; 16],
+    /// Bitfield of the operations enabled on top of the state, see `Config::FEATURE_*`
+    feature_flags: u8,
+    /// Smallest LP amount a deposit can mint, no floor when 0
+    min_deposit_lp: [u8; 8],
+    /// Smallest input amount a swap can take, no floor when 0
+    min_swap_amount: [u8; 8],
+    /// Cumulative fees in X per unit of LP supply, Q64.64, wraps on overflow
+    fee_growth_global_x: [u8; 16],
+    /// Cumulative fees in Y per unit of LP supply, Q64.64, wraps on overflow
+    fee_growth_global_y: [u8; 16],
+    /// Lowest price of X in Y a swap can leave the pool at, Q32.32, disabled when 0
+    price_floor: [u8; 8],
+    /// Highest price of X in Y a swap can leave the pool at, Q32.32, disabled when 0
+    price_ceiling: [u8; 8],
+    /// Decimals of the LP mint, as created by `Initialize`
+    lp_decimals: u8,
+    /// Widest slippage tolerance a swap `min` may imply, in basis points, disabled when 0
+    require_min_slippage_bps: [u8; 2],
+    /// Delay before a fee change applies, in seconds, changes are instant when 0
+    fee_change_timelock_secs: [u8; 4],
+    /// Fee replacing `fee` from `fee_effective_at` on
+    pending_fee: [u8; 2],
+    /// Unix timestamp the pending fee applies from, no pending fee when 0
+    fee_effective_at: [u8; 8],
+    /// Invariant the swaps price against, see `Config::CURVE_*`
+    curve_kind: u8,
+    /// Weight of X in the weighted curve, in basis points
+    weight_x: [u8; 2],
+    /// Weight of Y in the weighted curve, in basis points, `10_000 - weight_x`
+    weight_y: [u8; 2],
+    /// Unix timestamp the pool was initialized at
+    created_at: [u8; 8],
+    /// Takes the swap fee on the output instead of the input when 1
+    fee_on_output: u8,
+    /// Cumulative swap fees in X since inception, saturates
+    total_fee_x: [u8; 8],
+    /// Cumulative swap fees in Y since inception, saturates
+    total_fee_y: [u8; 8],
+    /// Share of the LP minted on deposit kept by the pool, in basis points
+    deposit_fee_bps: [u8; 2],
+    /// Share of the withdrawn amounts kept by the pool, in basis points
+    withdraw_fee_bps: [u8; 2],
+    /// Accepts instructions without an expiration when 1, see `NO_DEADLINE`
+    allow_no_deadline: u8,
+    /// Mirror of the LP mint supply, moved by deposits and withdrawals
+    lp_supply: [u8; 8],
+    /// Granularity swap inputs and deposited amounts must be multiples of, none when 0
+    lot_size: [u8; 8],
+    /// Cumulative `isqrt(reserve_x * reserve_y) * seconds`, for rewards programs, saturates
+    liquidity_seconds: [u8; 16],
+    /// Unix timestamp `liquidity_seconds` was last accrued at, 0 before the first accrual
+    last_accrual_ts: [u8; 8],
+    /// Authority nominated by the current one, taking over once it accepts,
+    /// none when [0u8; 32]
+    pending_authority: Pubkey,
+    /// Bumps of the vault ATAs, cached at initialization so instructions
+    /// don't grind them again, unset when [0] on older pools
+    vault_x_bump: [u8; 1],
+    vault_y_bump: [u8; 1],
+    /// Share of the swap fee kept for the protocol instead of the LPs, in basis points
+    protocol_fee_bps: [u8; 2],
+    /// Protocol share replacing `protocol_fee_bps` from `fee_effective_at` on
+    pending_protocol_fee_bps: [u8; 2],
+    /// LP mint of the pool, which `MigrateSeed` hands over to the new config
+    /// at its original address, so it can't be derived from the config key
+    mint_lp: Pubkey,
+    /// Kept zeroed for future fields, which carve their bytes from the front
+    /// of this block so `LEN` and the layout of existing accounts don't change.
+    /// Fields that don't fit anymore grow `LEN` instead, which needs existing
+    /// configs reallocated before loading, as `MigrateConfig` does for v0
+    reserved: [u8; 2],
 }
 
 #[repr(u8)]
@@ -27,6 +113,9 @@ pub enum AmmState {
     Initialized = 1u8,
     Disabled = 2u8,
     WithdrawOnly = 3u8,
+    /// Left behind by `MigrateSeed`, the pool lives on under its new seed.
+    /// Terminal: unlike `Disabled`, the authority can't move it out again
+    Migrated = 4u8,
 }
 
 impl AmmState {
@@ -35,24 +124,112 @@ impl AmmState {
     }
 }
 
+impl TryFrom<u8> for AmmState {
+    type Error = ProgramError;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0 => Ok(AmmState::Uninitialized),
+            1 => Ok(AmmState::Initialized),
+            2 => Ok(AmmState::Disabled),
+            3 => Ok(AmmState::WithdrawOnly),
+            4 => Ok(AmmState::Migrated),
+            _ => Err(ProgramError::InvalidAccountData),
+        }
+    }
+}
+
+// `LEN` is spelled out field by field, keep it in step with the struct
+const _: () = assert!(Config::LEN == size_of::<Config>());
+
 impl Config {
     pub const LEN: usize = size_of::<u8>()
+        + size_of::<u64>()
+        + size_of::<Pubkey>() * 3
+        + size_of::<u16>()
+        + size_of::<u8>()
+        + size_of::<u16>()
+        + size_of::<u64>() * 2
+        + size_of::<i64>()
+        + size_of::<[u8; 16]>()
+        + size_of::<u8>()
+        + size_of::<u64>() * 2
+        + size_of::<u128>() * 2
+        + size_of::<u64>() * 2
+        + size_of::<u8>()
+        + size_of::<u16>()
+        + size_of::<u32>()
+        + size_of::<u16>()
+        + size_of::<i64>()
+        + size_of::<u8>()
+        + size_of::<u16>() * 2
+        + size_of::<i64>()
+        + size_of::<u8>()
+        + size_of::<u64>() * 2
+        + size_of::<u16>() * 2
+        + size_of::<u8>()
+        + size_of::<u64>()
+        + size_of::<u64>()
+        + size_of::<u128>()
+        + size_of::<i64>()
+        + size_of::<Pubkey>()
+        + size_of::<[u8; 1]>() * 2
+        + size_of::<u16>() * 2
+        + size_of::<Pubkey>()
+        + size_of::<[u8; 2]>();
+
+    /// Length of the config accounts written by the first release, up to
+    /// `config_bump`. Every later field was appended, so a v0 account is a
+    /// prefix of the current layout, see `MigrateConfig`
+    pub const V0_LEN: usize = size_of::<u8>()
         + size_of::<u64>()
         + size_of::<Pubkey>() * 3
         + size_of::<u16>()
         + size_of::<u8>();
 
+    /// Decimals of the LP mint created by `Initialize`
+    pub const LP_DECIMALS: u8 = 6;
+
+    /// Highest deposit and withdraw fee, in basis points
+    pub const MAX_LIQUIDITY_FEE_BPS: u16 = 1_000;
+
+    /// Highest protocol share of the swap fee, in basis points
+    pub const MAX_PROTOCOL_FEE_BPS: u16 = 5_000;
+
+    /// Largest spot price move a curve switch may cause on a live pool, in basis points
+    pub const CURVE_SWITCH_TOLERANCE_BPS: u64 = 100;
+
+    /// `x * y = k`
+    pub const CURVE_CONSTANT_PRODUCT: u8 = 0;
+    /// `x^wx * y^wy = k`, with the weights in `weight_x` and `weight_y`
+    pub const CURVE_WEIGHTED: u8 = 1;
+
+    pub const FEATURE_DEPOSIT: u8 = 1 << 0;
+    pub const FEATURE_SWAP: u8 = 1 << 1;
+    pub const FEATURE_WITHDRAW: u8 = 1 << 2;
+    pub const FEATURE_ALL: u8 = Self::FEATURE_DEPOSIT | Self::FEATURE_SWAP | Self::FEATURE_WITHDRAW;
+
+    /// Rejects a config account of any length but `LEN`, telling v0
+    /// accounts apart so clients know to send `MigrateConfig` first
     #[inline(always)]
-    pub fn load(account_info: &AccountInfo) -> Result<Ref<Self>, ProgramError> {
-        if account_info.data_len() != Self::LEN {
-            return Err(ProgramError::InvalidAccountData);
+    pub fn check_len(len: usize) -> Result<(), ProgramError> {
+        match len {
+            Self::LEN => Ok(()),
+            Self::V0_LEN => Err(AmmError::ConfigNeedsMigration.into()),
+            _ => Err(ProgramError::InvalidAccountData),
         }
+    }
+
+    #[inline(always)]
+    pub fn load(account_info: &AccountInfo) -> Result<Ref<Self>, ProgramError> {
+        Self::check_len(account_info.data_len())?;
         if account_info.owner().ne(&crate::ID) {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(Ref::map(account_info.try_borrow_data()?, |data| unsafe {
+        let config = Ref::map(account_info.try_borrow_data()?, |data| unsafe {
             Self::from_bytes_unchecked(data)
-        }))
+        });
+        Ok(config)
     }
 
     /// # Safety
@@ -60,15 +237,12 @@ impl Config {
     /// blah blah blah
     #[inline(always)]
     pub unsafe fn load_unchecked(account_info: &AccountInfo) -> Result<&Self, ProgramError> {
-        if account_info.data_len() != Self::LEN {
-            return Err(ProgramError::InvalidAccountData);
-        }
+        Self::check_len(account_info.data_len())?;
         if account_info.owner() != &crate::ID {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(Self::from_bytes_unchecked(
-            account_info.borrow_data_unchecked(),
-        ))
+        let config = Self::from_bytes_unchecked(account_info.borrow_data_unchecked());
+        Ok(config)
     }
 
     /// Return a `Config` from the given bytes.
@@ -110,6 +284,16 @@ impl Config {
         &self.authority
     }
 
+    /// Authority of the pool, `None` when the pool is immutable
+    #[inline(always)]
+    pub fn authority_opt(&self) -> Option<&Pubkey> {
+        if self.authority == [0u8; 32] {
+            None
+        } else {
+            Some(&self.authority)
+        }
+    }
+
     #[inline(always)]
     pub fn mint_x(&self) -> &Pubkey {
         &self.mint_x
@@ -120,6 +304,11 @@ impl Config {
         &self.mint_y
     }
 
+    #[inline(always)]
+    pub fn mint_lp(&self) -> &Pubkey {
+        &self.mint_lp
+    }
+
     #[inline(always)]
     pub fn fee(&self) -> u16 {
         u16::from_le_bytes(self.fee)
@@ -131,24 +320,466 @@ impl Config {
     }
 
     #[inline(always)]
-    pub fn load_mut(account_info: &AccountInfo) -> Result<RefMut<Self>, ProgramError> {
-        if account_info.data_len() != Self::LEN {
+    pub fn max_swap_pct_bps(&self) -> u16 {
+        u16::from_le_bytes(self.max_swap_pct_bps)
+    }
+
+    /// Largest amount a single swap can move in or out of `reserve`
+    /// under the percentage circuit breaker, `None` when disabled
+    #[inline(always)]
+    pub fn max_swap_amount(&self, reserve: u64) -> Option<u64> {
+        let pct = self.max_swap_pct_bps();
+        if pct == 0 {
+            return None;
+        }
+        // pct <= 10_000, so the result fits back into a u64
+        Some(((reserve as u128) * (pct as u128) / 10_000) as u64)
+    }
+
+    #[inline(always)]
+    pub fn reserve_x(&self) -> u64 {
+        u64::from_le_bytes(self.reserve_x)
+    }
+
+    #[inline(always)]
+    pub fn reserve_y(&self) -> u64 {
+        u64::from_le_bytes(self.reserve_y)
+    }
+
+    #[inline(always)]
+    pub fn locked_until(&self) -> i64 {
+        i64::from_le_bytes(self.locked_until)
+    }
+
+    /// Label with the trailing zero padding trimmed
+    #[inline(always)]
+    pub fn label(&self) -> &[u8] {
+        let len = self
+            .label
+            .iter()
+            .rposition(|&b| b != 0)
+            .map_or(0, |i| i + 1);
+        &self.label[..len]
+    }
+
+    #[inline(always)]
+    pub fn feature_flags(&self) -> u8 {
+        self.feature_flags
+    }
+
+    #[inline(always)]
+    pub fn feature_enabled(&self, feature: u8) -> bool {
+        self.feature_flags & feature == feature
+    }
+
+    #[inline(always)]
+    pub fn min_deposit_lp(&self) -> u64 {
+        u64::from_le_bytes(self.min_deposit_lp)
+    }
+
+    #[inline(always)]
+    pub fn min_swap_amount(&self) -> u64 {
+        u64::from_le_bytes(self.min_swap_amount)
+    }
+
+    #[inline(always)]
+    pub fn fee_growth_global_x(&self) -> u128 {
+        u128::from_le_bytes(self.fee_growth_global_x)
+    }
+
+    #[inline(always)]
+    pub fn fee_growth_global_y(&self) -> u128 {
+        u128::from_le_bytes(self.fee_growth_global_y)
+    }
+
+    #[inline(always)]
+    pub fn price_floor(&self) -> u64 {
+        u64::from_le_bytes(self.price_floor)
+    }
+
+    #[inline(always)]
+    pub fn price_ceiling(&self) -> u64 {
+        u64::from_le_bytes(self.price_ceiling)
+    }
+
+    /// Whether reserves at `reserve_x`/`reserve_y` price X in Y within the band.
+    /// Prices are compared as Q32.32 without rounding, an empty X side is out of band.
+    #[inline(always)]
+    pub fn price_in_band(&self, reserve_x: u64, reserve_y: u64) -> bool {
+        let (floor, ceiling) = (self.price_floor(), self.price_ceiling());
+        if floor == 0 && ceiling == 0 {
+            return true;
+        }
+        if reserve_x == 0 {
+            return false;
+        }
+        // price = y / x, compared as y << 32 against bound * x to stay exact
+        let y = (reserve_y as u128) << 32;
+        let x = reserve_x as u128;
+        (floor == 0 || y >= floor as u128 * x) && (ceiling == 0 || y <= ceiling as u128 * x)
+    }
+
+    #[inline(always)]
+    pub fn lp_decimals(&self) -> u8 {
+        self.lp_decimals
+    }
+
+    /// Rejects an LP mint whose decimals differ from the recorded ones
+    #[inline(always)]
+    pub fn check_lp_decimals(&self, decimals: u8) -> Result<(), ProgramError> {
+        if decimals != self.lp_decimals {
+            return Err(AmmError::DecimalMismatch.into());
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn require_min_slippage_bps(&self) -> u16 {
+        u16::from_le_bytes(self.require_min_slippage_bps)
+    }
+
+    /// Smallest `min` accepted for a swap quoted at `quoted_out`, rounded up,
+    /// `None` when the requirement is disabled
+    #[inline(always)]
+    pub fn required_min_out(&self, quoted_out: u64) -> Option<u64> {
+        let bps = self.require_min_slippage_bps();
+        if bps == 0 {
+            return None;
+        }
+        // bps <= 10_000, so the result fits back into a u64
+        Some(((quoted_out as u128) * (10_000 - bps as u128)).div_ceil(10_000) as u64)
+    }
+
+    #[inline(always)]
+    pub fn fee_change_timelock_secs(&self) -> u32 {
+        u32::from_le_bytes(self.fee_change_timelock_secs)
+    }
+
+    #[inline(always)]
+    pub fn pending_fee(&self) -> u16 {
+        u16::from_le_bytes(self.pending_fee)
+    }
+
+    #[inline(always)]
+    pub fn fee_effective_at(&self) -> i64 {
+        i64::from_le_bytes(self.fee_effective_at)
+    }
+
+    /// Fee in force at `now`, the pending fee once its time has come
+    #[inline(always)]
+    pub fn effective_fee(&self, now: i64) -> u16 {
+        let effective_at = self.fee_effective_at();
+        if effective_at != 0 && now >= effective_at {
+            self.pending_fee()
+        } else {
+            self.fee()
+        }
+    }
+
+    #[inline(always)]
+    pub fn curve_kind(&self) -> u8 {
+        self.curve_kind
+    }
+
+    #[inline(always)]
+    pub fn weight_x(&self) -> u16 {
+        u16::from_le_bytes(self.weight_x)
+    }
+
+    #[inline(always)]
+    pub fn weight_y(&self) -> u16 {
+        u16::from_le_bytes(self.weight_y)
+    }
+
+    /// Vault ATAs of the pool at `config` and their bumps, as
+    /// `((vault_x, bump_x), (vault_y, bump_y))`. On-chain only, see
+    /// `crate::client::vault_addresses` off-chain
+    pub fn vault_addresses(
+        &self,
+        config: &Pubkey,
+        token_program: &Pubkey,
+    ) -> ((Pubkey, u8), (Pubkey, u8)) {
+        let vault = |mint: &Pubkey| {
+            find_program_address(
+                &[config, token_program, mint],
+                &pinocchio_associated_token_account::ID,
+            )
+        };
+        (vault(self.mint_x()), vault(self.mint_y()))
+    }
+
+    #[inline(always)]
+    pub fn created_at(&self) -> i64 {
+        i64::from_le_bytes(self.created_at)
+    }
+
+    /// Seconds elapsed since the pool was initialized, 0 for a clock behind it
+    #[inline(always)]
+    pub fn age_secs(&self, now: i64) -> i64 {
+        now.saturating_sub(self.created_at()).max(0)
+    }
+
+    #[inline(always)]
+    pub fn fee_on_output(&self) -> bool {
+        self.fee_on_output == 1
+    }
+
+    #[inline(always)]
+    pub fn total_fee_x(&self) -> u64 {
+        u64::from_le_bytes(self.total_fee_x)
+    }
+
+    #[inline(always)]
+    pub fn total_fee_y(&self) -> u64 {
+        u64::from_le_bytes(self.total_fee_y)
+    }
+
+    #[inline(always)]
+    pub fn deposit_fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.deposit_fee_bps)
+    }
+
+    #[inline(always)]
+    pub fn withdraw_fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.withdraw_fee_bps)
+    }
+
+    /// LP kept unminted out of a deposit minting `lp`, rounded up in favor
+    /// of the pool
+    #[inline(always)]
+    pub fn deposit_fee(&self, lp: u64) -> Result<u64, ProgramError> {
+        mul_div_up(lp, self.deposit_fee_bps() as u64, 10_000)
+    }
+
+    /// Share of `x` and `y` paid out by burning `lp` out of `supply`, the
+    /// withdraw fee left in the vaults rounded up in favor of the pool.
+    /// The last holder pays none: nobody would be left to accrue it to, and
+    /// reserves without LP supply would block the next bootstrap deposit.
+    #[inline(always)]
+    pub fn withdraw_after_fee(
+        &self,
+        x: u64,
+        y: u64,
+        lp: u64,
+        supply: u64,
+    ) -> Result<(u64, u64), ProgramError> {
+        if lp == supply {
+            return Ok((x, y));
+        }
+
+        let fee_bps = self.withdraw_fee_bps() as u64;
+        Ok((
+            x - mul_div_up(x, fee_bps, 10_000)?,
+            y - mul_div_up(y, fee_bps, 10_000)?,
+        ))
+    }
+
+    #[inline(always)]
+    pub fn allow_no_deadline(&self) -> bool {
+        self.allow_no_deadline == 1
+    }
+
+    /// Rejects an omitted expiration unless the pool opted out of deadlines
+    #[inline(always)]
+    pub fn check_deadline_required(&self, expiration: i64) -> Result<(), ProgramError> {
+        if expiration == NO_DEADLINE && !self.allow_no_deadline() {
+            return Err(AmmError::DeadlineRequired.into());
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn lp_supply(&self) -> u64 {
+        u64::from_le_bytes(self.lp_supply)
+    }
+
+    /// Rejects a mirrored LP supply that drifted from the LP mint
+    #[inline(always)]
+    pub fn validate_lp_mirror(&self, mint_lp: &Mint) -> Result<(), ProgramError> {
+        if self.lp_supply() != mint_lp.supply() {
+            return Err(AmmError::SupplyMismatch.into());
+        }
+        Ok(())
+    }
+
+    /// Rejects an LP mint other than the one of the pool, at `key`. Under
+    /// `strict-invariants` the supply must also match the mirror
+    #[inline(always)]
+    pub fn check_lp_mint(&self, key: &Pubkey, mint_lp: &Mint) -> Result<(), ProgramError> {
+        if key.ne(&self.mint_lp) {
             return Err(ProgramError::InvalidAccountData);
         }
+        #[cfg(feature = "strict-invariants")]
+        self.validate_lp_mirror(mint_lp)?;
+        #[cfg(not(feature = "strict-invariants"))]
+        let _ = mint_lp;
+        Ok(())
+    }
+
+    /// Rejects any instruction on a disabled or migrated pool, ahead of the
+    /// finer state rules of each instruction
+    #[inline(always)]
+    pub fn require_not_disabled(&self) -> Result<(), ProgramError> {
+        if self.state == AmmState::Disabled as u8 || self.state == AmmState::Migrated as u8 {
+            return Err(AmmError::PoolDisabled.into());
+        }
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn lot_size(&self) -> u64 {
+        u64::from_le_bytes(self.lot_size)
+    }
+
+    /// Rejects an amount off the pool lot size
+    #[inline(always)]
+    pub fn check_lot(&self, amount: u64) -> Result<(), ProgramError> {
+        match self.lot_size() {
+            0 => Ok(()),
+            lot if amount % lot == 0 => Ok(()),
+            _ => Err(AmmError::InvalidLotSize.into()),
+        }
+    }
+
+    /// Largest lot multiple within `amount`, `amount` itself without a lot size
+    #[inline(always)]
+    pub fn lot_floor(&self, amount: u64) -> u64 {
+        match self.lot_size() {
+            0 => amount,
+            lot => amount - amount % lot,
+        }
+    }
+
+    /// Smallest lot multiple covering `amount`, `amount` itself without a lot size
+    #[inline(always)]
+    pub fn lot_ceil(&self, amount: u64) -> Result<u64, ProgramError> {
+        match self.lot_size() {
+            0 => Ok(amount),
+            lot => amount
+                .div_ceil(lot)
+                .checked_mul(lot)
+                .ok_or(ProgramError::ArithmeticOverflow),
+        }
+    }
+
+    #[inline(always)]
+    pub fn liquidity_seconds(&self) -> u128 {
+        u128::from_le_bytes(self.liquidity_seconds)
+    }
+
+    #[inline(always)]
+    pub fn last_accrual_ts(&self) -> i64 {
+        i64::from_le_bytes(self.last_accrual_ts)
+    }
+
+    #[inline(always)]
+    pub fn pending_authority(&self) -> &Pubkey {
+        &self.pending_authority
+    }
+
+    /// Weights the spot price `reserve_y * w_x / (reserve_x * w_y)` is
+    /// computed with, the constant product being the 50/50 weighted curve
+    #[inline(always)]
+    pub fn price_weights(&self) -> (u16, u16) {
+        match self.curve_kind() {
+            Self::CURVE_WEIGHTED => (self.weight_x(), self.weight_y()),
+            _ => (5_000, 5_000),
+        }
+    }
+
+    #[inline(always)]
+    pub fn vault_bumps(&self) -> ([u8; 1], [u8; 1]) {
+        (self.vault_x_bump, self.vault_y_bump)
+    }
+
+    /// Vault ATAs of the pool at `config` from the cached bumps, falling back
+    /// to `vault_addresses` on pools created before they were stored
+    pub fn vault_keys(
+        &self,
+        config: &Pubkey,
+        token_program: &Pubkey,
+    ) -> Result<(Pubkey, Pubkey), ProgramError> {
+        if self.vault_x_bump == [0] || self.vault_y_bump == [0] {
+            let ((vault_x, _), (vault_y, _)) = self.vault_addresses(config, token_program);
+            return Ok((vault_x, vault_y));
+        }
+
+        let vault = |mint: &Pubkey, bump: &[u8; 1]| {
+            create_program_address(
+                &[config, token_program, mint, bump],
+                &pinocchio_associated_token_account::ID,
+            )
+        };
+        Ok((
+            vault(self.mint_x(), &self.vault_x_bump)?,
+            vault(self.mint_y(), &self.vault_y_bump)?,
+        ))
+    }
+
+    #[inline(always)]
+    pub fn protocol_fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.protocol_fee_bps)
+    }
+
+    #[inline(always)]
+    pub fn pending_protocol_fee_bps(&self) -> u16 {
+        u16::from_le_bytes(self.pending_protocol_fee_bps)
+    }
+
+    /// Protocol share in force at `now`, switching along with the fee
+    #[inline(always)]
+    pub fn effective_protocol_fee_bps(&self, now: i64) -> u16 {
+        let effective_at = self.fee_effective_at();
+        if effective_at != 0 && now >= effective_at {
+            self.pending_protocol_fee_bps()
+        } else {
+            self.protocol_fee_bps()
+        }
+    }
+
+    /// Vault balances capped to the tracked reserves, the amounts swaps,
+    /// deposits and withdrawals run against. Whatever the vaults hold on
+    /// top, protocol fees and direct donations, belongs to the protocol
+    #[inline(always)]
+    pub fn lp_reserves(&self, vault_x: u64, vault_y: u64) -> (u64, u64) {
+        (vault_x.min(self.reserve_x()), vault_y.min(self.reserve_y()))
+    }
+
+    /// Vault balances above the tracked reserves, swept by `CollectProtocolFees`
+    #[inline(always)]
+    pub fn protocol_fees(&self, vault_x: u64, vault_y: u64) -> (u64, u64) {
+        (
+            vault_x.saturating_sub(self.reserve_x()),
+            vault_y.saturating_sub(self.reserve_y()),
+        )
+    }
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountInfo) -> Result<RefMut<Self>, ProgramError> {
+        // Writes to a read-only account would only fail once the instruction
+        // returns, after every check already passed
+        if !account_info.is_writable() {
+            return Err(ProgramError::Immutable);
+        }
+        Self::check_len(account_info.data_len())?;
         if account_info.owner().ne(&crate::ID) {
             return Err(ProgramError::InvalidAccountOwner);
         }
-        Ok(RefMut::map(
-            account_info.try_borrow_mut_data()?,
-            |data| unsafe { Self::from_bytes_unchecked_mut(data) },
-        ))
+        let config = RefMut::map(account_info.try_borrow_mut_data()?, |data| unsafe {
+            Self::from_bytes_unchecked_mut(data)
+        });
+        Ok(config)
     }
 
     #[inline(always)]
     pub fn set_state(&mut self, state: u8) -> Result<(), ProgramError> {
-        if state.ge(&(AmmState::WithdrawOnly as u8)) {
+        if state.gt(&(AmmState::Migrated as u8)) {
             return Err(ProgramError::InvalidAccountData);
         }
+        // The old config of a migrated pool must never trade again
+        if self.state == AmmState::Migrated as u8 {
+            return Err(AmmError::PoolDisabled.into());
+        }
         self.state = state;
         Ok(())
     }
@@ -162,6 +793,15 @@ impl Config {
         Ok(())
     }
 
+    #[inline(always)]
+    pub fn set_max_swap_pct_bps(&mut self, pct: u16) -> Result<(), ProgramError> {
+        if pct > 10_000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.max_swap_pct_bps = pct.to_le_bytes();
+        Ok(())
+    }
+
     #[inline(always)]
     pub fn set_authority(&mut self, authority: Pubkey) -> Result<(), ProgramError> {
         if authority == Pubkey::default() {
@@ -215,30 +855,409 @@ impl Config {
         Ok(())
     }
 
-    /// Atomic update - all fields are updated at once,
-    /// so there's no risk of  inconsistencies
+    /// Credits tokens entering the pool to the tracked reserves
     #[inline(always)]
-    pub fn set_inner(
-        &mut self,
-        seed: u64,
+    pub fn credit_reserves(&mut self, x: u64, y: u64) -> Result<(), ProgramError> {
+        let reserve_x = self
+            .reserve_x()
+            .checked_add(x)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        let reserve_y = self
+            .reserve_y()
+            .checked_add(y)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.reserve_x = reserve_x.to_le_bytes();
+        self.reserve_y = reserve_y.to_le_bytes();
+        Ok(())
+    }
+
+    /// Debits tokens leaving the pool from the tracked reserves.
+    /// Saturates, since vaults can hold more than tracked (direct donations)
+    /// and a full withdraw hands out the whole vault
+    #[inline(always)]
+    pub fn debit_reserves(&mut self, x: u64, y: u64) {
+        self.reserve_x = self.reserve_x().saturating_sub(x).to_le_bytes();
+        self.reserve_y = self.reserve_y().saturating_sub(y).to_le_bytes();
+    }
+
+    /// The lock can only ever be extended
+    #[inline(always)]
+    pub fn set_locked_until(&mut self, locked_until: i64) -> Result<(), ProgramError> {
+        if locked_until < self.locked_until() {
+            return Err(AmmError::LockCannotBeShortened.into());
+        }
+        self.locked_until = locked_until.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_label(&mut self, label: &[u8]) -> Result<(), ProgramError> {
+        if label.len() > self.label.len() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.label = [0u8; 16];
+        self.label[..label.len()].copy_from_slice(label);
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_feature_flags(&mut self, flags: u8) -> Result<(), ProgramError> {
+        if flags & !Self::FEATURE_ALL != 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.feature_flags = flags;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_min_deposit_lp(&mut self, min_deposit_lp: u64) {
+        self.min_deposit_lp = min_deposit_lp.to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn set_min_swap_amount(&mut self, min_swap_amount: u64) {
+        self.min_swap_amount = min_swap_amount.to_le_bytes();
+    }
+
+    /// Accrues swap fees into the per unit of liquidity accumulators.
+    /// As in Uniswap V3 the accumulators wrap, consumers only ever
+    /// look at differences between two readings.
+    #[inline(always)]
+    pub fn accrue_fee_growth(&mut self, fee_x: u64, fee_y: u64, lp_supply: u64) {
+        if lp_supply == 0 {
+            return;
+        }
+
+        // A u64 shifted by 64 bits always fits in a u128
+        let growth_x = ((fee_x as u128) << 64) / lp_supply as u128;
+        let growth_y = ((fee_y as u128) << 64) / lp_supply as u128;
+
+        self.fee_growth_global_x = self
+            .fee_growth_global_x()
+            .wrapping_add(growth_x)
+            .to_le_bytes();
+        self.fee_growth_global_y = self
+            .fee_growth_global_y()
+            .wrapping_add(growth_y)
+            .to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn set_price_floor(&mut self, price_floor: u64) -> Result<(), ProgramError> {
+        let ceiling = self.price_ceiling();
+        if price_floor != 0 && ceiling != 0 && price_floor > ceiling {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.price_floor = price_floor.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_price_ceiling(&mut self, price_ceiling: u64) -> Result<(), ProgramError> {
+        let floor = self.price_floor();
+        if price_ceiling != 0 && floor != 0 && price_ceiling < floor {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.price_ceiling = price_ceiling.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_lp_decimals(&mut self, lp_decimals: u8) {
+        self.lp_decimals = lp_decimals;
+    }
+
+    #[inline(always)]
+    pub fn set_mint_lp(&mut self, mint_lp: Pubkey) {
+        self.mint_lp = mint_lp;
+    }
+
+    #[inline(always)]
+    pub fn set_require_min_slippage_bps(&mut self, bps: u16) -> Result<(), ProgramError> {
+        if bps > 10_000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.require_min_slippage_bps = bps.to_le_bytes();
+        Ok(())
+    }
+
+    /// The timelock can only ever be extended, otherwise it could be
+    /// dropped right before an instant fee change
+    #[inline(always)]
+    pub fn set_fee_change_timelock_secs(&mut self, secs: u32) -> Result<(), ProgramError> {
+        if secs < self.fee_change_timelock_secs() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.fee_change_timelock_secs = secs.to_le_bytes();
+        Ok(())
+    }
+
+    /// Changes the fee, right away without a timelock, otherwise once
+    /// the timelock elapsed from `now`, replacing any pending change
+    #[inline(always)]
+    pub fn schedule_fee(&mut self, fee: u16, now: i64) -> Result<(), ProgramError> {
+        let (_, protocol_fee_bps) = self.target_fees();
+        self.schedule_fees(fee, protocol_fee_bps, now)
+    }
+
+    /// Changes the protocol share of the swap fee, under the same
+    /// timelock as the fee
+    #[inline(always)]
+    pub fn schedule_protocol_fee(&mut self, bps: u16, now: i64) -> Result<(), ProgramError> {
+        let (fee, _) = self.target_fees();
+        self.schedule_fees(fee, bps, now)
+    }
+
+    /// Fee and protocol share the pool is headed for, the pending
+    /// ones while a change is scheduled or applied but not settled
+    #[inline(always)]
+    fn target_fees(&self) -> (u16, u16) {
+        match self.fee_effective_at() {
+            0 => (self.fee(), self.protocol_fee_bps()),
+            _ => (self.pending_fee(), self.pending_protocol_fee_bps()),
+        }
+    }
+
+    /// Both fees share a single pending change, so scheduling one keeps
+    /// the pending value of the other
+    #[inline(always)]
+    fn schedule_fees(
+        &mut self,
+        fee: u16,
+        protocol_fee_bps: u16,
+        now: i64,
+    ) -> Result<(), ProgramError> {
+        if fee >= 10_000 || protocol_fee_bps > Self::MAX_PROTOCOL_FEE_BPS {
+            return Err(ProgramError::InvalidArgument);
+        }
+
+        // Settle a pending change that already applies
+        let current_fee = self.effective_fee(now);
+        let current_protocol_fee_bps = self.effective_protocol_fee_bps(now);
+        let timelock = self.fee_change_timelock_secs();
+        let effective_at = now
+            .checked_add(timelock as i64)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+
+        if timelock == 0 {
+            self.fee = fee.to_le_bytes();
+            self.protocol_fee_bps = protocol_fee_bps.to_le_bytes();
+            self.pending_fee = [0; 2];
+            self.pending_protocol_fee_bps = [0; 2];
+            self.fee_effective_at = [0; 8];
+        } else {
+            self.fee = current_fee.to_le_bytes();
+            self.protocol_fee_bps = current_protocol_fee_bps.to_le_bytes();
+            self.pending_fee = fee.to_le_bytes();
+            self.pending_protocol_fee_bps = protocol_fee_bps.to_le_bytes();
+            self.fee_effective_at = effective_at.to_le_bytes();
+        }
+        Ok(())
+    }
+
+    /// Switches the invariant, weights are only used by the weighted
+    /// curve and must both be non zero and sum to 10_000
+    #[inline(always)]
+    pub fn set_curve(&mut self, curve_kind: u8, weight_x: u16) -> Result<(), ProgramError> {
+        if curve_kind > Self::CURVE_WEIGHTED || weight_x == 0 || weight_x >= 10_000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.curve_kind = curve_kind;
+        self.weight_x = weight_x.to_le_bytes();
+        self.weight_y = (10_000 - weight_x).to_le_bytes();
+        Ok(())
+    }
+
+    /// Fills in the fields appended since v0, on a config holding a v0
+    /// account in its first `V0_LEN` bytes and zeroes after them, see
+    /// `MigrateConfig`. v0 pools had every operation enabled on the constant
+    /// product and no other settings, zeroed here but for `FEATURE_ALL` and
+    /// the 50/50 weights. `created_at` stays 0, it was never recorded
+    #[inline(always)]
+    pub fn upgrade_from_v0(
+        &mut self,
+        mint_lp: Pubkey,
+        lp_decimals: u8,
+        lp_supply: u64,
+        (reserve_x, reserve_y): (u64, u64),
+        (vault_x_bump, vault_y_bump): ([u8; 1], [u8; 1]),
+    ) -> Result<(), ProgramError> {
+        self.set_feature_flags(Self::FEATURE_ALL)?;
+        self.set_curve(Self::CURVE_CONSTANT_PRODUCT, 5_000)?;
+        self.set_lp_decimals(lp_decimals);
+        self.set_mint_lp(mint_lp);
+        self.credit_lp_supply(lp_supply)?;
+        self.credit_reserves(reserve_x, reserve_y)?;
+        self.set_vault_bumps(vault_x_bump, vault_y_bump);
+        Ok(())
+    }
+
+    /// Moves a copied config under a new seed, along with the bumps of
+    /// its new vaults, see `MigrateSeed`
+    #[inline(always)]
+    pub fn rehome(
+        &mut self,
+        seed: u64,
+        config_bump: [u8; 1],
+        vault_bumps: ([u8; 1], [u8; 1]),
+    ) -> Result<(), ProgramError> {
+        if seed == 0 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.seed = seed.to_le_bytes();
+        self.config_bump = config_bump;
+        (self.vault_x_bump, self.vault_y_bump) = vault_bumps;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_created_at(&mut self, created_at: i64) {
+        self.created_at = created_at.to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn set_fee_on_output(&mut self, fee_on_output: bool) {
+        self.fee_on_output = fee_on_output as u8;
+    }
+
+    /// Adds swap fees to the totals accrued since inception
+    #[inline(always)]
+    pub fn record_fees(&mut self, fee_x: u64, fee_y: u64) {
+        self.total_fee_x = self.total_fee_x().saturating_add(fee_x).to_le_bytes();
+        self.total_fee_y = self.total_fee_y().saturating_add(fee_y).to_le_bytes();
+    }
+
+    #[inline(always)]
+    pub fn set_deposit_fee_bps(&mut self, bps: u16) -> Result<(), ProgramError> {
+        if bps > Self::MAX_LIQUIDITY_FEE_BPS {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.deposit_fee_bps = bps.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_withdraw_fee_bps(&mut self, bps: u16) -> Result<(), ProgramError> {
+        if bps > Self::MAX_LIQUIDITY_FEE_BPS {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.withdraw_fee_bps = bps.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_allow_no_deadline(&mut self, allow_no_deadline: bool) {
+        self.allow_no_deadline = allow_no_deadline as u8;
+    }
+
+    /// Adds LP minted by a deposit to the mirrored supply
+    #[inline(always)]
+    pub fn credit_lp_supply(&mut self, amount: u64) -> Result<(), ProgramError> {
+        let lp_supply = self
+            .lp_supply()
+            .checked_add(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.lp_supply = lp_supply.to_le_bytes();
+        Ok(())
+    }
+
+    /// Removes LP burned by a withdrawal from the mirrored supply
+    #[inline(always)]
+    pub fn debit_lp_supply(&mut self, amount: u64) -> Result<(), ProgramError> {
+        let lp_supply = self
+            .lp_supply()
+            .checked_sub(amount)
+            .ok_or(ProgramError::ArithmeticOverflow)?;
+        self.lp_supply = lp_supply.to_le_bytes();
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_lot_size(&mut self, lot_size: u64) {
+        self.lot_size = lot_size.to_le_bytes();
+    }
+
+    /// Accrues the liquidity held since the last accrual, ahead of any
+    /// reserve change. The first accrual only starts the clock, and a clock
+    /// behind the last accrual accrues nothing.
+    #[inline(always)]
+    pub fn accrue_liquidity_seconds(&mut self, now: i64) {
+        let last = self.last_accrual_ts();
+        if now <= last {
+            return;
+        }
+
+        if last != 0 {
+            // Both are non negative here, the cast is lossless
+            let elapsed = (now - last) as u128;
+            let liquidity = isqrt(self.reserve_x() as u128 * self.reserve_y() as u128);
+            self.liquidity_seconds = self
+                .liquidity_seconds()
+                .saturating_add(liquidity.saturating_mul(elapsed))
+                .to_le_bytes();
+        }
+        self.last_accrual_ts = now.to_le_bytes();
+    }
+
+    /// Nominates the next authority, which has to accept to take over
+    #[inline(always)]
+    pub fn set_pending_authority(&mut self, authority: Pubkey) -> Result<(), ProgramError> {
+        if authority == Pubkey::default() {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.pending_authority = authority;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn clear_pending_authority(&mut self) {
+        self.pending_authority = Pubkey::default();
+    }
+
+    #[inline(always)]
+    pub fn set_vault_bumps(&mut self, vault_x_bump: [u8; 1], vault_y_bump: [u8; 1]) {
+        self.vault_x_bump = vault_x_bump;
+        self.vault_y_bump = vault_y_bump;
+    }
+
+    /// Atomic update - all fields are updated at once,
+    /// so there's no risk of  inconsistencies
+    #[inline(always)]
+    #[allow(clippy::too_many_arguments)]
+    pub fn set_inner(
+        &mut self,
+        seed: u64,
         authority: Pubkey,
         mint_x: Pubkey,
         mint_y: Pubkey,
         fee: u16,
         config_bump: [u8; 1],
+        state: AmmState,
     ) -> Result<(), ProgramError> {
-        self.set_state(AmmState::Initialized as u8)?;
+        // A pool can start paused or withdraw only, never uninitialized
+        // or migrated
+        if state == AmmState::Uninitialized || state == AmmState::Migrated {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.state = state as u8;
         self.set_seed(seed)?;
-        self.set_authority(authority)?;
+        // A zeroed authority leaves the pool immutable
+        if authority != Pubkey::default() {
+            self.set_authority(authority)?;
+        }
+        self.clear_pending_authority();
         self.set_mint_x(mint_x)?;
         self.set_mint_y(mint_y)?;
         self.set_fee(fee)?;
         self.set_config_bump(config_bump)?;
+        self.set_feature_flags(Self::FEATURE_ALL)?;
+        self.set_curve(Self::CURVE_CONSTANT_PRODUCT, 5_000)?;
         Ok(())
     }
 
     /// efficient way to check whether authority is set or
     /// it's made of zeroes
+    #[deprecated(note = "use `authority_opt`, which avoids the unsafe cast")]
     #[inline(always)]
     pub fn has_authority(&self) -> Option<Pubkey> {
         let bytes = self.authority();
@@ -250,3 +1269,910 @@ impl Config {
         }
     }
 }
+
+/// Protocol wide settings, singleton PDA of `[GlobalConfig::SEED]`
+#[repr(C)]
+pub struct GlobalConfig {
+    /// Set once `InitializeGlobal` ran
+    is_initialized: u8,
+    /// Bump seed for PDA derivation
+    bump: [u8; 1],
+    /// Receives the protocol fees, pool creation fee included
+    protocol_fee_recipient: Pubkey,
+    /// Lamports charged to the initializer of a new pool, none when 0
+    creation_fee: [u8; 8],
+    /// `max_swap_pct_bps` new pools start with
+    default_max_swap_pct_bps: [u8; 2],
+    /// `min_deposit_lp` new pools start with
+    default_min_deposit_lp: [u8; 8],
+    /// `min_swap_amount` new pools start with
+    default_min_swap_amount: [u8; 8],
+    /// Emergency switch blocking deposits, withdrawals and swaps on every pool
+    global_paused: u8,
+    /// Pools allowed per mint pair, counted in `PairRegistry`, no limit when 0
+    max_pools_per_pair: [u8; 2],
+}
+
+// `LEN` is spelled out field by field, keep it in step with the struct
+const _: () = assert!(GlobalConfig::LEN == size_of::<GlobalConfig>());
+
+impl GlobalConfig {
+    pub const LEN: usize = size_of::<u8>()
+        + size_of::<u8>()
+        + size_of::<Pubkey>()
+        + size_of::<u64>()
+        + size_of::<u16>()
+        + size_of::<u64>() * 2
+        + size_of::<u8>()
+        + size_of::<u16>();
+
+    pub const SEED: &'static [u8] = b"global";
+
+    /// Only key allowed to write the global config, the base58 key in the
+    /// `AMM_ADMIN` environment variable at build time. Every build fails
+    /// without it, host builds included, so clients and tests never run
+    /// against another admin than the program's.
+    pub const ADMIN: Pubkey = pubkey_from_base58(env!(
+        "AMM_ADMIN",
+        "AMM_ADMIN must be set to the base58 key of the protocol admin"
+    ));
+
+    /// Loads an initialized global config, checking it lives at the singleton address
+    #[inline(always)]
+    pub fn load(account_info: &AccountInfo) -> Result<Ref<Self>, ProgramError> {
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if account_info.owner().ne(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        let global = Ref::map(account_info.try_borrow_data()?, |data| unsafe {
+            &*(data.as_ptr() as *const GlobalConfig)
+        });
+        if !global.is_initialized() {
+            return Err(ProgramError::UninitializedAccount);
+        }
+        // The canonical address only, whatever bump is stored
+        let (key, _) = find_program_address(&[Self::SEED], &crate::ID);
+        if key.ne(account_info.key()) {
+            return Err(ProgramError::InvalidSeeds);
+        }
+        Ok(global)
+    }
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountInfo) -> Result<RefMut<Self>, ProgramError> {
+        // Writes to a read-only account would only fail once the instruction
+        // returns, after every check already passed
+        if !account_info.is_writable() {
+            return Err(ProgramError::Immutable);
+        }
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if account_info.owner().ne(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        Ok(RefMut::map(
+            account_info.try_borrow_mut_data()?,
+            |data| unsafe { &mut *(data.as_mut_ptr() as *mut GlobalConfig) },
+        ))
+    }
+
+    /// Rejects with `AmmError::ProtocolPaused` while the protocol is paused.
+    /// A global config not created yet can't be paused.
+    #[inline(always)]
+    pub fn check_not_paused(account_info: &AccountInfo) -> Result<(), ProgramError> {
+        match Self::load_if_initialized(account_info)? {
+            Some(global) if global.is_paused() => Err(AmmError::ProtocolPaused.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Loads the global config, `None` while the singleton address is
+    /// still empty, before `InitializeGlobal` ran
+    #[inline(always)]
+    pub fn load_if_initialized(
+        account_info: &AccountInfo,
+    ) -> Result<Option<Ref<Self>>, ProgramError> {
+        if account_info.data_is_empty() {
+            let (key, _) = find_program_address(&[Self::SEED], &crate::ID);
+            if key.ne(account_info.key()) {
+                return Err(ProgramError::InvalidSeeds);
+            }
+            return Ok(None);
+        }
+        Self::load(account_info).map(Some)
+    }
+
+    #[inline(always)]
+    pub fn is_initialized(&self) -> bool {
+        self.is_initialized != 0
+    }
+
+    #[inline(always)]
+    pub fn is_paused(&self) -> bool {
+        self.global_paused != 0
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    #[inline(always)]
+    pub fn protocol_fee_recipient(&self) -> &Pubkey {
+        &self.protocol_fee_recipient
+    }
+
+    #[inline(always)]
+    pub fn creation_fee(&self) -> u64 {
+        u64::from_le_bytes(self.creation_fee)
+    }
+
+    #[inline(always)]
+    pub fn default_max_swap_pct_bps(&self) -> u16 {
+        u16::from_le_bytes(self.default_max_swap_pct_bps)
+    }
+
+    #[inline(always)]
+    pub fn default_min_deposit_lp(&self) -> u64 {
+        u64::from_le_bytes(self.default_min_deposit_lp)
+    }
+
+    #[inline(always)]
+    pub fn default_min_swap_amount(&self) -> u64 {
+        u64::from_le_bytes(self.default_min_swap_amount)
+    }
+
+    #[inline(always)]
+    pub fn max_pools_per_pair(&self) -> u16 {
+        u16::from_le_bytes(self.max_pools_per_pair)
+    }
+
+    /// Writes all the settings at once, marking the account initialized
+    #[inline(always)]
+    pub fn set_inner(
+        &mut self,
+        bump: [u8; 1],
+        protocol_fee_recipient: Pubkey,
+        creation_fee: u64,
+        default_max_swap_pct_bps: u16,
+        default_min_deposit_lp: u64,
+        default_min_swap_amount: u64,
+    ) -> Result<(), ProgramError> {
+        if default_max_swap_pct_bps > 10_000 {
+            return Err(ProgramError::InvalidArgument);
+        }
+        self.bump = bump;
+        self.protocol_fee_recipient = protocol_fee_recipient;
+        self.creation_fee = creation_fee.to_le_bytes();
+        self.default_max_swap_pct_bps = default_max_swap_pct_bps.to_le_bytes();
+        self.default_min_deposit_lp = default_min_deposit_lp.to_le_bytes();
+        self.default_min_swap_amount = default_min_swap_amount.to_le_bytes();
+        self.is_initialized = 1;
+        Ok(())
+    }
+
+    #[inline(always)]
+    pub fn set_paused(&mut self, paused: bool) {
+        self.global_paused = paused as u8;
+    }
+
+    #[inline(always)]
+    pub fn set_max_pools_per_pair(&mut self, max_pools_per_pair: u16) {
+        self.max_pools_per_pair = max_pools_per_pair.to_le_bytes();
+    }
+}
+
+/// Pools created for a mint pair, PDA of `[PairRegistry::SEED, mint_x, mint_y]`,
+/// only kept while the global config caps the pools per pair
+#[repr(C)]
+pub struct PairRegistry {
+    /// Bump seed for PDA derivation
+    bump: [u8; 1],
+    /// Pools registered for the pair
+    pool_count: [u8; 2],
+}
+
+// `LEN` is spelled out field by field, keep it in step with the struct
+const _: () = assert!(PairRegistry::LEN == size_of::<PairRegistry>());
+
+impl PairRegistry {
+    pub const LEN: usize = size_of::<u8>() + size_of::<u16>();
+
+    pub const SEED: &'static [u8] = b"pair";
+
+    #[inline(always)]
+    pub fn load_mut(account_info: &AccountInfo) -> Result<RefMut<Self>, ProgramError> {
+        // Writes to a read-only account would only fail once the instruction
+        // returns, after every check already passed
+        if !account_info.is_writable() {
+            return Err(ProgramError::Immutable);
+        }
+        if account_info.data_len() != Self::LEN {
+            return Err(ProgramError::InvalidAccountData);
+        }
+        if account_info.owner().ne(&crate::ID) {
+            return Err(ProgramError::InvalidAccountOwner);
+        }
+        Ok(RefMut::map(
+            account_info.try_borrow_mut_data()?,
+            |data| unsafe { &mut *(data.as_mut_ptr() as *mut PairRegistry) },
+        ))
+    }
+
+    #[inline(always)]
+    pub fn bump(&self) -> [u8; 1] {
+        self.bump
+    }
+
+    #[inline(always)]
+    pub fn pool_count(&self) -> u16 {
+        u16::from_le_bytes(self.pool_count)
+    }
+
+    #[inline(always)]
+    pub fn set_bump(&mut self, bump: [u8; 1]) {
+        self.bump = bump;
+    }
+
+    /// Registers one more pool, rejected once the pair holds `max_pools`
+    #[inline(always)]
+    pub fn register(&mut self, max_pools: u16) -> Result<(), ProgramError> {
+        let count = self.pool_count();
+        if count >= max_pools {
+            return Err(AmmError::PoolLimitReached.into());
+        }
+        self.pool_count = (count + 1).to_le_bytes();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::mint_data;
+    use core::mem::offset_of;
+
+    fn test_config() -> [u8; Config::LEN] {
+        Config::new_test(
+            1,
+            [1; 32],
+            [2; 32],
+            [3; 32],
+            [4; 32],
+            30,
+            [255, 254, 253],
+            0,
+        )
+    }
+
+    fn config(data: &mut [u8; Config::LEN]) -> &mut Config {
+        // `Config` has an alignment of 1 and the buffer is exactly `LEN` bytes
+        unsafe { Config::from_bytes_unchecked_mut(data) }
+    }
+
+    #[test]
+    fn config_layout_is_pinned() {
+        // Accounts are read in place: moving a field corrupts every existing
+        // pool, new fields go at the end, carved from `reserved`
+        let offsets = [
+            ("state", offset_of!(Config, state), 0),
+            ("seed", offset_of!(Config, seed), 1),
+            ("authority", offset_of!(Config, authority), 9),
+            ("mint_x", offset_of!(Config, mint_x), 41),
+            ("mint_y", offset_of!(Config, mint_y), 73),
+            ("fee", offset_of!(Config, fee), 105),
+            ("config_bump", offset_of!(Config, config_bump), 107),
+            (
+                "max_swap_pct_bps",
+                offset_of!(Config, max_swap_pct_bps),
+                108,
+            ),
+            ("reserve_x", offset_of!(Config, reserve_x), 110),
+            ("reserve_y", offset_of!(Config, reserve_y), 118),
+            ("locked_until", offset_of!(Config, locked_until), 126),
+            ("label", offset_of!(Config, label), 134),
+            ("feature_flags", offset_of!(Config, feature_flags), 150),
+            ("min_deposit_lp", offset_of!(Config, min_deposit_lp), 151),
+            ("min_swap_amount", offset_of!(Config, min_swap_amount), 159),
+            (
+                "fee_growth_global_x",
+                offset_of!(Config, fee_growth_global_x),
+                167,
+            ),
+            (
+                "fee_growth_global_y",
+                offset_of!(Config, fee_growth_global_y),
+                183,
+            ),
+            ("price_floor", offset_of!(Config, price_floor), 199),
+            ("price_ceiling", offset_of!(Config, price_ceiling), 207),
+            ("lp_decimals", offset_of!(Config, lp_decimals), 215),
+            (
+                "require_min_slippage_bps",
+                offset_of!(Config, require_min_slippage_bps),
+                216,
+            ),
+            (
+                "fee_change_timelock_secs",
+                offset_of!(Config, fee_change_timelock_secs),
+                218,
+            ),
+            ("pending_fee", offset_of!(Config, pending_fee), 222),
+            (
+                "fee_effective_at",
+                offset_of!(Config, fee_effective_at),
+                224,
+            ),
+            ("curve_kind", offset_of!(Config, curve_kind), 232),
+            ("weight_x", offset_of!(Config, weight_x), 233),
+            ("weight_y", offset_of!(Config, weight_y), 235),
+            ("created_at", offset_of!(Config, created_at), 237),
+            ("fee_on_output", offset_of!(Config, fee_on_output), 245),
+            ("total_fee_x", offset_of!(Config, total_fee_x), 246),
+            ("total_fee_y", offset_of!(Config, total_fee_y), 254),
+            ("deposit_fee_bps", offset_of!(Config, deposit_fee_bps), 262),
+            (
+                "withdraw_fee_bps",
+                offset_of!(Config, withdraw_fee_bps),
+                264,
+            ),
+            (
+                "allow_no_deadline",
+                offset_of!(Config, allow_no_deadline),
+                266,
+            ),
+            ("lp_supply", offset_of!(Config, lp_supply), 267),
+            ("lot_size", offset_of!(Config, lot_size), 275),
+            (
+                "liquidity_seconds",
+                offset_of!(Config, liquidity_seconds),
+                283,
+            ),
+            ("last_accrual_ts", offset_of!(Config, last_accrual_ts), 299),
+            (
+                "pending_authority",
+                offset_of!(Config, pending_authority),
+                307,
+            ),
+            ("vault_x_bump", offset_of!(Config, vault_x_bump), 339),
+            ("vault_y_bump", offset_of!(Config, vault_y_bump), 340),
+            (
+                "protocol_fee_bps",
+                offset_of!(Config, protocol_fee_bps),
+                341,
+            ),
+            (
+                "pending_protocol_fee_bps",
+                offset_of!(Config, pending_protocol_fee_bps),
+                343,
+            ),
+            ("mint_lp", offset_of!(Config, mint_lp), 345),
+            ("reserved", offset_of!(Config, reserved), 377),
+        ];
+        for (field, offset, expected) in offsets {
+            assert_eq!(offset, expected, "{field}");
+        }
+        assert_eq!(Config::LEN, 379);
+    }
+
+    /// Golden config accounts, one per layout version, never regenerated
+    const CONFIG_V0: &[u8; Config::V0_LEN] = include_bytes!("../tests/fixtures/config_v0.bin");
+    const CONFIG_V1: &[u8; Config::LEN] = include_bytes!("../tests/fixtures/config_v1.bin");
+
+    /// `data` in a buffer of the current layout, zeroed past it
+    fn load_fixture(data: &[u8]) -> [u8; Config::LEN] {
+        let mut buffer = [0u8; Config::LEN];
+        buffer[..data.len()].copy_from_slice(data);
+        buffer
+    }
+
+    #[test]
+    fn config_len_tells_versions_apart() {
+        assert_eq!(Config::check_len(Config::LEN), Ok(()));
+        assert_eq!(
+            Config::check_len(Config::V0_LEN),
+            Err(AmmError::ConfigNeedsMigration.into())
+        );
+        for len in [0, Config::V0_LEN + 1, Config::LEN - 1, Config::LEN + 1] {
+            assert_eq!(
+                Config::check_len(len),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+
+    #[test]
+    fn v0_fixture_reads_through_the_current_layout() {
+        let mut data = load_fixture(CONFIG_V0);
+        let config = config(&mut data);
+
+        assert_eq!(config.state(), AmmState::Initialized as u8);
+        assert_eq!(config.seed(), 1);
+        assert_eq!(config.authority_opt(), Some(&[0xaa; 32]));
+        assert_eq!(config.mint_x(), &[0x11; 32]);
+        assert_eq!(config.mint_y(), &[0x22; 32]);
+        assert_eq!(config.fee(), 30);
+        assert_eq!(config.config_bump(), [254]);
+    }
+
+    #[test]
+    fn v0_fixture_upgrades_to_v1_fixture() {
+        let mut data = load_fixture(CONFIG_V0);
+        config(&mut data)
+            .upgrade_from_v0(
+                [0x44; 32],
+                6,
+                1_000_000,
+                (10_000_000, 40_000_000),
+                ([253], [252]),
+            )
+            .unwrap();
+        assert_eq!(&data, CONFIG_V1);
+
+        let config = config(&mut data);
+        assert_eq!(config.feature_flags(), Config::FEATURE_ALL);
+        assert_eq!(config.curve_kind(), Config::CURVE_CONSTANT_PRODUCT);
+        assert_eq!(config.lp_supply(), 1_000_000);
+        assert_eq!(
+            (config.reserve_x(), config.reserve_y()),
+            (10_000_000, 40_000_000)
+        );
+        assert_eq!(config.mint_lp(), &[0x44; 32]);
+        assert_eq!(config.created_at(), 0);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn authority_opt_of_a_managed_pool() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert_eq!(config.authority_opt(), Some(&[1; 32]));
+        assert_eq!(config.has_authority(), Some([1; 32]));
+
+        // Every byte counts, not only the leading ones
+        let mut authority = [0; 32];
+        authority[31] = 1;
+        config.set_authority(authority).unwrap();
+        assert_eq!(config.authority_opt(), Some(&authority));
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn authority_opt_of_an_immutable_pool() {
+        let mut data = Config::new_test(
+            1,
+            [0; 32],
+            [2; 32],
+            [3; 32],
+            [4; 32],
+            30,
+            [255, 254, 253],
+            0,
+        );
+        let config = config(&mut data);
+        assert_eq!(config.authority(), &[0; 32]);
+        assert_eq!(config.authority_opt(), None);
+        assert_eq!(config.has_authority(), None);
+    }
+
+    #[test]
+    fn fee_change_applies_after_timelock() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(3_600).unwrap();
+
+        config.schedule_fee(100, 1_000).unwrap();
+        assert_eq!(config.fee(), 30);
+        assert_eq!(config.pending_fee(), 100);
+        assert_eq!(config.fee_effective_at(), 4_600);
+        assert_eq!(config.effective_fee(4_599), 30);
+        assert_eq!(config.effective_fee(4_600), 100);
+    }
+
+    #[test]
+    fn fee_change_is_instant_without_timelock() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.schedule_fee(100, 1_000).unwrap();
+        assert_eq!(config.fee(), 100);
+        assert_eq!(config.fee_effective_at(), 0);
+        assert_eq!(config.effective_fee(1_000), 100);
+    }
+
+    #[test]
+    fn fee_change_replaces_pending_change() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(3_600).unwrap();
+
+        // Raised then lowered back before applying, never in force
+        config.schedule_fee(1_000, 1_000).unwrap();
+        config.schedule_fee(30, 2_000).unwrap();
+        assert_eq!(config.effective_fee(4_600), 30);
+        assert_eq!(config.effective_fee(5_600), 30);
+    }
+
+    #[test]
+    fn fee_change_timelock_cannot_shrink() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(3_600).unwrap();
+
+        assert!(config.set_fee_change_timelock_secs(0).is_err());
+        assert!(config.set_fee_change_timelock_secs(7_200).is_ok());
+    }
+
+    #[test]
+    fn fee_change_rejects_full_fee() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert!(config.schedule_fee(10_000, 1_000).is_err());
+    }
+
+    #[test]
+    fn protocol_fee_applies_after_timelock() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(100).unwrap();
+
+        config.schedule_protocol_fee(2_000, 1_000).unwrap();
+        assert_eq!(config.effective_protocol_fee_bps(1_099), 0);
+        assert_eq!(config.effective_protocol_fee_bps(1_100), 2_000);
+        // The fee itself isn't touched
+        assert_eq!(config.effective_fee(1_100), 30);
+    }
+
+    #[test]
+    fn protocol_fee_applies_at_once_without_timelock() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.schedule_protocol_fee(2_000, 1_000).unwrap();
+        assert_eq!(config.protocol_fee_bps(), 2_000);
+        assert_eq!(config.fee_effective_at(), 0);
+    }
+
+    #[test]
+    fn fee_and_protocol_fee_share_pending_change() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(100).unwrap();
+
+        config.schedule_protocol_fee(2_000, 1_000).unwrap();
+        config.schedule_fee(50, 1_050).unwrap();
+
+        // The later change restarts the timelock for both
+        assert_eq!(config.effective_protocol_fee_bps(1_100), 0);
+        assert_eq!(config.effective_fee(1_149), 30);
+        assert_eq!(config.effective_protocol_fee_bps(1_150), 2_000);
+        assert_eq!(config.effective_fee(1_150), 50);
+    }
+
+    #[test]
+    fn protocol_fee_settles_applied_change() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_fee_change_timelock_secs(100).unwrap();
+
+        config.schedule_protocol_fee(2_000, 1_000).unwrap();
+        config.schedule_protocol_fee(1_000, 1_200).unwrap();
+
+        assert_eq!(config.protocol_fee_bps(), 2_000);
+        assert_eq!(config.effective_protocol_fee_bps(1_299), 2_000);
+        assert_eq!(config.effective_protocol_fee_bps(1_300), 1_000);
+    }
+
+    #[test]
+    fn protocol_fee_is_capped() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        assert!(config
+            .schedule_protocol_fee(Config::MAX_PROTOCOL_FEE_BPS + 1, 1_000)
+            .is_err());
+        assert!(config
+            .schedule_protocol_fee(Config::MAX_PROTOCOL_FEE_BPS, 1_000)
+            .is_ok());
+    }
+
+    #[test]
+    fn deposit_fee_is_rounded_up() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert_eq!(config.deposit_fee(1_000_000), Ok(0));
+
+        config.set_deposit_fee_bps(50).unwrap();
+        assert_eq!(config.deposit_fee(1_000_000), Ok(5_000));
+        assert_eq!(config.deposit_fee(1_001), Ok(6));
+    }
+
+    #[test]
+    fn withdraw_fee_reduces_proceeds() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert_eq!(
+            config.withdraw_after_fee(10_000, 40_000, 500, 1_000),
+            Ok((10_000, 40_000))
+        );
+
+        config.set_withdraw_fee_bps(100).unwrap();
+        assert_eq!(
+            config.withdraw_after_fee(10_000, 40_000, 500, 1_000),
+            Ok((9_900, 39_600))
+        );
+        // Rounded up in favor of the pool
+        assert_eq!(config.withdraw_after_fee(999, 1, 1, 1_000), Ok((989, 0)));
+    }
+
+    #[test]
+    fn full_withdraw_pays_no_fee() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_withdraw_fee_bps(100).unwrap();
+
+        // Nothing may be left in the reserves once the supply is burned
+        assert_eq!(
+            config.withdraw_after_fee(10_000, 40_000, 1_000, 1_000),
+            Ok((10_000, 40_000))
+        );
+    }
+
+    #[test]
+    fn lock_cannot_be_shortened() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.set_locked_until(2_000).unwrap();
+        assert_eq!(
+            config.set_locked_until(1_999),
+            Err(AmmError::LockCannotBeShortened.into())
+        );
+        config.set_locked_until(2_000).unwrap();
+        config.set_locked_until(3_000).unwrap();
+        assert_eq!(config.locked_until(), 3_000);
+    }
+
+    #[test]
+    fn set_state_accepts_every_state() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        // WithdrawOnly (3) included, Migrated last since it's terminal
+        for state in 0..=AmmState::Migrated as u8 {
+            config.set_state(state).unwrap();
+            assert_eq!(config.state(), state);
+        }
+    }
+
+    #[test]
+    fn set_state_rejects_unknown_states() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.set_state(AmmState::WithdrawOnly as u8).unwrap();
+        for state in [AmmState::Migrated as u8 + 1, u8::MAX] {
+            assert_eq!(
+                config.set_state(state),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+        assert_eq!(config.state(), AmmState::WithdrawOnly as u8);
+    }
+
+    #[test]
+    fn disabled_pool_can_be_reenabled() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.set_state(AmmState::Disabled as u8).unwrap();
+        assert_eq!(
+            config.require_not_disabled(),
+            Err(AmmError::PoolDisabled.into())
+        );
+        config.set_state(AmmState::Initialized as u8).unwrap();
+        assert_eq!(config.require_not_disabled(), Ok(()));
+    }
+
+    #[test]
+    fn migrated_pool_is_terminal() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        config.set_state(AmmState::Migrated as u8).unwrap();
+        assert_eq!(
+            config.require_not_disabled(),
+            Err(AmmError::PoolDisabled.into())
+        );
+        for state in [
+            AmmState::Initialized,
+            AmmState::Disabled,
+            AmmState::WithdrawOnly,
+            AmmState::Migrated,
+        ] {
+            assert_eq!(
+                config.set_state(state as u8),
+                Err(AmmError::PoolDisabled.into())
+            );
+        }
+        assert_eq!(config.state(), AmmState::Migrated as u8);
+    }
+
+    #[test]
+    fn protocol_fees_are_vault_excess() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.credit_reserves(1_000, 2_000).unwrap();
+
+        assert_eq!(config.lp_reserves(1_010, 2_000), (1_000, 2_000));
+        assert_eq!(config.protocol_fees(1_010, 2_000), (10, 0));
+        // A vault short of the tracked reserves holds no protocol fees
+        assert_eq!(config.lp_reserves(900, 2_000), (900, 2_000));
+        assert_eq!(config.protocol_fees(900, 2_000), (0, 0));
+    }
+
+    #[test]
+    fn lp_mint_of_the_pool_passes() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_mint_lp([9; 32]);
+        config.credit_lp_supply(1_000).unwrap();
+
+        let mint = mint_data(Some(&[4; 32]), 1_000, Config::LP_DECIMALS);
+        let mint = unsafe { Mint::from_bytes_unchecked(&mint) };
+        assert!(config.check_lp_mint(&[9; 32], mint).is_ok());
+    }
+
+    #[test]
+    fn lp_mint_at_another_address_is_rejected() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_mint_lp([9; 32]);
+        config.credit_lp_supply(1_000).unwrap();
+
+        // Even one naming the config as its mint authority
+        let mint = mint_data(Some(&[4; 32]), 1_000, Config::LP_DECIMALS);
+        let mint = unsafe { Mint::from_bytes_unchecked(&mint) };
+        assert_eq!(
+            config.check_lp_mint(&[8; 32], mint),
+            Err(ProgramError::InvalidAccountData)
+        );
+    }
+
+    #[cfg(feature = "strict-invariants")]
+    #[test]
+    fn lp_mint_off_the_mirror_is_rejected() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        config.set_mint_lp([9; 32]);
+        config.credit_lp_supply(1_000).unwrap();
+
+        let mint = mint_data(Some(&[4; 32]), 999, Config::LP_DECIMALS);
+        let mint = unsafe { Mint::from_bytes_unchecked(&mint) };
+        assert_eq!(
+            config.check_lp_mint(&[9; 32], mint),
+            Err(AmmError::SupplyMismatch.into())
+        );
+    }
+
+    #[test]
+    fn lot_ceil_rounds_up_to_lot() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert_eq!(config.lot_ceil(1_001), Ok(1_001));
+
+        config.set_lot_size(100);
+        assert_eq!(config.lot_ceil(0), Ok(0));
+        assert_eq!(config.lot_ceil(1_000), Ok(1_000));
+        assert_eq!(config.lot_ceil(1_001), Ok(1_100));
+        assert_eq!(
+            config.lot_ceil(u64::MAX),
+            Err(ProgramError::ArithmeticOverflow)
+        );
+    }
+
+    #[test]
+    fn lot_floor_rounds_down_to_lot() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert_eq!(config.lot_floor(1_099), 1_099);
+
+        config.set_lot_size(100);
+        assert_eq!(config.lot_floor(99), 0);
+        assert_eq!(config.lot_floor(1_099), 1_000);
+        assert_eq!(config.lot_floor(1_100), 1_100);
+    }
+
+    #[test]
+    fn pair_registry_rejects_pools_over_the_cap() {
+        let mut registry = PairRegistry {
+            bump: [255],
+            pool_count: [0; 2],
+        };
+
+        // Fill the pair up to the cap
+        for count in 1..=3 {
+            registry.register(3).unwrap();
+            assert_eq!(registry.pool_count(), count);
+        }
+
+        assert_eq!(registry.register(3), Err(AmmError::PoolLimitReached.into()));
+        assert_eq!(registry.pool_count(), 3);
+    }
+
+    #[test]
+    fn pair_registry_follows_a_raised_cap() {
+        let mut registry = PairRegistry {
+            bump: [255],
+            pool_count: 2u16.to_le_bytes(),
+        };
+
+        assert!(registry.register(2).is_err());
+        registry.register(3).unwrap();
+        assert_eq!(registry.pool_count(), 3);
+    }
+
+    #[test]
+    fn label_trims_trailing_zeros_only() {
+        let mut data = test_config();
+        let config = config(&mut data);
+        assert!(config.label().is_empty());
+
+        config.set_label(b"a\0b").unwrap();
+        assert_eq!(config.label(), b"a\0b");
+
+        // A shorter label clears what the previous one left behind
+        config.set_label(b"c").unwrap();
+        assert_eq!(config.label(), b"c");
+
+        assert!(config.set_label(&[1; 17]).is_err());
+        assert_eq!(config.label(), b"c");
+    }
+
+    #[test]
+    fn fee_growth_accrues_per_unit_of_liquidity() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        // Each accrual rounds down on its own, in Q64.64 per LP
+        let accruals = [
+            (300, 0, 1_000_000),
+            (0, 7, 1_000_000),
+            (1, 1, 3),
+            (999, 0, 4_000_000),
+        ];
+        let (mut x, mut y) = (0u128, 0u128);
+        for (fee_x, fee_y, supply) in accruals {
+            config.accrue_fee_growth(fee_x, fee_y, supply);
+            x += ((fee_x as u128) << 64) / supply as u128;
+            y += ((fee_y as u128) << 64) / supply as u128;
+        }
+        assert_eq!(config.fee_growth_global_x(), x);
+        assert_eq!(config.fee_growth_global_y(), y);
+        // 300 / 1_000_000 per LP, then a third of a unit
+        assert_eq!(config.fee_growth_global_x() >> 64, 0);
+        assert!(config.fee_growth_global_x() > (1u128 << 64) / 3);
+
+        // Nothing to accrue to without liquidity
+        config.accrue_fee_growth(1_000, 1_000, 0);
+        assert_eq!(config.fee_growth_global_x(), x);
+        assert_eq!(config.fee_growth_global_y(), y);
+    }
+
+    #[test]
+    fn fee_growth_wraps() {
+        let mut data = test_config();
+        let config = config(&mut data);
+
+        // 2^64 units per LP overflow the Q64.64 accumulator back to zero,
+        // differences between readings stay right across the wrap
+        config.accrue_fee_growth(u64::MAX, 0, 1);
+        let before = config.fee_growth_global_x();
+        config.accrue_fee_growth(2, 0, 1);
+        assert_eq!(config.fee_growth_global_x(), 1 << 64);
+        assert_eq!(config.fee_growth_global_x().wrapping_sub(before), 2 << 64);
+    }
+}
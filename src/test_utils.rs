@@ -0,0 +1,87 @@
+use crate::{AmmState, Config};
+use pinocchio::pubkey::Pubkey;
+use pinocchio_token::state::{Mint, TokenAccount};
+
+// Fixtures to fabricate account data in tests, laid out exactly
+// as the program and the token program read them.
+
+impl Config {
+    /// Initialized `Config` account data, as written by `Initialize`
+    /// without an initial state nor protocol defaults at `created_at`.
+    /// `bumps` are the config and vault bumps, which it grinds on-chain.
+    #[allow(clippy::too_many_arguments)]
+    pub fn new_test(
+        seed: u64,
+        authority: Pubkey,
+        mint_x: Pubkey,
+        mint_y: Pubkey,
+        mint_lp: Pubkey,
+        fee: u16,
+        bumps: [u8; 3],
+        created_at: i64,
+    ) -> [u8; Config::LEN] {
+        let mut data = [0u8; Config::LEN];
+        let [config_bump, vault_x_bump, vault_y_bump] = bumps;
+        // `Config` has an alignment of 1 and the buffer is exactly `LEN` bytes
+        let config = unsafe { Config::from_bytes_unchecked_mut(&mut data) };
+        config
+            .set_inner(
+                seed,
+                authority,
+                mint_x,
+                mint_y,
+                fee,
+                [config_bump],
+                AmmState::Initialized,
+            )
+            .expect("invalid test config");
+        config.set_lp_decimals(Config::LP_DECIMALS);
+        config.set_mint_lp(mint_lp);
+        config.set_created_at(created_at);
+        config.set_vault_bumps([vault_x_bump], [vault_y_bump]);
+        data
+    }
+}
+
+/// Writes a `COption<Pubkey>` at the start of `data`
+fn write_option_key(data: &mut [u8], key: Option<&Pubkey>) {
+    if let Some(key) = key {
+        data[0..4].copy_from_slice(&1u32.to_le_bytes());
+        data[4..36].copy_from_slice(key);
+    }
+}
+
+/// Initialized SPL `Mint` account data
+pub fn mint_data(mint_authority: Option<&Pubkey>, supply: u64, decimals: u8) -> [u8; Mint::LEN] {
+    let mut data = [0u8; Mint::LEN];
+    write_option_key(&mut data[0..36], mint_authority);
+    data[36..44].copy_from_slice(&supply.to_le_bytes());
+    data[44] = decimals;
+    // is_initialized
+    data[45] = 1;
+    data
+}
+
+/// Initialized SPL `TokenAccount` account data, without delegate
+pub fn token_account_data(mint: &Pubkey, owner: &Pubkey, amount: u64) -> [u8; TokenAccount::LEN] {
+    token_account_data_with_delegate(mint, owner, amount, None, 0)
+}
+
+/// Initialized SPL `TokenAccount` account data, with an optional delegate
+pub fn token_account_data_with_delegate(
+    mint: &Pubkey,
+    owner: &Pubkey,
+    amount: u64,
+    delegate: Option<&Pubkey>,
+    delegated_amount: u64,
+) -> [u8; TokenAccount::LEN] {
+    let mut data = [0u8; TokenAccount::LEN];
+    data[0..32].copy_from_slice(mint);
+    data[32..64].copy_from_slice(owner);
+    data[64..72].copy_from_slice(&amount.to_le_bytes());
+    write_option_key(&mut data[72..108], delegate);
+    // AccountState::Initialized
+    data[108] = 1;
+    data[121..129].copy_from_slice(&delegated_amount.to_le_bytes());
+    data
+}
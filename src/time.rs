@@ -0,0 +1,51 @@
+use crate::{report_error_detail, AmmError, ErrorDetailKind};
+use pinocchio::program_error::ProgramError;
+use pinocchio::sysvars::clock::Clock;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+
+/// Source of the current unix timestamp, lets deadline checks
+/// be driven without the Clock sysvar
+pub trait TimeSource {
+    fn now(&self) -> Result<i64, ProgramError>;
+}
+
+/// Production time source, reads the Clock sysvar
+pub struct ClockTimeSource;
+
+impl TimeSource for ClockTimeSource {
+    #[inline(always)]
+    fn now(&self) -> Result<i64, ProgramError> {
+        Ok(Clock::get()?.unix_timestamp)
+    }
+}
+
+/// Time source frozen at a given timestamp
+pub struct FixedTimeSource(pub i64);
+
+impl TimeSource for FixedTimeSource {
+    #[inline(always)]
+    fn now(&self) -> Result<i64, ProgramError> {
+        Ok(self.0)
+    }
+}
+
+/// Expiration an instruction parses to when it omits one, only
+/// accepted by pools with `Config::allow_no_deadline`
+pub const NO_DEADLINE: i64 = i64::MAX;
+
+/// Rejects once `now` is past `expiration`, the expiration second itself is still valid.
+/// Timestamps before the unix epoch are rejected as nonsensical.
+#[inline(always)]
+pub fn check_deadline<T: TimeSource>(expiration: i64, time: &T) -> ProgramResult {
+    let now = time.now()?;
+    if now < 0 || expiration < 0 {
+        return Err(AmmError::InvalidTimestamp.into());
+    }
+    // Both are non negative from here, the casts below are lossless
+    if now > expiration {
+        report_error_detail(ErrorDetailKind::Deadline, now as u64, expiration as u64);
+        return Err(ProgramError::InvalidInstructionData);
+    }
+    Ok(())
+}
@@ -0,0 +1,341 @@
+use crate::{report_error_detail, AmmError, ErrorDetailKind};
+use pinocchio::account_info::AccountInfo;
+use pinocchio::instruction::Signer;
+use pinocchio::program_error::ProgramError;
+use pinocchio::pubkey::Pubkey;
+use pinocchio::sysvars::rent::Rent;
+use pinocchio::sysvars::Sysvar;
+use pinocchio::ProgramResult;
+use pinocchio_system::instructions::{Allocate, Assign, CreateAccount, Transfer};
+use pinocchio_token::state::{Mint, TokenAccount};
+
+/// `N` bytes of `data` from `offset`, failing cleanly instead of panicking
+/// when a payload is shorter than its parser expects
+#[inline(always)]
+pub fn read_array<const N: usize>(data: &[u8], offset: usize) -> Result<[u8; N], ProgramError> {
+    data.get(offset..offset + N)
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or(AmmError::InvalidInstructionData.into())
+}
+
+/// Token-2022 program id
+// TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb
+pub const TOKEN_2022_PROGRAM_ID: Pubkey = [
+    0x06, 0xdd, 0xf6, 0xe1, 0xee, 0x75, 0x8f, 0xde, 0x18, 0x42, 0x5d, 0xbc, 0xe4, 0x6c, 0xcd, 0xda,
+    0xb6, 0x1a, 0xfc, 0x4d, 0x83, 0xb9, 0x0d, 0x27, 0xfe, 0xbd, 0xf9, 0x28, 0xd8, 0xa1, 0x8b, 0xfc,
+];
+
+/// Decodes a base58 public key in a const context, for keys supplied at
+/// build time. Panics on a malformed key, which fails the build
+pub const fn pubkey_from_base58(encoded: &str) -> Pubkey {
+    const ALPHABET: &[u8; 58] = b"123456789ABCDEFGHJKLMNPQRSTUVWXYZabcdefghijkmnopqrstuvwxyz";
+
+    let encoded = encoded.as_bytes();
+    assert!(!encoded.is_empty(), "empty base58 key");
+
+    let mut key = [0u8; 32];
+    let mut i = 0;
+    while i < encoded.len() {
+        let mut digit = 0;
+        while digit < ALPHABET.len() && ALPHABET[digit] != encoded[i] {
+            digit += 1;
+        }
+        assert!(digit < ALPHABET.len(), "invalid base58 character");
+
+        // key = key * 58 + digit, big endian
+        let mut carry = digit as u32;
+        let mut j = key.len();
+        while j > 0 {
+            j -= 1;
+            carry += key[j] as u32 * 58;
+            key[j] = carry as u8;
+            carry >>= 8;
+        }
+        assert!(carry == 0, "base58 key longer than 32 bytes");
+
+        i += 1;
+    }
+    key
+}
+
+/// Rejects any account passed more than once in `accounts`
+#[inline(always)]
+pub fn ensure_distinct_accounts(accounts: &[&AccountInfo]) -> ProgramResult {
+    for (i, a) in accounts.iter().enumerate() {
+        if accounts[i + 1..].iter().any(|b| a.key().eq(b.key())) {
+            return Err(AmmError::DuplicateAccount.into());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects an LP supply other than `expected` after a mint or burn, as a
+/// fee-bearing LP mint would leave it. `None` is an overflowed expectation.
+#[cfg(feature = "strict-invariants")]
+#[inline(always)]
+pub fn ensure_supply(supply: u64, expected: Option<u64>) -> ProgramResult {
+    if Some(supply) != expected {
+        return Err(AmmError::SupplyMismatch.into());
+    }
+    Ok(())
+}
+
+/// Rejects a vault balance that didn't grow from `before` to `after` by
+/// exactly `expected`, as a partial or fee-bearing transfer would leave it
+#[inline(always)]
+pub fn ensure_received(before: u64, after: u64, expected: u64) -> ProgramResult {
+    if after.checked_sub(before) != Some(expected) {
+        return Err(AmmError::TransferShortfall.into());
+    }
+    Ok(())
+}
+
+/// The Rent sysvar, with `RentSysvarUnavailable` where the runtime
+/// doesn't provide it rather than the opaque sysvar error
+#[inline(always)]
+pub fn get_rent() -> Result<Rent, ProgramError> {
+    Rent::get().map_err(|_| AmmError::RentSysvarUnavailable.into())
+}
+
+/// Rejects a frozen token account among `accounts`, which would otherwise
+/// fail the transfer with an opaque token program error. The position of
+/// the frozen account in `accounts` is reported as the error detail.
+#[inline(always)]
+pub fn ensure_not_frozen(accounts: &[&AccountInfo]) -> ProgramResult {
+    for (i, account) in accounts.iter().enumerate() {
+        if unsafe { TokenAccount::from_account_info_unchecked(account)? }.is_frozen() {
+            report_error_detail(ErrorDetailKind::FrozenAccount, i as u64, 0);
+            return Err(AmmError::AccountFrozen.into());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects any token program but SPL Token. The vault addresses derive
+/// from its key and every token CPI goes through it, so a lookalike
+/// program would be handed the vaults.
+/// Token-2022 isn't accepted yet, its vaults aren't read through
+/// extension aware parsers everywhere.
+#[inline(always)]
+pub fn ensure_spl_token_program(token_program: &AccountInfo) -> ProgramResult {
+    if token_program.key().ne(&pinocchio_token::ID) {
+        return Err(ProgramError::IncorrectProgramId);
+    }
+    Ok(())
+}
+
+/// Rejects token accounts owned by another program than `token_program`,
+/// which would fail the transfers between them with a CPI mismatch
+#[inline(always)]
+pub fn ensure_token_program(
+    token_program: &AccountInfo,
+    accounts: &[&AccountInfo],
+) -> ProgramResult {
+    for account in accounts {
+        if account.owner().ne(token_program.key()) {
+            return Err(AmmError::TokenProgramMismatch.into());
+        }
+    }
+    Ok(())
+}
+
+/// Rejects vaults other than the `expected` ATAs of the config, as derived
+/// by `Config::vault_keys`. Any token account of the pool mints owned by
+/// the config would otherwise pass for a vault.
+#[inline(always)]
+pub fn ensure_vaults(
+    (expected_x, expected_y): &(Pubkey, Pubkey),
+    vault_x: &Pubkey,
+    vault_y: &Pubkey,
+) -> ProgramResult {
+    if expected_x.ne(vault_x) || expected_y.ne(vault_y) {
+        return Err(ProgramError::InvalidAccountData);
+    }
+    Ok(())
+}
+
+/// Rejects a mint account that isn't the initialized token program mint
+/// at `expected`, one of the mints stored in the config
+#[inline(always)]
+pub fn ensure_pool_mint(account: &AccountInfo, expected: &Pubkey) -> ProgramResult {
+    if account.key().ne(expected) {
+        return Err(AmmError::InvalidMint.into());
+    }
+
+    // Checks the owner and length
+    let mint = Mint::from_account_info(account).map_err(|_| AmmError::InvalidMint)?;
+
+    if !mint.is_initialized() {
+        return Err(AmmError::InvalidMint.into());
+    }
+    Ok(())
+}
+
+/// Creates the PDA account `to`, signed by `signers`.
+///
+/// `CreateAccount` fails on an address already holding lamports, which lets
+/// anyone grief a predictable PDA by pre-funding it. A pre-funded, still
+/// system owned and empty account is adopted instead: it is topped up to
+/// `lamports`, allocated and assigned to `owner`. Any other pre-existing
+/// account is rejected with `AmmError::ConfigPrefunded`.
+pub fn create_pda_account(
+    from: &AccountInfo,
+    to: &AccountInfo,
+    lamports: u64,
+    space: u64,
+    owner: &Pubkey,
+    signers: &[Signer],
+) -> ProgramResult {
+    let current = to.lamports();
+
+    if current == 0 {
+        return CreateAccount {
+            from,
+            to,
+            lamports,
+            space,
+            owner,
+        }
+        .invoke_signed(signers);
+    }
+
+    if to.owner().ne(&pinocchio_system::ID) || !to.data_is_empty() {
+        return Err(AmmError::ConfigPrefunded.into());
+    }
+
+    if current < lamports {
+        Transfer {
+            from,
+            to,
+            lamports: lamports - current,
+        }
+        .invoke()?;
+    }
+
+    Allocate { account: to, space }.invoke_signed(signers)?;
+
+    Assign { account: to, owner }.invoke_signed(signers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pubkey_from_base58_decodes_known_keys() {
+        assert_eq!(
+            pubkey_from_base58("11111111111111111111111111111111"),
+            [0; 32]
+        );
+        assert_eq!(
+            pubkey_from_base58("22222222222222222222222222222222222222222222"),
+            crate::ID
+        );
+        assert_eq!(
+            pubkey_from_base58("TokenkegQfeZyiNwAJbNbGKPFXCWuBvf9Ss623VQ5DA"),
+            pinocchio_token::ID
+        );
+        assert_eq!(
+            pubkey_from_base58("TokenzQdBNbLqP5VEhdkAS6EPFLC1PHnBqCXEpPxuEb"),
+            TOKEN_2022_PROGRAM_ID
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid base58 character")]
+    fn pubkey_from_base58_rejects_invalid_characters() {
+        pubkey_from_base58("0OIl");
+    }
+
+    #[test]
+    #[should_panic(expected = "base58 key longer than 32 bytes")]
+    fn pubkey_from_base58_rejects_long_keys() {
+        pubkey_from_base58("zzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzzz");
+    }
+
+    #[test]
+    #[cfg(feature = "strict-invariants")]
+    fn ensure_supply_accepts_the_exact_change() {
+        // 1_000 LP minted over a supply of 10_000, then burned
+        assert!(ensure_supply(11_000, 10_000u64.checked_add(1_000)).is_ok());
+        assert!(ensure_supply(10_000, 11_000u64.checked_sub(1_000)).is_ok());
+    }
+
+    #[test]
+    #[cfg(feature = "strict-invariants")]
+    fn ensure_supply_trips_on_a_fee_bearing_mint() {
+        // A 1% fee withheld from the 1_000 minted, or burned on top
+        for (supply, expected) in [(10_990, 11_000), (8_990, 9_000)] {
+            assert_eq!(
+                ensure_supply(supply, Some(expected)),
+                Err(AmmError::SupplyMismatch.into())
+            );
+        }
+        assert_eq!(
+            ensure_supply(u64::MAX, u64::MAX.checked_add(1)),
+            Err(AmmError::SupplyMismatch.into())
+        );
+    }
+
+    #[test]
+    fn ensure_received_accepts_the_exact_amount() {
+        assert!(ensure_received(1_000, 1_500, 500).is_ok());
+        assert!(ensure_received(1_000, 1_000, 0).is_ok());
+    }
+
+    #[test]
+    fn ensure_received_rejects_a_short_transfer() {
+        // Short, over, or a balance that went down
+        for (before, after) in [(1_000, 1_499), (1_000, 1_501), (1_000, 999)] {
+            assert_eq!(
+                ensure_received(before, after, 500),
+                Err(AmmError::TransferShortfall.into())
+            );
+        }
+    }
+
+    #[test]
+    fn read_array_reads_in_bounds() {
+        let data = [1, 2, 3, 4, 5];
+        assert_eq!(read_array::<2>(&data, 0), Ok([1, 2]));
+        assert_eq!(read_array::<2>(&data, 3), Ok([4, 5]));
+    }
+
+    #[test]
+    fn read_array_rejects_short_payloads() {
+        let data = [1, 2, 3, 4, 5];
+        for offset in [4, 5, 6] {
+            assert_eq!(
+                read_array::<2>(&data, offset),
+                Err(AmmError::InvalidInstructionData.into())
+            );
+        }
+        assert!(read_array::<8>(&[], 0).is_err());
+    }
+
+    #[test]
+    fn get_rent_without_the_sysvar() {
+        // Off-chain there is no runtime to provide the sysvar
+        assert_eq!(
+            get_rent().err(),
+            Some(AmmError::RentSysvarUnavailable.into())
+        );
+    }
+
+    #[test]
+    fn ensure_vaults_accepts_config_atas() {
+        let expected = ([1; 32], [2; 32]);
+        assert!(ensure_vaults(&expected, &[1; 32], &[2; 32]).is_ok());
+    }
+
+    #[test]
+    fn ensure_vaults_rejects_spoofed_vaults() {
+        let expected = ([1; 32], [2; 32]);
+        for (vault_x, vault_y) in [([3; 32], [2; 32]), ([1; 32], [3; 32]), ([2; 32], [1; 32])] {
+            assert_eq!(
+                ensure_vaults(&expected, &vault_x, &vault_y),
+                Err(ProgramError::InvalidAccountData)
+            );
+        }
+    }
+}
@@ -0,0 +1,818 @@
+// Authority instructions against the runtime: who may run them, which
+// values they take, which config accounts they accept, and how they gate
+// the user instructions. Also the protocol admin's global config, and the
+// defaults it gives new pools.
+//
+// Runs the SBF build of the program through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils --test admin
+
+mod common;
+
+use blueshift_native_amm::client::{get_pool_by_mints, PoolAddresses};
+use blueshift_native_amm::{
+    AcceptAuthority, AmmError, AmmState, CollectProtocolFees, Config, ConfigParam, GlobalConfig,
+    MigrateSeed, NominateAuthority, SetCurveKind,
+};
+use common::{
+    amm_error, system_account, Pool, ATA_PROGRAM, BALANCE, SEED, SYSTEM_PROGRAM, TOKEN_PROGRAM,
+};
+use solana_instruction::{AccountMeta, Instruction};
+use solana_program_error::ProgramError;
+use solana_pubkey::Pubkey;
+
+#[test]
+fn set_state_requires_the_authority() {
+    let mut pool = Pool::funded();
+    let other = pool.new_user();
+
+    let ix = pool.admin_set_state_ix(&other.key, AmmState::Disabled);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn pool_initialized_withdraw_only_opens_through_the_authority() {
+    let mut pool = Pool::new();
+    let user = pool.user;
+
+    // Unknown, uninitialized, or paused without an authority to lift it
+    let mut invalid = Vec::new();
+    for state in [42, AmmState::Uninitialized as u8] {
+        let mut ix = pool.initialize_ix(Some(user.key));
+        ix.data.push(state);
+        invalid.push(ix);
+    }
+    let mut ix = pool.initialize_ix(Some(Pubkey::default()));
+    ix.data.push(AmmState::WithdrawOnly as u8);
+    invalid.push(ix);
+    for ix in invalid {
+        pool.fail(&ix, ProgramError::InvalidInstructionData);
+    }
+
+    let mut ix = pool.initialize_ix(Some(user.key));
+    ix.data.push(AmmState::WithdrawOnly as u8);
+    pool.run(&ix);
+    pool.set_token_account(user.lp, pool.mint_lp, user.key, 0);
+    assert_eq!(
+        pool.with_config(|config| config.state()),
+        AmmState::WithdrawOnly as u8
+    );
+
+    // Nothing goes in until the authority opens the pool
+    let ix = pool.deposit_ix(&user, 1_000_000, 10_000_000, 40_000_000);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    pool.admin_set_state(AmmState::Initialized);
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    pool.swap(&user, true, 100_000, 1);
+    assert_eq!(pool.amount(&pool.vault_x), 10_100_000);
+}
+
+#[test]
+fn set_state_rejects_uninitialized_and_migrated() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    // The config would be initialized again, or tombstoned for good
+    for state in [AmmState::Uninitialized, AmmState::Migrated] {
+        let ix = pool.admin_set_state_ix(&authority, state);
+        pool.fail(&ix, ProgramError::InvalidInstructionData);
+    }
+}
+
+#[test]
+fn disabled_pool_rejects_swaps_and_withdrawals() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.admin_set_state(AmmState::Disabled);
+
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, amm_error(AmmError::PoolDisabled));
+    let ix = pool.withdraw_ix(&user, 1_000_000, 0, 0);
+    pool.fail(&ix, amm_error(AmmError::PoolDisabled));
+
+    // Re-enabled, it trades again
+    pool.admin_set_state(AmmState::Initialized);
+    pool.swap(&user, true, 100_000, 1);
+}
+
+#[test]
+fn disabled_pool_rejects_every_instruction() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let authority = user.key;
+    pool.admin_set_state(AmmState::Disabled);
+
+    let (migrate_seed, _) = migrate_seed_ix(&mut pool, &authority, SEED + 1);
+    let instructions = [
+        pool.swap_ix(&user, true, 100_000, 1),
+        pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+        pool.withdraw_ix(&user, 100_000, 0, 0),
+        pool.update_config_ix(&authority, ConfigParam::MaxSwapPctBps, 100),
+        pool.set_label_ix(&authority, b"pool"),
+        pool.reinitialize_vault_authority_ix(&authority, &pool.vault_x),
+        pool.simulate_withdraw_ix(100_000),
+        pool.pool_health_ix(0),
+        pool.accrued_fees_ix(),
+        pool.quote_ix(true, 100_000),
+        migrate_seed,
+    ];
+    for ix in instructions {
+        pool.fail(&ix, amm_error(AmmError::PoolDisabled));
+    }
+}
+
+#[test]
+fn withdraw_only_pool_rejects_swaps_and_deposits() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.admin_set_state(AmmState::WithdrawOnly);
+
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+    let ix = pool.deposit_ix(&user, 500_000, 5_000_000, 20_000_000);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    // LPs still get their liquidity out
+    pool.withdraw(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.amount(&user.x), BALANCE);
+    assert_eq!(pool.amount(&user.y), BALANCE);
+}
+
+#[test]
+fn migrated_config_cannot_be_reenabled() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    // As left behind by `MigrateSeed`
+    pool.with_config_mut(|config| config.set_state(AmmState::Migrated as u8).unwrap());
+
+    for state in [AmmState::Initialized, AmmState::WithdrawOnly] {
+        let ix = pool.admin_set_state_ix(&authority, state);
+        pool.fail(&ix, amm_error(AmmError::PoolDisabled));
+    }
+}
+
+#[test]
+fn update_fee_applies_without_timelock() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    pool.run(&pool.update_fee_ix(&authority, 250));
+    pool.with_config(|config| assert_eq!(config.fee(), 250));
+}
+
+#[test]
+fn update_fee_requires_the_authority() {
+    let mut pool = Pool::funded();
+    let other = pool.new_user();
+
+    let ix = pool.update_fee_ix(&other.key, 250);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn update_fee_rejects_immutable_pool() {
+    let mut pool = Pool::new();
+    pool.run(&pool.initialize_ix(None));
+    let signer = pool.user.key;
+
+    let ix = pool.update_fee_ix(&signer, 250);
+    pool.fail(&ix, ProgramError::Immutable);
+}
+
+#[test]
+fn read_only_config_is_rejected() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    let mut ix = pool.update_config_ix(&authority, ConfigParam::WithdrawFeeBps, 100);
+    ix.accounts[1] = AccountMeta::new_readonly(pool.config, false);
+    pool.fail(&ix, ProgramError::Immutable);
+}
+
+#[test]
+fn config_of_another_program_is_rejected() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    let mut account = pool.get(&pool.config).clone();
+    account.owner = TOKEN_PROGRAM;
+    pool.set(pool.config, account);
+
+    let ix = pool.update_config_ix(&authority, ConfigParam::WithdrawFeeBps, 100);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn initialize_global_requires_the_admin() {
+    let mut pool = Pool::new();
+    let other = pool.user.key;
+
+    let ix = pool.initialize_global_ix(&other, &other, 0, (10_000, 0, 0));
+    pool.fail(&ix, ProgramError::IncorrectAuthority);
+}
+
+#[test]
+fn new_pools_inherit_the_global_defaults() {
+    let mut pool = Pool::new();
+    // The key `AMM_ADMIN` set at build time, mollusk doesn't verify signatures
+    let admin = Pubkey::new_from_array(GlobalConfig::ADMIN);
+    let recipient = Pubkey::new_unique();
+    pool.set(admin, system_account(10_000_000_000));
+    pool.set(recipient, system_account(0));
+
+    let ix = pool.initialize_global_ix(&admin, &recipient, 1_000_000, (2_500, 1_000, 100));
+    pool.run(&ix);
+
+    // The creation fee can't be skipped by leaving out its recipient
+    let mut ix = pool.initialize_ix(Some(pool.user.key));
+    pool.fail(&ix, ProgramError::NotEnoughAccountKeys);
+
+    ix.accounts.push(AccountMeta::new(recipient, false));
+    pool.run(&ix);
+    assert_eq!(pool.get(&recipient).lamports, 1_000_000);
+    pool.with_config(|config| {
+        assert_eq!(config.max_swap_pct_bps(), 2_500);
+        assert_eq!(config.min_deposit_lp(), 1_000);
+        assert_eq!(config.min_swap_amount(), 100);
+    });
+}
+
+/// Initializes the global config without fee nor defaults, returns its admin
+fn initialize_global(pool: &mut Pool) -> Pubkey {
+    let admin = Pubkey::new_from_array(GlobalConfig::ADMIN);
+    pool.set(admin, system_account(10_000_000_000));
+    pool.run(&pool.initialize_global_ix(&admin, &admin, 0, (0, 0, 0)));
+    admin
+}
+
+#[test]
+fn global_pause_blocks_every_pool_operation() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let admin = initialize_global(&mut pool);
+
+    pool.run(&pool.set_global_pause_ix(&admin, true));
+    let operations = [
+        pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+        pool.swap_ix(&user, true, 100_000, 1),
+        pool.withdraw_ix(&user, 100_000, 0, 0),
+    ];
+    for ix in &operations {
+        pool.fail(ix, amm_error(AmmError::ProtocolPaused));
+    }
+
+    // Back to business once unpaused
+    pool.run(&pool.set_global_pause_ix(&admin, false));
+    for ix in &operations {
+        pool.run(ix);
+    }
+}
+
+#[test]
+fn global_pause_requires_the_admin() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    initialize_global(&mut pool);
+
+    // Not even a pool authority
+    let ix = pool.set_global_pause_ix(&authority, true);
+    pool.fail(&ix, ProgramError::IncorrectAuthority);
+}
+
+/// `MigrateSeed` of the pool to `seed` signed by `authority`, with the new
+/// config and vaults not created yet
+fn migrate_seed_ix(pool: &mut Pool, authority: &Pubkey, seed: u64) -> (Instruction, PoolAddresses) {
+    let new = get_pool_by_mints(
+        &blueshift_native_amm::ID,
+        seed,
+        &pool.mint_x.to_bytes(),
+        &pool.mint_y.to_bytes(),
+    );
+    let key = Pubkey::new_from_array;
+    for address in [new.config, new.vault_x, new.vault_y] {
+        pool.set(key(address), system_account(0));
+    }
+
+    let mut data = vec![*MigrateSeed::DISCRIMINATOR];
+    data.extend_from_slice(&seed.to_le_bytes());
+    data.push(new.config_bump);
+
+    let ix = pool.instruction(
+        data,
+        vec![
+            AccountMeta::new(*authority, true),
+            AccountMeta::new(pool.config, false),
+            AccountMeta::new(key(new.config), false),
+            AccountMeta::new(pool.mint_lp, false),
+            AccountMeta::new_readonly(pool.mint_x, false),
+            AccountMeta::new_readonly(pool.mint_y, false),
+            AccountMeta::new(pool.vault_x, false),
+            AccountMeta::new(pool.vault_y, false),
+            AccountMeta::new(key(new.vault_x), false),
+            AccountMeta::new(key(new.vault_y), false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            AccountMeta::new_readonly(ATA_PROGRAM, false),
+        ],
+    );
+    (ix, new)
+}
+
+#[test]
+fn migrated_pool_trades_under_the_new_seed() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.swap(&user, true, 100_000, 1);
+    let (reserves, lp_supply) = (
+        (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y)),
+        pool.lp_supply(),
+    );
+
+    let (ix, new) = migrate_seed_ix(&mut pool, &user.key, SEED + 1);
+    pool.run(&ix);
+
+    // The old config is left behind for good, its vaults closed
+    assert_eq!(
+        pool.with_config(|config| config.state()),
+        AmmState::Migrated as u8
+    );
+    for vault in [pool.vault_x, pool.vault_y] {
+        assert_eq!(pool.get(&vault).lamports, 0);
+    }
+
+    pool.config = Pubkey::new_from_array(new.config);
+    pool.config_bump = new.config_bump;
+    pool.vault_x = Pubkey::new_from_array(new.vault_x);
+    pool.vault_y = Pubkey::new_from_array(new.vault_y);
+    assert_eq!(pool.with_config(|config| config.seed()), SEED + 1);
+    assert_eq!(
+        (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y)),
+        reserves
+    );
+    assert_eq!(pool.lp_supply(), lp_supply);
+    assert_eq!(token_owner(&pool, &pool.vault_x), pool.config);
+
+    // Swaps run against the new config and vaults
+    pool.swap(&user, true, 100_000, 1);
+    assert_eq!(pool.amount(&pool.vault_x), reserves.0 + 100_000);
+    assert!(pool.amount(&pool.vault_y) < reserves.1);
+    pool.swap(&user, false, 100_000, 1);
+}
+
+#[test]
+fn migrate_seed_requires_the_authority_and_a_new_seed() {
+    let mut pool = Pool::funded();
+    let (authority, other) = (pool.user.key, pool.new_user());
+
+    let (ix, _) = migrate_seed_ix(&mut pool, &other.key, SEED + 1);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+
+    let (ix, _) = migrate_seed_ix(&mut pool, &authority, SEED);
+    pool.fail(&ix, ProgramError::InvalidArgument);
+}
+
+/// Funded pool whose X vault was handed to its authority
+fn pool_with_lost_vault() -> Pool {
+    let mut pool = Pool::funded();
+    let (vault, mint, owner) = (pool.vault_x, pool.mint_x, pool.user.key);
+    let amount = pool.amount(&vault);
+    pool.set_token_account(vault, mint, owner, amount);
+    pool
+}
+
+/// Owner of a token account
+fn token_owner(pool: &Pool, key: &Pubkey) -> Pubkey {
+    Pubkey::new_from_array(pool.get(key).data[32..64].try_into().unwrap())
+}
+
+#[test]
+fn lost_vault_is_handed_back_to_the_config() {
+    let mut pool = pool_with_lost_vault();
+    let authority = pool.user.key;
+
+    pool.run(&pool.reinitialize_vault_authority_ix(&authority, &pool.vault_x));
+    assert_eq!(token_owner(&pool, &pool.vault_x), pool.config);
+
+    // The pool trades on it again
+    let user = pool.user;
+    pool.swap(&user, false, 100_000, 1);
+}
+
+#[test]
+fn vault_recovery_requires_the_authority() {
+    let mut pool = pool_with_lost_vault();
+    let other = pool.new_user();
+
+    let ix = pool.reinitialize_vault_authority_ix(&other.key, &pool.vault_x);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn vault_recovery_rejects_healthy_vaults() {
+    let mut pool = pool_with_lost_vault();
+    let authority = pool.user.key;
+
+    let ix = pool.reinitialize_vault_authority_ix(&authority, &pool.vault_y);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+}
+
+#[test]
+fn vault_recovery_rejects_other_accounts() {
+    let mut pool = pool_with_lost_vault();
+    let authority = pool.user.key;
+
+    // Of a pool mint and the authority, but not at the vault address
+    let other = Pubkey::new_unique();
+    pool.set_token_account(other, pool.mint_x, authority, 0);
+    let ix = pool.reinitialize_vault_authority_ix(&authority, &other);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    // The vault, but held by someone else than the authority
+    let (vault, mint) = (pool.vault_x, pool.mint_x);
+    pool.set_token_account(vault, mint, Pubkey::new_unique(), 0);
+    let ix = pool.reinitialize_vault_authority_ix(&authority, &vault);
+    pool.fail(&ix, ProgramError::IllegalOwner);
+}
+
+#[test]
+fn vault_recovery_requires_spl_token() {
+    let mut pool = pool_with_lost_vault();
+    let authority = pool.user.key;
+
+    let other = Pubkey::new_unique();
+    pool.set(other, system_account(0));
+    let mut ix = pool.reinitialize_vault_authority_ix(&authority, &pool.vault_x);
+    ix.accounts[3] = AccountMeta::new_readonly(other, false);
+    pool.fail(&ix, ProgramError::IncorrectProgramId);
+}
+
+#[test]
+fn update_config_requires_the_authority() {
+    let mut pool = Pool::funded();
+    let other = pool.new_user();
+
+    let ix = pool.update_config_ix(&other.key, ConfigParam::MaxSwapPctBps, 100);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn update_config_rejects_unknown_params_and_values() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    let mut ix = pool.update_config_ix(&authority, ConfigParam::MaxSwapPctBps, 100);
+    ix.data[1] = ConfigParam::ProtocolFeeBps as u8 + 1;
+    pool.fail(&ix, ProgramError::InvalidInstructionData);
+
+    // Over 100%, or beyond the width of the field
+    for value in [10_001, u16::MAX as u64 + 1] {
+        let ix = pool.update_config_ix(&authority, ConfigParam::MaxSwapPctBps, value);
+        pool.fail(&ix, ProgramError::InvalidArgument);
+    }
+}
+
+#[test]
+fn label_is_set_and_read_back() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    pool.run(&pool.set_label_ix(&authority, b"SOL/USDC main"));
+    pool.with_config(|config| assert_eq!(config.label(), b"SOL/USDC main"));
+
+    // The full 16 bytes fit, an empty label clears it
+    pool.run(&pool.set_label_ix(&authority, b"0123456789abcdef"));
+    pool.with_config(|config| assert_eq!(config.label(), b"0123456789abcdef"));
+    pool.run(&pool.set_label_ix(&authority, b""));
+    pool.with_config(|config| assert!(config.label().is_empty()));
+}
+
+#[test]
+fn over_long_label_is_rejected() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    // Rejected rather than truncated to the first 16 bytes
+    let ix = pool.set_label_ix(&authority, b"0123456789abcdefg");
+    pool.fail(&ix, ProgramError::InvalidInstructionData);
+}
+
+#[test]
+fn set_label_requires_the_authority() {
+    let mut pool = Pool::funded();
+    let other = pool.new_user();
+
+    let ix = pool.set_label_ix(&other.key, b"mine");
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+}
+
+#[test]
+fn feature_flags_switch_operations_one_by_one() {
+    let features = [
+        Config::FEATURE_DEPOSIT,
+        Config::FEATURE_SWAP,
+        Config::FEATURE_WITHDRAW,
+    ];
+
+    for off in features {
+        let mut pool = Pool::funded();
+        let user = pool.user;
+        pool.update_config(
+            ConfigParam::FeatureFlags,
+            (Config::FEATURE_ALL & !off) as u64,
+        );
+
+        // Only the operation switched off fails, the others go through
+        let operations = [
+            pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+            pool.swap_ix(&user, true, 100_000, 1),
+            pool.withdraw_ix(&user, 100_000, 0, 0),
+        ];
+        for (feature, ix) in features.into_iter().zip(operations) {
+            match feature == off {
+                true => pool.fail(&ix, amm_error(AmmError::FeatureDisabled)),
+                false => {
+                    pool.run(&ix);
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn state_overrides_the_feature_flags() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let authority = user.key;
+
+    // Every flag on doesn't reopen a disabled pool
+    pool.admin_set_state(AmmState::Disabled);
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, amm_error(AmmError::PoolDisabled));
+
+    // Every flag off closes an initialized one
+    pool.admin_set_state(AmmState::Initialized);
+    pool.update_config(ConfigParam::FeatureFlags, 0);
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, amm_error(AmmError::FeatureDisabled));
+
+    // Bits past the known operations are rejected
+    let value = (Config::FEATURE_ALL | (Config::FEATURE_WITHDRAW << 1)) as u64;
+    let ix = pool.update_config_ix(&authority, ConfigParam::FeatureFlags, value);
+    pool.fail(&ix, ProgramError::InvalidArgument);
+
+    pool.update_config(ConfigParam::FeatureFlags, Config::FEATURE_ALL as u64);
+    pool.swap(&user, true, 100_000, 1);
+}
+
+#[test]
+fn lock_beyond_the_i64_range_is_rejected() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    // Would wrap to a negative timestamp
+    for value in [i64::MAX as u64 + 1, u64::MAX] {
+        let ix = pool.update_config_ix(&authority, ConfigParam::LockedUntil, value);
+        pool.fail(&ix, amm_error(AmmError::InvalidTimestamp));
+    }
+
+    pool.update_config(ConfigParam::LockedUntil, i64::MAX as u64);
+    pool.with_config(|config| assert_eq!(config.locked_until(), i64::MAX));
+}
+
+#[test]
+fn migrate_seed_rejects_a_non_mint_in_the_mint_slot() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    let (ix, _) = migrate_seed_ix(&mut pool, &authority, SEED + 1);
+
+    // Another mint in the X slot, then the real X mint key holding a
+    // token account, a system account, or an uninitialized mint
+    let mut other_mint = ix.clone();
+    other_mint.accounts[4].pubkey = pool.mint_lp;
+    pool.fail(&other_mint, amm_error(AmmError::InvalidMint));
+
+    let mint_x = pool.get(&pool.mint_x).clone();
+    let token_account = pool.get(&pool.vault_x).clone();
+    let uninitialized = pool.token_program_account(&[0; 82]);
+    for account in [token_account, system_account(1_000_000), uninitialized] {
+        pool.set(pool.mint_x, account);
+        pool.fail(&ix, amm_error(AmmError::InvalidMint));
+    }
+
+    // The real mint goes through
+    pool.set(pool.mint_x, mint_x);
+    pool.run(&ix);
+}
+
+#[test]
+fn migrate_seed_rejects_the_same_mint_in_both_slots() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    let (ix, _) = migrate_seed_ix(&mut pool, &authority, SEED + 1);
+
+    for (slot, mint) in [(5, pool.mint_x), (4, pool.mint_y)] {
+        let mut same = ix.clone();
+        same.accounts[slot].pubkey = mint;
+        pool.fail(&same, amm_error(AmmError::IdenticalMints));
+    }
+
+    pool.run(&ix);
+}
+
+fn nominate_authority_ix(pool: &Pool, authority: &Pubkey, nominee: &Pubkey) -> Instruction {
+    let mut data = vec![*NominateAuthority::DISCRIMINATOR];
+    data.extend_from_slice(nominee.as_ref());
+    pool.instruction(
+        data,
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(pool.config, false),
+        ],
+    )
+}
+
+fn accept_authority_ix(pool: &Pool, signer: &Pubkey) -> Instruction {
+    pool.instruction(
+        vec![*AcceptAuthority::DISCRIMINATOR],
+        vec![
+            AccountMeta::new_readonly(*signer, true),
+            AccountMeta::new(pool.config, false),
+        ],
+    )
+}
+
+#[test]
+fn authority_transfers_once_the_nominee_accepts() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+    let (nominee, other) = (pool.new_user().key, pool.new_user().key);
+
+    // Nobody nominated yet, and only the authority nominates
+    pool.fail(
+        &accept_authority_ix(&pool, &nominee),
+        ProgramError::InvalidAccountOwner,
+    );
+    pool.fail(
+        &nominate_authority_ix(&pool, &other, &nominee),
+        ProgramError::InvalidAccountOwner,
+    );
+    pool.fail(
+        &nominate_authority_ix(&pool, &authority, &Pubkey::default()),
+        ProgramError::InvalidArgument,
+    );
+
+    // The authority keeps control until the nominee accepts
+    pool.run(&nominate_authority_ix(&pool, &authority, &nominee));
+    assert_eq!(
+        pool.with_config(|config| *config.pending_authority()),
+        nominee.to_bytes()
+    );
+    pool.admin_set_state(AmmState::WithdrawOnly);
+    pool.fail(
+        &accept_authority_ix(&pool, &other),
+        ProgramError::InvalidAccountOwner,
+    );
+
+    pool.run(&accept_authority_ix(&pool, &nominee));
+    pool.with_config(|config| {
+        assert_eq!(*config.authority(), nominee.to_bytes());
+        assert_eq!(*config.pending_authority(), [0; 32]);
+    });
+
+    // The old authority is out, the new one in, and accepting again fails
+    let ix = pool.admin_set_state_ix(&authority, AmmState::Initialized);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+    pool.run(&pool.admin_set_state_ix(&nominee, AmmState::Initialized));
+    pool.fail(
+        &accept_authority_ix(&pool, &nominee),
+        ProgramError::InvalidAccountOwner,
+    );
+}
+
+fn set_curve_kind_ix(
+    pool: &Pool,
+    authority: &Pubkey,
+    curve_kind: u8,
+    weight_x: u16,
+) -> Instruction {
+    let mut data = vec![*SetCurveKind::DISCRIMINATOR, curve_kind];
+    data.extend_from_slice(&weight_x.to_le_bytes());
+    pool.instruction(
+        data,
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new(pool.config, false),
+        ],
+    )
+}
+
+#[test]
+fn empty_pool_switches_to_any_curve() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let authority = pool.user.key;
+
+    let other = pool.new_user();
+    let ix = set_curve_kind_ix(&pool, &other.key, Config::CURVE_WEIGHTED, 8_000);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+
+    pool.run(&set_curve_kind_ix(
+        &pool,
+        &authority,
+        Config::CURVE_WEIGHTED,
+        8_000,
+    ));
+    pool.with_config(|config| {
+        assert_eq!(config.curve_kind(), Config::CURVE_WEIGHTED);
+        assert_eq!(config.price_weights(), (8_000, 2_000));
+    });
+
+    // And back, the weight ignored
+    pool.run(&set_curve_kind_ix(
+        &pool,
+        &authority,
+        Config::CURVE_CONSTANT_PRODUCT,
+        1,
+    ));
+    assert_eq!(
+        pool.with_config(|config| config.price_weights()),
+        (5_000, 5_000)
+    );
+}
+
+#[test]
+fn live_pool_switches_only_within_the_tolerance() {
+    let mut pool = Pool::funded();
+    let authority = pool.user.key;
+
+    // 80/20 would quadruple the price of X, 50.3/49.7 moves it by 1.2%
+    for weight_x in [8_000, 2_000, 5_030] {
+        let ix = set_curve_kind_ix(&pool, &authority, Config::CURVE_WEIGHTED, weight_x);
+        pool.fail(&ix, amm_error(AmmError::CurveSwitchUnsafe));
+    }
+    assert_eq!(
+        pool.with_config(|config| config.curve_kind()),
+        Config::CURVE_CONSTANT_PRODUCT
+    );
+
+    // 50/50 weighted prices as the constant product, 50.2/49.8 by 0.8%
+    for weight_x in [5_000, 5_020] {
+        let ix = set_curve_kind_ix(&pool, &authority, Config::CURVE_WEIGHTED, weight_x);
+        pool.run(&ix);
+    }
+    assert_eq!(
+        pool.with_config(|config| config.price_weights()),
+        (5_020, 4_980)
+    );
+}
+
+fn collect_protocol_fees_ix(pool: &Pool, authority: &Pubkey, to: (Pubkey, Pubkey)) -> Instruction {
+    pool.instruction(
+        vec![*CollectProtocolFees::DISCRIMINATOR],
+        vec![
+            AccountMeta::new_readonly(*authority, true),
+            AccountMeta::new_readonly(pool.config, false),
+            AccountMeta::new(pool.vault_x, false),
+            AccountMeta::new(pool.vault_y, false),
+            AccountMeta::new(to.0, false),
+            AccountMeta::new(to.1, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+        ],
+    )
+}
+
+#[test]
+fn protocol_fees_are_swept_to_the_authority_accounts() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let other = pool.new_user();
+    let to = (Pubkey::new_unique(), Pubkey::new_unique());
+    pool.set_token_account(to.0, pool.mint_x, other.key, 0);
+    pool.set_token_account(to.1, pool.mint_y, other.key, 0);
+
+    // A fifth of the 300 X fee goes to the protocol
+    pool.update_config(ConfigParam::ProtocolFeeBps, 2_000);
+    pool.swap(&user, true, 100_000, 1);
+    let excess = |pool: &Pool| {
+        let (vault_x, vault_y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+        pool.with_config(|config| config.protocol_fees(vault_x, vault_y))
+    };
+    assert_eq!(excess(&pool), (60, 0));
+
+    // Only the authority sweeps, and only into accounts of the pool mints
+    let ix = collect_protocol_fees_ix(&pool, &other.key, to);
+    pool.fail(&ix, ProgramError::InvalidAccountOwner);
+    let ix = collect_protocol_fees_ix(&pool, &user.key, (to.1, to.0));
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    let ix = collect_protocol_fees_ix(&pool, &user.key, to);
+    pool.run(&ix);
+    assert_eq!((pool.amount(&to.0), pool.amount(&to.1)), (60, 0));
+    assert_eq!(excess(&pool), (0, 0));
+
+    // The reserves LPs own stay, a second sweep moves nothing
+    pool.run(&ix);
+    assert_eq!(pool.amount(&to.0), 60);
+}
@@ -0,0 +1,94 @@
+// Borsh encoding of the instruction data against the layout the program
+// parses, byte for byte. Host only, no SBF build needed:
+//   AMM_ADMIN=<pubkey> cargo test --features borsh --test borsh
+
+use blueshift_native_amm::{
+    AmmState, ConfigParam, DepositInstructionData, FixedTimeSource, InitializeInstructionData,
+    SwapInstructionData, UpdateConfigInstructionData, WithdrawInstructionData,
+};
+
+const NOW: i64 = 1_700_000_000;
+
+/// Little endian concatenation of `fields`
+fn layout(fields: &[&[u8]]) -> Vec<u8> {
+    fields.concat()
+}
+
+#[test]
+fn initialize_matches_the_full_layout() {
+    let data = layout(&[
+        &7u64.to_le_bytes(),
+        &30u16.to_le_bytes(),
+        &[1; 32],
+        &[2; 32],
+        &[254],
+        &[253],
+        &[9; 32],
+        &[AmmState::Disabled as u8],
+    ]);
+
+    let parsed = InitializeInstructionData::try_from(data.as_slice()).unwrap();
+    assert_eq!(borsh::to_vec(&parsed).unwrap(), data);
+
+    let decoded: InitializeInstructionData = borsh::from_slice(&data).unwrap();
+    let (seed, fee) = (decoded.seed, decoded.fee);
+    assert_eq!((seed, fee), (7, 30));
+    assert_eq!(decoded.authority, [9; 32]);
+    assert_eq!(decoded.initial_state, AmmState::Disabled as u8);
+}
+
+#[test]
+fn deposit_and_withdraw_match_the_layout() {
+    let data = layout(&[
+        &1_000u64.to_le_bytes(),
+        &2_000u64.to_le_bytes(),
+        &3_000u64.to_le_bytes(),
+        &(NOW + 60).to_le_bytes(),
+    ]);
+
+    let deposit = DepositInstructionData::parse(&data, &FixedTimeSource(NOW)).unwrap();
+    assert_eq!(borsh::to_vec(&deposit).unwrap(), data);
+    let withdraw = WithdrawInstructionData::parse(&data, &FixedTimeSource(NOW)).unwrap();
+    assert_eq!(borsh::to_vec(&withdraw).unwrap(), data);
+
+    let decoded: DepositInstructionData = borsh::from_slice(&data).unwrap();
+    assert_eq!(
+        (decoded.amount, decoded.max_x, decoded.max_y),
+        (1_000, 2_000, 3_000)
+    );
+}
+
+#[test]
+fn swap_matches_the_layout_with_partial_fill() {
+    let data = layout(&[
+        &[1],
+        &500u64.to_le_bytes(),
+        &400u64.to_le_bytes(),
+        &(NOW + 60).to_le_bytes(),
+        &[1],
+    ]);
+
+    let parsed = SwapInstructionData::parse(&data, &FixedTimeSource(NOW)).unwrap();
+    assert_eq!(borsh::to_vec(&parsed).unwrap(), data);
+
+    let decoded: SwapInstructionData = borsh::from_slice(&data).unwrap();
+    assert!(decoded.is_x && decoded.partial_fill);
+    assert_eq!((decoded.amount, decoded.min), (500, 400));
+}
+
+#[test]
+fn update_config_matches_the_layout() {
+    let data = layout(&[&[ConfigParam::MaxSwapPctBps as u8], &100u64.to_le_bytes()]);
+
+    let parsed = UpdateConfigInstructionData::try_from(data.as_slice()).unwrap();
+    assert_eq!(borsh::to_vec(&parsed).unwrap(), data);
+
+    let decoded: UpdateConfigInstructionData = borsh::from_slice(&data).unwrap();
+    assert!(decoded.param == ConfigParam::MaxSwapPctBps);
+    assert_eq!(decoded.value, 100);
+
+    // Unknown parameters don't decode
+    let mut unknown = data;
+    unknown[0] = u8::MAX;
+    assert!(borsh::from_slice::<UpdateConfigInstructionData>(&unknown).is_err());
+}
@@ -0,0 +1,629 @@
+// Harness shared by the runtime suites: runs the SBF build of the program
+// through mollusk, with the SPL token and associated token programs loaded,
+// over an account store kept up to date across instructions.
+#![allow(dead_code, clippy::new_without_default)]
+
+use blueshift_native_amm::client::{
+    admin_set_state_data, canonicalize_mints, get_pool_by_mints, update_fee_data,
+};
+use blueshift_native_amm::test_utils::{mint_data, token_account_data};
+use blueshift_native_amm::{AmmError, AmmState, Config, ConfigParam, GlobalConfig};
+use mollusk_svm::program::keyed_account_for_system_program;
+use mollusk_svm::result::{Check, InstructionResult, ProgramResult};
+use mollusk_svm::Mollusk;
+use mollusk_svm_programs_token::{associated_token, token};
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_program_error::ProgramError;
+use solana_pubkey::Pubkey;
+
+pub const SEED: u64 = 1;
+pub const FEE: u16 = 30;
+pub const DECIMALS: u8 = 6;
+/// Balance of each token a new user starts with
+pub const BALANCE: u64 = 1_000_000_000;
+/// Unix timestamp of the clock when the harness starts
+pub const NOW: i64 = 1_700_000_000;
+pub const SYSTEM_PROGRAM: Pubkey = Pubkey::new_from_array([0; 32]);
+pub const TOKEN_PROGRAM: Pubkey = Pubkey::new_from_array(pinocchio_token::ID);
+pub const ATA_PROGRAM: Pubkey = Pubkey::new_from_array(pinocchio_associated_token_account::ID);
+
+pub fn amm_error(error: AmmError) -> ProgramError {
+    ProgramError::Custom(error as u32)
+}
+
+/// Wallet of a pool user and its token accounts
+#[derive(Clone, Copy)]
+pub struct User {
+    pub key: Pubkey,
+    pub x: Pubkey,
+    pub y: Pubkey,
+    pub lp: Pubkey,
+}
+
+/// A pool of two fresh mints, initialized by `Pool::initialize`
+pub struct Pool {
+    pub mollusk: Mollusk,
+    pub program_id: Pubkey,
+    pub accounts: Vec<(Pubkey, Account)>,
+    /// Initializer and authority of the pool
+    pub user: User,
+    pub mint_x: Pubkey,
+    pub mint_y: Pubkey,
+    pub config: Pubkey,
+    pub config_bump: u8,
+    pub mint_lp: Pubkey,
+    pub lp_bump: u8,
+    pub vault_x: Pubkey,
+    pub vault_y: Pubkey,
+    pub global_config: Pubkey,
+}
+
+impl Pool {
+    /// Pool accounts before `Initialize`, the vaults already created
+    pub fn new() -> Self {
+        let mut pool = Self::without_vaults();
+        pool.set_token_account(pool.vault_x, pool.mint_x, pool.config, 0);
+        pool.set_token_account(pool.vault_y, pool.mint_y, pool.config, 0);
+        pool
+    }
+
+    /// Pool accounts before `Initialize`, the vaults not created yet
+    pub fn without_vaults() -> Self {
+        let program_id = Pubkey::new_from_array(blueshift_native_amm::ID);
+        let mut mollusk = Mollusk::new(&program_id, "blueshift_native_amm");
+        token::add_program(&mut mollusk);
+        associated_token::add_program(&mut mollusk);
+        mollusk.sysvars.clock.unix_timestamp = NOW;
+
+        let (mint_x, mint_y) = canonicalize_mints(
+            Pubkey::new_unique().to_bytes(),
+            Pubkey::new_unique().to_bytes(),
+        );
+        let addresses = get_pool_by_mints(&blueshift_native_amm::ID, SEED, &mint_x, &mint_y);
+        let (global_config, _) = Pubkey::find_program_address(&[GlobalConfig::SEED], &program_id);
+
+        let mut pool = Self {
+            mollusk,
+            program_id,
+            accounts: Vec::new(),
+            user: User {
+                key: Pubkey::default(),
+                x: Pubkey::default(),
+                y: Pubkey::default(),
+                lp: Pubkey::default(),
+            },
+            mint_x: Pubkey::new_from_array(mint_x),
+            mint_y: Pubkey::new_from_array(mint_y),
+            config: Pubkey::new_from_array(addresses.config),
+            config_bump: addresses.config_bump,
+            mint_lp: Pubkey::new_from_array(addresses.mint_lp),
+            lp_bump: addresses.lp_bump,
+            vault_x: Pubkey::new_from_array(addresses.vault_x),
+            vault_y: Pubkey::new_from_array(addresses.vault_y),
+            global_config,
+        };
+
+        pool.set(pool.config, system_account(0));
+        pool.set(pool.mint_lp, system_account(0));
+        // Never initialized, the global config only gates pools once it is
+        pool.set(global_config, system_account(0));
+
+        let (key, account) = keyed_account_for_system_program();
+        pool.set(key, account);
+        let (key, account) = token::keyed_account();
+        pool.set(key, account);
+        let (key, account) = associated_token::keyed_account();
+        pool.set(key, account);
+
+        for mint in [pool.mint_x, pool.mint_y] {
+            let account = pool.token_program_account(&mint_data(None, u64::MAX, DECIMALS));
+            pool.set(mint, account);
+        }
+
+        pool.user = pool.new_user();
+        pool
+    }
+
+    /// Initialized pool holding 10_000_000 X and 40_000_000 Y of the harness
+    /// user, for 1_000_000 LP
+    pub fn funded() -> Self {
+        let mut pool = Self::new();
+        pool.initialize();
+        let user = pool.user;
+        pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+        pool
+    }
+
+    /// A funded wallet holding `BALANCE` of X and Y, and an empty LP account
+    /// once the LP mint exists
+    pub fn new_user(&mut self) -> User {
+        let user = User {
+            key: Pubkey::new_unique(),
+            x: Pubkey::new_unique(),
+            y: Pubkey::new_unique(),
+            lp: Pubkey::new_unique(),
+        };
+
+        self.set(user.key, system_account(10_000_000_000));
+        self.set_token_account(user.x, self.mint_x, user.key, BALANCE);
+        self.set_token_account(user.y, self.mint_y, user.key, BALANCE);
+        if self
+            .accounts
+            .iter()
+            .any(|(key, account)| *key == self.mint_lp && account.owner == TOKEN_PROGRAM)
+        {
+            self.set_token_account(user.lp, self.mint_lp, user.key, 0);
+        }
+
+        user
+    }
+
+    pub fn set(&mut self, key: Pubkey, account: Account) {
+        match self.accounts.iter_mut().find(|(k, _)| *k == key) {
+            Some((_, existing)) => *existing = account,
+            None => self.accounts.push((key, account)),
+        }
+    }
+
+    pub fn get(&self, key: &Pubkey) -> &Account {
+        &self.accounts.iter().find(|(k, _)| k == key).unwrap().1
+    }
+
+    /// Rent exempt account of the token program holding `data`
+    pub fn token_program_account(&self, data: &[u8]) -> Account {
+        Account {
+            lamports: self.mollusk.sysvars.rent.minimum_balance(data.len()),
+            data: data.to_vec(),
+            owner: TOKEN_PROGRAM,
+            executable: false,
+            rent_epoch: 0,
+        }
+    }
+
+    pub fn set_token_account(&mut self, key: Pubkey, mint: Pubkey, owner: Pubkey, amount: u64) {
+        let data = token_account_data(&mint.to_bytes(), &owner.to_bytes(), amount);
+        let account = self.token_program_account(&data);
+        self.set(key, account);
+    }
+
+    /// Associated token account of `wallet` for `mint`
+    pub fn ata(&self, wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+        Pubkey::find_program_address(
+            &[wallet.as_ref(), TOKEN_PROGRAM.as_ref(), mint.as_ref()],
+            &ATA_PROGRAM,
+        )
+        .0
+    }
+
+    /// `CreateIdempotent` of the associated token program, as a client
+    /// sends it next to the pool instructions
+    pub fn create_ata_ix(&self, payer: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Instruction {
+        Instruction::new_with_bytes(
+            ATA_PROGRAM,
+            &[1],
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.ata(wallet, mint), false),
+                AccountMeta::new_readonly(*wallet, false),
+                AccountMeta::new_readonly(*mint, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            ],
+        )
+    }
+
+    /// Creates the associated token account of `wallet` for `mint`, paid
+    /// by `payer`, and returns its address
+    pub fn create_ata(&mut self, payer: &Pubkey, wallet: &Pubkey, mint: &Pubkey) -> Pubkey {
+        let ata = self.ata(wallet, mint);
+        self.set(ata, system_account(0));
+        self.run(&self.create_ata_ix(payer, wallet, mint));
+        ata
+    }
+
+    /// Token amount of a token account
+    pub fn amount(&self, key: &Pubkey) -> u64 {
+        let data = &self.get(key).data;
+        u64::from_le_bytes(data[64..72].try_into().unwrap())
+    }
+
+    /// Supply of the LP mint
+    pub fn lp_supply(&self) -> u64 {
+        let data = &self.get(&self.mint_lp).data;
+        u64::from_le_bytes(data[36..44].try_into().unwrap())
+    }
+
+    /// Copy of the config account data
+    pub fn config_data(&self) -> Vec<u8> {
+        self.get(&self.config).data.clone()
+    }
+
+    /// Reads the config account
+    pub fn with_config<T>(&self, read: impl FnOnce(&Config) -> T) -> T {
+        let data = self.config_data();
+        assert_eq!(data.len(), Config::LEN);
+        // `Config` has an alignment of 1 and the buffer is exactly `LEN` bytes
+        read(unsafe { Config::from_bytes_unchecked(&data) })
+    }
+
+    /// Edits the config account in place, for states no instruction
+    /// reaches on a fresh pool
+    pub fn with_config_mut<T>(&mut self, write: impl FnOnce(&mut Config) -> T) -> T {
+        let mut account = self.get(&self.config).clone();
+        assert_eq!(account.data.len(), Config::LEN);
+        // `Config` has an alignment of 1 and the buffer is exactly `LEN` bytes
+        let result = write(unsafe { Config::from_bytes_unchecked_mut(&mut account.data) });
+        self.set(self.config, account);
+        result
+    }
+
+    /// Rolls the config account back to the layout of the first release,
+    /// keeping the bytes it shares with the current one
+    pub fn downgrade_config_to_v0(&mut self) {
+        let mut account = self.get(&self.config).clone();
+        account.data.truncate(Config::V0_LEN);
+        account.lamports = self.mollusk.sysvars.rent.minimum_balance(Config::V0_LEN);
+        self.set(self.config, account);
+    }
+
+    pub fn now(&self) -> i64 {
+        self.mollusk.sysvars.clock.unix_timestamp
+    }
+
+    /// Moves the clock `secs` seconds forward
+    pub fn warp(&mut self, secs: i64) {
+        self.mollusk.sysvars.clock.unix_timestamp += secs;
+    }
+
+    /// Expiration a minute from now
+    pub fn expiration(&self) -> i64 {
+        self.now() + 60
+    }
+
+    /// Runs `instruction` against the store, keeping the resulting accounts
+    /// only when it succeeds
+    pub fn process(&mut self, instruction: &Instruction) -> InstructionResult {
+        let result = self
+            .mollusk
+            .process_instruction(instruction, &self.accounts);
+
+        if matches!(result.program_result, ProgramResult::Success) {
+            for (key, account) in result.resulting_accounts.clone() {
+                self.set(key, account);
+            }
+        }
+
+        result
+    }
+
+    /// Runs `instruction`, expecting it to succeed
+    pub fn run(&mut self, instruction: &Instruction) -> InstructionResult {
+        let result = self.mollusk.process_and_validate_instruction(
+            instruction,
+            &self.accounts,
+            &[Check::success()],
+        );
+
+        for (key, account) in result.resulting_accounts.clone() {
+            self.set(key, account);
+        }
+
+        result
+    }
+
+    /// Runs `instruction`, expecting it to fail with `error` and leave
+    /// every account untouched
+    pub fn fail(&mut self, instruction: &Instruction, error: ProgramError) {
+        self.mollusk.process_and_validate_instruction(
+            instruction,
+            &self.accounts,
+            &[Check::err(error)],
+        );
+    }
+
+    pub fn instruction(&self, data: Vec<u8>, accounts: Vec<AccountMeta>) -> Instruction {
+        Instruction::new_with_bytes(self.program_id, &data, accounts)
+    }
+
+    /// `InitializeGlobal` signed by `admin`, charging new pools
+    /// `creation_fee` for `recipient` and giving them `defaults`, as
+    /// `(max_swap_pct_bps, min_deposit_lp, min_swap_amount)`
+    pub fn initialize_global_ix(
+        &self,
+        admin: &Pubkey,
+        recipient: &Pubkey,
+        creation_fee: u64,
+        defaults: (u16, u64, u64),
+    ) -> Instruction {
+        let (_, bump) = Pubkey::find_program_address(&[GlobalConfig::SEED], &self.program_id);
+        let mut data = vec![*blueshift_native_amm::InitializeGlobal::DISCRIMINATOR, bump];
+        data.extend_from_slice(&recipient.to_bytes());
+        data.extend_from_slice(&creation_fee.to_le_bytes());
+        data.extend_from_slice(&defaults.0.to_le_bytes());
+        data.extend_from_slice(&defaults.1.to_le_bytes());
+        data.extend_from_slice(&defaults.2.to_le_bytes());
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new(*admin, true),
+                AccountMeta::new(self.global_config, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+            ],
+        )
+    }
+
+    /// `SetGlobalPause` signed by `admin`
+    pub fn set_global_pause_ix(&self, admin: &Pubkey, paused: bool) -> Instruction {
+        self.instruction(
+            vec![
+                *blueshift_native_amm::SetGlobalPause::DISCRIMINATOR,
+                paused as u8,
+            ],
+            vec![
+                AccountMeta::new_readonly(*admin, true),
+                AccountMeta::new(self.global_config, false),
+            ],
+        )
+    }
+
+    /// `Initialize` with the harness user as initializer and authority,
+    /// `authority` left out for an immutable pool
+    pub fn initialize_ix(&self, authority: Option<Pubkey>) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::Initialize::DISCRIMINATOR];
+        data.extend_from_slice(&SEED.to_le_bytes());
+        data.extend_from_slice(&FEE.to_le_bytes());
+        data.extend_from_slice(&self.mint_x.to_bytes());
+        data.extend_from_slice(&self.mint_y.to_bytes());
+        data.push(self.config_bump);
+        data.push(self.lp_bump);
+        if let Some(authority) = authority {
+            data.extend_from_slice(&authority.to_bytes());
+        }
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new(self.user.key, true),
+                AccountMeta::new(self.mint_lp, false),
+                AccountMeta::new(self.config, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+                AccountMeta::new_readonly(self.global_config, false),
+            ],
+        )
+    }
+
+    /// Initializes the pool with the harness user as its authority, and
+    /// gives the harness user its LP account
+    pub fn initialize(&mut self) {
+        self.run(&self.initialize_ix(Some(self.user.key)));
+        self.set_token_account(self.user.lp, self.mint_lp, self.user.key, 0);
+    }
+
+    /// Accounts of `Deposit` and `Withdraw`, which share their layout
+    pub fn liquidity_metas(&self, user: &User) -> Vec<AccountMeta> {
+        vec![
+            AccountMeta::new(user.key, true),
+            AccountMeta::new(self.mint_lp, false),
+            AccountMeta::new(self.vault_x, false),
+            AccountMeta::new(self.vault_y, false),
+            AccountMeta::new(user.x, false),
+            AccountMeta::new(user.y, false),
+            AccountMeta::new(user.lp, false),
+            AccountMeta::new(self.config, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            AccountMeta::new_readonly(self.global_config, false),
+        ]
+    }
+
+    pub fn deposit_ix(&self, user: &User, amount: u64, max_x: u64, max_y: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::Deposit::DISCRIMINATOR];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&max_x.to_le_bytes());
+        data.extend_from_slice(&max_y.to_le_bytes());
+        data.extend_from_slice(&self.expiration().to_le_bytes());
+
+        self.instruction(data, self.liquidity_metas(user))
+    }
+
+    pub fn deposit(&mut self, user: &User, amount: u64, max_x: u64, max_y: u64) {
+        self.run(&self.deposit_ix(user, amount, max_x, max_y));
+    }
+
+    pub fn withdraw_ix(&self, user: &User, amount: u64, min_x: u64, min_y: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::Withdraw::DISCRIMINATOR];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&min_x.to_le_bytes());
+        data.extend_from_slice(&min_y.to_le_bytes());
+        data.extend_from_slice(&self.expiration().to_le_bytes());
+
+        self.instruction(data, self.liquidity_metas(user))
+    }
+
+    pub fn withdraw(&mut self, user: &User, amount: u64, min_x: u64, min_y: u64) {
+        self.run(&self.withdraw_ix(user, amount, min_x, min_y));
+    }
+
+    pub fn swap_ix(&self, user: &User, is_x: bool, amount: u64, min: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::Swap::DISCRIMINATOR, is_x as u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+        data.extend_from_slice(&min.to_le_bytes());
+        data.extend_from_slice(&self.expiration().to_le_bytes());
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new(user.key, true),
+                AccountMeta::new(user.x, false),
+                AccountMeta::new(user.y, false),
+                AccountMeta::new(self.vault_x, false),
+                AccountMeta::new(self.vault_y, false),
+                AccountMeta::new_readonly(self.mint_lp, false),
+                AccountMeta::new(self.config, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+                AccountMeta::new_readonly(self.global_config, false),
+            ],
+        )
+    }
+
+    pub fn swap(&mut self, user: &User, is_x: bool, amount: u64, min: u64) {
+        self.run(&self.swap_ix(user, is_x, amount, min));
+    }
+
+    /// `SimulateWithdraw` of `amount` LP
+    pub fn simulate_withdraw_ix(&self, amount: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::SimulateWithdraw::DISCRIMINATOR];
+        data.extend_from_slice(&amount.to_le_bytes());
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.mint_lp, false),
+                AccountMeta::new_readonly(self.vault_x, false),
+                AccountMeta::new_readonly(self.vault_y, false),
+                AccountMeta::new_readonly(self.config, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            ],
+        )
+    }
+
+    /// `PoolHealth` with X valued at `price`, Q32.32, or 1:1 when 0
+    pub fn pool_health_ix(&self, price: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::PoolHealth::DISCRIMINATOR];
+        data.extend_from_slice(&price.to_le_bytes());
+
+        self.instruction(data, vec![AccountMeta::new_readonly(self.config, false)])
+    }
+
+    /// `AccruedFees` of the pool
+    pub fn accrued_fees_ix(&self) -> Instruction {
+        self.instruction(
+            vec![*blueshift_native_amm::AccruedFees::DISCRIMINATOR],
+            vec![AccountMeta::new_readonly(self.config, false)],
+        )
+    }
+
+    /// `Quote` of a swap of `amount` X, or Y
+    pub fn quote_ix(&self, is_x: bool, amount: u64) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::Quote::DISCRIMINATOR, is_x as u8];
+        data.extend_from_slice(&amount.to_le_bytes());
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(self.config, false),
+                AccountMeta::new_readonly(self.mint_lp, false),
+                AccountMeta::new_readonly(self.vault_x, false),
+                AccountMeta::new_readonly(self.vault_y, false),
+            ],
+        )
+    }
+
+    /// `UpdateConfig` of `param` signed by `authority`
+    pub fn update_config_ix(
+        &self,
+        authority: &Pubkey,
+        param: ConfigParam,
+        value: u64,
+    ) -> Instruction {
+        let mut data = vec![
+            *blueshift_native_amm::UpdateConfig::DISCRIMINATOR,
+            param as u8,
+        ];
+        data.extend_from_slice(&value.to_le_bytes());
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(self.config, false),
+            ],
+        )
+    }
+
+    /// Sets `param` as the pool authority
+    pub fn update_config(&mut self, param: ConfigParam, value: u64) {
+        let authority = self.user.key;
+        self.run(&self.update_config_ix(&authority, param, value));
+    }
+
+    /// `SetLabel` of `label` signed by `authority`
+    pub fn set_label_ix(&self, authority: &Pubkey, label: &[u8]) -> Instruction {
+        let mut data = vec![*blueshift_native_amm::SetLabel::DISCRIMINATOR];
+        data.extend_from_slice(label);
+
+        self.instruction(
+            data,
+            vec![
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(self.config, false),
+            ],
+        )
+    }
+
+    /// `MigrateConfig` paid by `payer`
+    pub fn migrate_config_ix(&self, payer: &Pubkey, lp_bump: u8) -> Instruction {
+        self.instruction(
+            vec![*blueshift_native_amm::MigrateConfig::DISCRIMINATOR, lp_bump],
+            vec![
+                AccountMeta::new(*payer, true),
+                AccountMeta::new(self.config, false),
+                AccountMeta::new_readonly(self.mint_lp, false),
+                AccountMeta::new_readonly(self.vault_x, false),
+                AccountMeta::new_readonly(self.vault_y, false),
+                AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+            ],
+        )
+    }
+
+    /// `ReinitializeVaultAuthority` of `vault` signed by `authority`
+    pub fn reinitialize_vault_authority_ix(
+        &self,
+        authority: &Pubkey,
+        vault: &Pubkey,
+    ) -> Instruction {
+        self.instruction(
+            vec![*blueshift_native_amm::ReinitializeVaultAuthority::DISCRIMINATOR],
+            vec![
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new_readonly(self.config, false),
+                AccountMeta::new(*vault, false),
+                AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            ],
+        )
+    }
+
+    /// `UpdateFee` to `fee` signed by `authority`
+    pub fn update_fee_ix(&self, authority: &Pubkey, fee: u16) -> Instruction {
+        self.instruction(
+            update_fee_data(fee).to_vec(),
+            vec![
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(self.config, false),
+            ],
+        )
+    }
+
+    /// `AdminSetState` to `state` signed by `authority`
+    pub fn admin_set_state_ix(&self, authority: &Pubkey, state: AmmState) -> Instruction {
+        self.instruction(
+            admin_set_state_data(state as u8).to_vec(),
+            vec![
+                AccountMeta::new_readonly(*authority, true),
+                AccountMeta::new(self.config, false),
+            ],
+        )
+    }
+
+    /// Moves the pool to `state` as the pool authority
+    pub fn admin_set_state(&mut self, state: AmmState) {
+        let authority = self.user.key;
+        self.run(&self.admin_set_state_ix(&authority, state));
+    }
+}
+
+pub fn system_account(lamports: u64) -> Account {
+    Account::new(lamports, 0, &SYSTEM_PROGRAM)
+}
@@ -0,0 +1,44 @@
+// Error logs against the runtime: the failing instruction and the name of
+// its `AmmError`, logged under `debug-errors`.
+//
+// Runs the SBF build of the program with the feature through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf --features debug-errors
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils,debug-errors --test debug_errors
+
+mod common;
+
+use blueshift_native_amm::math::quote_swap;
+use blueshift_native_amm::{AmmError, Swap};
+use common::{amm_error, Pool, FEE};
+use solana_log_collector::LogCollector;
+
+#[test]
+fn slippage_error_logs_the_instruction_and_the_variant() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let logs = LogCollector::new_ref();
+    pool.mollusk.logger = Some(logs.clone());
+
+    let out = quote_swap(10_000_000, 40_000_000, 100_000, FEE).unwrap();
+    let ix = pool.swap_ix(&user, true, 100_000, out + 1);
+    pool.fail(&ix, amm_error(AmmError::SlippageExceeded));
+
+    let expected = format!(
+        "Program log: instruction {} failed: AmmError::SlippageExceeded",
+        Swap::DISCRIMINATOR
+    );
+    assert!(logs.borrow().get_recorded_content().contains(&expected));
+}
+
+#[test]
+fn successful_instructions_log_no_error() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let logs = LogCollector::new_ref();
+    pool.mollusk.logger = Some(logs.clone());
+
+    pool.swap(&user, true, 100_000, 1);
+
+    let recorded = logs.borrow().get_recorded_content().to_vec();
+    assert!(!recorded.iter().any(|log| log.contains("AmmError::")));
+}
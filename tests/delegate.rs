@@ -0,0 +1,243 @@
+// Relayed swaps against the runtime: a relayer, delegate of the user's
+// input account, swaps on the user's behalf under an intent the user
+// signed off-chain, verified by the Ed25519 instruction right before.
+//
+// Runs the SBF build of the program through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils --test delegate
+
+mod common;
+
+use blueshift_native_amm::ed25519::{ED25519_PROGRAM_ID, INSTRUCTIONS_SYSVAR_ID};
+use blueshift_native_amm::test_utils::token_account_data_with_delegate;
+use blueshift_native_amm::{AmmError, SwapWithDelegate};
+use common::{amm_error, system_account, Pool, User, BALANCE, SYSTEM_PROGRAM, TOKEN_PROGRAM};
+use solana_account::Account;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::{pubkey, Pubkey};
+
+const SYSVAR_PROGRAM: Pubkey = pubkey!("Sysvar1111111111111111111111111111111111111");
+const INSTRUCTIONS_SYSVAR: Pubkey = Pubkey::new_from_array(INSTRUCTIONS_SYSVAR_ID);
+/// Allowance the user gives the relayer over its X
+const ALLOWANCE: u64 = 1_000_000;
+
+/// A user of the funded pool whose X the returned relayer may spend
+fn delegation(pool: &mut Pool) -> (User, User) {
+    let user = pool.new_user();
+    let relayer = pool.new_user();
+    let data = token_account_data_with_delegate(
+        &pool.mint_x.to_bytes(),
+        &user.key.to_bytes(),
+        BALANCE,
+        Some(&relayer.key.to_bytes()),
+        ALLOWANCE,
+    );
+    let account = pool.token_program_account(&data);
+    pool.set(user.x, account);
+    (user, relayer)
+}
+
+/// Nonce account of `user` on the pool
+fn nonce_account(pool: &Pool, user: &User) -> Pubkey {
+    let seeds: &[&[u8]] = &[b"nonce", pool.config.as_ref(), user.key.as_ref()];
+    Pubkey::find_program_address(seeds, &pool.program_id).0
+}
+
+/// `SwapWithDelegate` of `amount` X for at least `min` Y, relayed by `relayer`
+fn delegated_swap_ix(
+    pool: &mut Pool,
+    relayer: &User,
+    user: &User,
+    amount: u64,
+    min: u64,
+    nonce: u64,
+) -> Instruction {
+    let nonce_account = nonce_account(pool, user);
+    if !pool.accounts.iter().any(|(key, _)| *key == nonce_account) {
+        pool.set(nonce_account, system_account(0));
+    }
+
+    let mut data = vec![*SwapWithDelegate::DISCRIMINATOR, 1];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.extend_from_slice(&min.to_le_bytes());
+    data.extend_from_slice(&pool.expiration().to_le_bytes());
+    data.extend_from_slice(&nonce.to_le_bytes());
+
+    pool.instruction(
+        data,
+        vec![
+            AccountMeta::new(relayer.key, true),
+            AccountMeta::new_readonly(user.key, false),
+            AccountMeta::new(user.x, false),
+            AccountMeta::new(user.y, false),
+            AccountMeta::new(pool.vault_x, false),
+            AccountMeta::new(pool.vault_y, false),
+            AccountMeta::new_readonly(pool.mint_lp, false),
+            AccountMeta::new(pool.config, false),
+            AccountMeta::new(nonce_account, false),
+            AccountMeta::new_readonly(INSTRUCTIONS_SYSVAR, false),
+            AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+            AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+            AccountMeta::new_readonly(pool.global_config, false),
+        ],
+    )
+}
+
+/// Intent the user signs for `swap`: the config, then the swap parameters
+/// and nonce exactly as they follow the discriminator
+fn intent(pool: &Pool, swap: &Instruction) -> Vec<u8> {
+    [pool.config.as_ref(), &swap.data[1..]].concat()
+}
+
+/// Ed25519 verification of a signature of `message` by `signer`, all of
+/// its inputs in its own data. The runtime checks the signature itself,
+/// the program only what it covers.
+fn ed25519_ix(signer: &Pubkey, message: &[u8]) -> Instruction {
+    const PUBLIC_KEY: u16 = 16;
+    const SIGNATURE: u16 = PUBLIC_KEY + 32;
+    const MESSAGE: u16 = SIGNATURE + 64;
+
+    let mut data = vec![1, 0];
+    for offset in [
+        SIGNATURE,
+        u16::MAX,
+        PUBLIC_KEY,
+        u16::MAX,
+        MESSAGE,
+        message.len() as u16,
+        u16::MAX,
+    ] {
+        data.extend_from_slice(&offset.to_le_bytes());
+    }
+    data.extend_from_slice(signer.as_ref());
+    data.extend_from_slice(&[0; 64]);
+    data.extend_from_slice(message);
+
+    Instruction::new_with_bytes(Pubkey::new_from_array(ED25519_PROGRAM_ID), &data, vec![])
+}
+
+/// Instructions sysvar of a transaction running `instructions`, at `current`
+fn set_instructions_sysvar(pool: &mut Pool, instructions: &[Instruction], current: u16) {
+    let serialized: Vec<Vec<u8>> = instructions
+        .iter()
+        .map(|instruction| {
+            let mut data = (instruction.accounts.len() as u16).to_le_bytes().to_vec();
+            for meta in &instruction.accounts {
+                data.push((meta.is_signer as u8) | ((meta.is_writable as u8) << 1));
+                data.extend_from_slice(meta.pubkey.as_ref());
+            }
+            data.extend_from_slice(instruction.program_id.as_ref());
+            data.extend_from_slice(&(instruction.data.len() as u16).to_le_bytes());
+            data.extend_from_slice(&instruction.data);
+            data
+        })
+        .collect();
+
+    let mut data = (instructions.len() as u16).to_le_bytes().to_vec();
+    let mut offset = 2 + 2 * instructions.len();
+    for instruction in &serialized {
+        data.extend_from_slice(&(offset as u16).to_le_bytes());
+        offset += instruction.len();
+    }
+    data.extend(serialized.concat());
+    data.extend_from_slice(&current.to_le_bytes());
+
+    let account = Account {
+        lamports: 1,
+        data,
+        owner: SYSVAR_PROGRAM,
+        executable: false,
+        rent_epoch: 0,
+    };
+    pool.set(INSTRUCTIONS_SYSVAR, account);
+}
+
+/// Precedes `swap` with the verification of `message` signed by `signer`
+fn sign(pool: &mut Pool, signer: &Pubkey, message: &[u8], swap: &Instruction) {
+    set_instructions_sysvar(pool, &[ed25519_ix(signer, message), swap.clone()], 1);
+}
+
+fn nonce(pool: &Pool, user: &User) -> u64 {
+    let data = &pool.get(&nonce_account(pool, user)).data;
+    u64::from_le_bytes(data[..8].try_into().unwrap())
+}
+
+#[test]
+fn delegated_swap_spends_the_users_allowance() {
+    let mut pool = Pool::funded();
+    let (user, relayer) = delegation(&mut pool);
+
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, 100_000, 1, 0);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.run(&ix);
+
+    // The user's tokens moved, the relayer only paid for the nonce account
+    assert_eq!(pool.amount(&user.x), BALANCE - 100_000);
+    assert!(pool.amount(&user.y) > BALANCE);
+    assert_eq!(pool.amount(&relayer.x), BALANCE);
+    assert_eq!(pool.amount(&relayer.y), BALANCE);
+    assert_eq!(nonce(&pool, &user), 1);
+
+    // The next intent takes the next nonce
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, 100_000, 1, 1);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.run(&ix);
+    assert_eq!(pool.amount(&user.x), BALANCE - 200_000);
+    assert_eq!(nonce(&pool, &user), 2);
+}
+
+#[test]
+fn replayed_intent_is_rejected() {
+    let mut pool = Pool::funded();
+    let (user, relayer) = delegation(&mut pool);
+
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, 100_000, 1, 0);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.run(&ix);
+
+    // Same intent, same signature
+    pool.fail(&ix, amm_error(AmmError::NonceMismatch));
+
+    // Nor can an intent skip ahead
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, 100_000, 1, 2);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.fail(&ix, amm_error(AmmError::NonceMismatch));
+}
+
+#[test]
+fn intent_must_cover_the_swap() {
+    let mut pool = Pool::funded();
+    let (user, relayer) = delegation(&mut pool);
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, 100_000, 1, 0);
+
+    // Signed for a higher minimum than the relayer submits
+    let mut message = intent(&pool, &ix);
+    message[41..49].copy_from_slice(&90_000u64.to_le_bytes());
+    sign(&mut pool, &user.key, &message, &ix);
+    pool.fail(&ix, amm_error(AmmError::InvalidIntentSignature));
+
+    // Signed by someone else, the relayer itself
+    sign(&mut pool, &relayer.key, &intent(&pool, &ix), &ix);
+    pool.fail(&ix, amm_error(AmmError::InvalidIntentSignature));
+
+    // Not signed at all
+    set_instructions_sysvar(&mut pool, &[ix.clone()], 0);
+    pool.fail(&ix, amm_error(AmmError::InvalidIntentSignature));
+}
+
+#[test]
+fn relayer_must_be_the_delegate() {
+    let mut pool = Pool::funded();
+    let (user, _) = delegation(&mut pool);
+    let other = pool.new_user();
+
+    let ix = delegated_swap_ix(&mut pool, &other, &user, 100_000, 1, 0);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.fail(&ix, amm_error(AmmError::InvalidDelegate));
+
+    // Nor spend beyond the allowance
+    let (user, relayer) = delegation(&mut pool);
+    let ix = delegated_swap_ix(&mut pool, &relayer, &user, ALLOWANCE + 1, 1, 0);
+    sign(&mut pool, &user.key, &intent(&pool, &ix), &ix);
+    pool.fail(&ix, amm_error(AmmError::InvalidDelegate));
+}
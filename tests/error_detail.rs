@@ -0,0 +1,138 @@
+// Error details against the runtime: the numbers behind a slippage or
+// deadline rejection, written to the return data under `return-data`.
+//
+// Runs the SBF build of the program with the feature through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf --features return-data
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils,return-data --test error_detail
+
+mod common;
+
+use blueshift_native_amm::math::quote_swap;
+use blueshift_native_amm::{AmmError, ErrorDetail, ErrorDetailKind};
+use common::{amm_error, Pool, FEE};
+use mollusk_svm::result::ProgramResult;
+use solana_instruction::Instruction;
+use solana_program_error::ProgramError;
+
+/// Runs `instruction`, expecting it to fail with `error`, and decodes the
+/// detail it left behind
+fn fail_with_detail(
+    pool: &mut Pool,
+    instruction: &Instruction,
+    error: ProgramError,
+) -> ErrorDetail {
+    let result = pool.process(instruction);
+    assert!(matches!(result.program_result, ProgramResult::Failure(ref e) if *e == error));
+    ErrorDetail::decode(&result.return_data).unwrap()
+}
+
+#[test]
+fn swap_slippage_reports_the_output_and_the_minimum() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let out = quote_swap(10_000_000, 40_000_000, 100_000, FEE).unwrap();
+
+    let ix = pool.swap_ix(&user, true, 100_000, out + 1);
+    let detail = fail_with_detail(&mut pool, &ix, amm_error(AmmError::SlippageExceeded));
+    assert_eq!(
+        detail,
+        ErrorDetail {
+            kind: ErrorDetailKind::Slippage,
+            computed: out,
+            bound: out + 1,
+        }
+    );
+}
+
+#[test]
+fn deposit_slippage_reports_the_side_over_its_maximum() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // 100_000 LP takes 1_000_000 X and 4_000_000 Y, one short on either side
+    let cases = [
+        (
+            (999_999, 4_000_000),
+            ErrorDetailKind::DepositSlippageX,
+            1_000_000,
+        ),
+        (
+            (1_000_000, 3_999_999),
+            ErrorDetailKind::DepositSlippageY,
+            4_000_000,
+        ),
+    ];
+    for ((max_x, max_y), kind, computed) in cases {
+        let ix = pool.deposit_ix(&user, 100_000, max_x, max_y);
+        let detail = fail_with_detail(&mut pool, &ix, amm_error(AmmError::DepositSlippageExceeded));
+        assert_eq!(
+            detail,
+            ErrorDetail {
+                kind,
+                computed,
+                bound: computed - 1,
+            }
+        );
+    }
+
+    // Both at their maximum, the deposit goes through
+    pool.deposit(&user, 100_000, 1_000_000, 4_000_000);
+}
+
+#[test]
+fn withdraw_slippage_reports_the_amount_and_the_minimum() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // 100_000 LP releases 1_000_000 X
+    let ix = pool.withdraw_ix(&user, 100_000, 1_000_001, 0);
+    let detail = fail_with_detail(&mut pool, &ix, ProgramError::InvalidArgument);
+    assert_eq!(
+        detail,
+        ErrorDetail {
+            kind: ErrorDetailKind::Slippage,
+            computed: 1_000_000,
+            bound: 1_000_001,
+        }
+    );
+}
+
+#[test]
+fn expired_swap_reports_the_time_and_the_expiration() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    let expiration = pool.expiration();
+    pool.warp(61);
+
+    let detail = fail_with_detail(&mut pool, &ix, ProgramError::InvalidInstructionData);
+    assert_eq!(
+        detail,
+        ErrorDetail {
+            kind: ErrorDetailKind::Deadline,
+            computed: pool.now() as u64,
+            bound: expiration as u64,
+        }
+    );
+}
+
+#[test]
+fn frozen_account_reports_its_position() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    // AccountState::Frozen on the Y vault, fourth in the swap's check list
+    let mut account = pool.get(&pool.vault_y).clone();
+    account.data[108] = 2;
+    pool.set(pool.vault_y, account);
+
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    let detail = fail_with_detail(&mut pool, &ix, amm_error(AmmError::AccountFrozen));
+    assert_eq!(
+        detail,
+        ErrorDetail {
+            kind: ErrorDetailKind::FrozenAccount,
+            computed: 3,
+            bound: 0,
+        }
+    );
+}
@@ -0,0 +1,70 @@
+// First deposit under `exact-bootstrap-lp` against the runtime: the
+// geometric mean of the deposited amounts is minted, less the minimum
+// liquidity locked in the LP ATA of the config.
+//
+// Runs the SBF build of the program with the feature through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf --features exact-bootstrap-lp
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils,exact-bootstrap-lp --test exact_bootstrap_lp
+
+mod common;
+
+use blueshift_native_amm::math::MINIMUM_LIQUIDITY;
+use blueshift_native_amm::AmmError;
+use common::{amm_error, system_account, Pool, User, ATA_PROGRAM, SYSTEM_PROGRAM, TOKEN_PROGRAM};
+use solana_instruction::{AccountMeta, Instruction};
+use solana_pubkey::Pubkey;
+
+/// Initialized pool without liquidity, and the address of its LP lock
+fn empty_pool() -> (Pool, Pubkey) {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let lp_lock = pool.ata(&pool.config, &pool.mint_lp);
+    pool.set(lp_lock, system_account(0));
+    (pool, lp_lock)
+}
+
+/// First `Deposit`, with the accounts creating the LP lock
+fn bootstrap_ix(pool: &Pool, user: &User, lp_lock: Pubkey, max_x: u64, max_y: u64) -> Instruction {
+    // The requested amount is ignored
+    let mut ix = pool.deposit_ix(user, 1, max_x, max_y);
+    ix.accounts.extend([
+        AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+        AccountMeta::new_readonly(ATA_PROGRAM, false),
+        AccountMeta::new(lp_lock, false),
+    ]);
+    ix
+}
+
+#[test]
+fn first_deposit_mints_the_geometric_mean() {
+    let (mut pool, lp_lock) = empty_pool();
+    let user = pool.user;
+
+    // sqrt(10_000_000 * 40_000_000) = 20_000_000
+    pool.run(&bootstrap_ix(&pool, &user, lp_lock, 10_000_000, 40_000_000));
+    assert_eq!(pool.amount(&user.lp), 20_000_000 - MINIMUM_LIQUIDITY);
+    assert_eq!(pool.amount(&lp_lock), MINIMUM_LIQUIDITY);
+    assert_eq!(pool.get(&lp_lock).owner, TOKEN_PROGRAM);
+    assert_eq!(pool.lp_supply(), 20_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 10_000_000);
+    assert_eq!(pool.amount(&pool.vault_y), 40_000_000);
+
+    // Later deposits mint the requested amount, without the lock
+    pool.deposit(&user, 2_000_000, 1_000_000, 4_000_000);
+    assert_eq!(pool.lp_supply(), 22_000_000);
+    assert_eq!(pool.amount(&lp_lock), MINIMUM_LIQUIDITY);
+}
+
+#[test]
+fn first_deposit_at_the_minimum_liquidity_is_rejected() {
+    let (mut pool, lp_lock) = empty_pool();
+    let user = pool.user;
+
+    // sqrt(1_000 * 1_000) leaves nothing for the depositor
+    let ix = bootstrap_ix(&pool, &user, lp_lock, 1_000, 1_000);
+    pool.fail(&ix, amm_error(AmmError::AmountTooSmall));
+
+    // sqrt(1_000 * 1_003) clears it by one LP
+    pool.run(&bootstrap_ix(&pool, &user, lp_lock, 1_000, 1_003));
+    assert_eq!(pool.amount(&user.lp), 1);
+}
@@ -0,0 +1,980 @@
+// End-to-end lifecycle of a pool against the runtime: initialize, deposit,
+// swap both ways, withdraw, asserting balances and LP supply at each step,
+// and what holds liquidity back: fees and the liquidity lock.
+//
+// Runs the SBF build of the program through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils --test lifecycle
+
+mod common;
+
+use blueshift_native_amm::client::{get_pool_by_mints, get_pools_by_mints, vault_addresses};
+use blueshift_native_amm::test_utils::mint_data;
+use blueshift_native_amm::utils::TOKEN_2022_PROGRAM_ID;
+use blueshift_native_amm::{
+    decode_pool_health, decode_simulate_withdraw, AmmError, AmmState, Config, ConfigParam,
+    DepositSingleSided, WithdrawSingleSided,
+};
+use common::{
+    amm_error, system_account, Pool, User, ATA_PROGRAM, BALANCE, FEE, NOW, SEED, SYSTEM_PROGRAM,
+    TOKEN_PROGRAM,
+};
+use mollusk_svm::result::ProgramResult;
+use pinocchio_token::state::Mint;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_program_error::ProgramError;
+use solana_pubkey::Pubkey;
+
+/// Swaps `amount` in, asserting that exactly `amount` left the user for
+/// the input vault and that the output left the other vault for the user,
+/// priced no better than the constant product
+fn swap_and_check(pool: &mut Pool, user: &User, is_x: bool, amount: u64) {
+    let (user_in, user_out, vault_in, vault_out) = match is_x {
+        true => (user.x, user.y, pool.vault_x, pool.vault_y),
+        false => (user.y, user.x, pool.vault_y, pool.vault_x),
+    };
+
+    let before = (
+        pool.amount(&user_in),
+        pool.amount(&user_out),
+        pool.amount(&vault_in),
+        pool.amount(&vault_out),
+    );
+    let supply = pool.lp_supply();
+
+    pool.swap(user, is_x, amount, 1);
+
+    let out = pool.amount(&user_out) - before.1;
+    assert_eq!(pool.amount(&user_in), before.0 - amount);
+    assert_eq!(pool.amount(&vault_in), before.2 + amount);
+    assert_eq!(pool.amount(&vault_out), before.3 - out);
+    assert!(out > 0);
+    // Fees only ever leave the pool better off
+    assert!(out < (before.3 as u128 * amount as u128 / (before.2 + amount) as u128) as u64);
+    assert_eq!(pool.lp_supply(), supply);
+}
+
+#[test]
+fn initialize_sets_up_the_pool() {
+    let mut pool = Pool::new();
+    pool.initialize();
+
+    // `set_inner` ran, the pool is usable
+    assert_eq!(pool.get(&pool.config).owner, pool.program_id);
+    pool.with_config(|config| {
+        assert_eq!(config.state(), AmmState::Initialized as u8);
+        assert_eq!(config.seed(), SEED);
+        assert_eq!(config.fee(), FEE);
+        assert_eq!(config.mint_x(), &pool.mint_x.to_bytes());
+        assert_eq!(config.mint_y(), &pool.mint_y.to_bytes());
+        assert_eq!(config.mint_lp(), &pool.mint_lp.to_bytes());
+        assert_eq!(config.authority_opt(), Some(&pool.user.key.to_bytes()));
+    });
+    // `reserved`, the last 2 bytes, is left for future fields
+    assert_eq!(pool.config_data()[Config::LEN - 2..], [0, 0]);
+
+    assert_eq!(pool.get(&pool.mint_lp).owner, TOKEN_PROGRAM);
+    assert_eq!(pool.lp_supply(), 0);
+}
+
+#[test]
+fn initialize_records_the_creation_time() {
+    let mut pool = Pool::new();
+    pool.warp(3_600);
+    pool.initialize();
+    assert_eq!(pool.with_config(|config| config.created_at()), NOW + 3_600);
+
+    // Later instructions leave it alone, the age follows the clock
+    let user = pool.user;
+    pool.warp(86_400);
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    let now = pool.now();
+    pool.with_config(|config| {
+        assert_eq!(config.created_at(), NOW + 3_600);
+        assert_eq!(config.age_secs(now), 86_400);
+        // A clock behind the creation
+        assert_eq!(config.age_secs(NOW), 0);
+    });
+}
+
+#[test]
+fn initialize_rejects_an_existing_lp_mint() {
+    let mut pool = Pool::new();
+    let user = pool.user;
+    let mint = pool.token_program_account(&mint_data(Some(&pool.config.to_bytes()), 0, 6));
+    pool.set(pool.mint_lp, mint);
+
+    let ix = pool.initialize_ix(Some(user.key));
+    pool.fail(&ix, amm_error(AmmError::MintAlreadyExists));
+
+    // Rejected before the config was created
+    let config = pool.get(&pool.config);
+    assert_eq!(config.owner, SYSTEM_PROGRAM);
+    assert!(config.data.is_empty());
+}
+
+#[test]
+fn initialize_adopts_prefunded_accounts() {
+    let mut pool = Pool::new();
+    let user = pool.user;
+    let rent = &pool.mollusk.sysvars.rent;
+    let (config_rent, mint_rent) = (
+        rent.minimum_balance(Config::LEN),
+        rent.minimum_balance(Mint::LEN),
+    );
+
+    // Someone sent lamports to both addresses ahead of the pool, the
+    // config more than its rent
+    pool.set(pool.config, system_account(config_rent + 1_000));
+    pool.set(pool.mint_lp, system_account(1_000));
+    pool.initialize();
+
+    // Adopted as they are, the initializer only paid the mint shortfall
+    assert_eq!(pool.get(&pool.config).owner, pool.program_id);
+    assert_eq!(pool.get(&pool.config).lamports, config_rent + 1_000);
+    assert_eq!(pool.get(&pool.mint_lp).owner, TOKEN_PROGRAM);
+    assert_eq!(pool.get(&pool.mint_lp).lamports, mint_rent);
+    assert_eq!(
+        pool.get(&user.key).lamports,
+        10_000_000_000 - (mint_rent - 1_000)
+    );
+    pool.with_config(|config| assert_eq!(config.state(), AmmState::Initialized as u8));
+
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+}
+
+#[test]
+fn initialize_rejects_a_config_address_owned_elsewhere() {
+    let mut pool = Pool::new();
+    let user = pool.user;
+    let mut account = system_account(1_000_000);
+    account.owner = TOKEN_PROGRAM;
+    pool.set(pool.config, account);
+
+    let ix = pool.initialize_ix(Some(user.key));
+    pool.fail(&ix, amm_error(AmmError::ConfigPrefunded));
+}
+
+#[test]
+fn failed_mint_creation_rolls_back_the_config() {
+    let mut pool = Pool::new();
+    let user = pool.user;
+    let mut ix = pool.initialize_ix(Some(user.key));
+    // The config is created, then creating the mint fails: it isn't writable
+    ix.accounts[1].is_writable = false;
+
+    let result = pool.process(&ix);
+    assert!(!matches!(result.program_result, ProgramResult::Success));
+
+    // Nothing the first CPI did persists
+    for (key, account) in &result.resulting_accounts {
+        assert_eq!(account, pool.get(key));
+    }
+    let config = pool.get(&pool.config);
+    assert_eq!(config.owner, SYSTEM_PROGRAM);
+    assert!(config.data.is_empty());
+    assert_eq!(pool.get(&user.key).lamports, 10_000_000_000);
+}
+
+#[test]
+fn test_config_matches_initialize() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let ((_, vault_x_bump), (_, vault_y_bump)) = vault_addresses(
+        &pool.config.to_bytes(),
+        &TOKEN_PROGRAM.to_bytes(),
+        &pool.mint_x.to_bytes(),
+        &pool.mint_y.to_bytes(),
+    );
+    let test_config = |created_at| {
+        Config::new_test(
+            SEED,
+            pool.user.key.to_bytes(),
+            pool.mint_x.to_bytes(),
+            pool.mint_y.to_bytes(),
+            pool.mint_lp.to_bytes(),
+            FEE,
+            [pool.config_bump, vault_x_bump, vault_y_bump],
+            created_at,
+        )
+    };
+
+    // Byte for byte what `Initialize` wrote
+    assert_eq!(pool.config_data(), test_config(NOW));
+
+    // And the program loads it back like one of its own
+    let data = test_config(NOW - 3_600);
+    let mut account = pool.get(&pool.config).clone();
+    account.data = data.to_vec();
+    pool.set(pool.config, account);
+
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.lp_supply(), 1_000_000);
+    pool.with_config(|config| assert_eq!(config.created_at(), NOW - 3_600));
+}
+
+#[test]
+fn client_vault_addresses_match_the_deposit_checks() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let ((vault_x, _), (vault_y, _)) = vault_addresses(
+        &pool.config.to_bytes(),
+        &TOKEN_PROGRAM.to_bytes(),
+        &pool.mint_x.to_bytes(),
+        &pool.mint_y.to_bytes(),
+    );
+    assert_eq!(
+        (vault_x, vault_y),
+        (pool.vault_x.to_bytes(), pool.vault_y.to_bytes())
+    );
+
+    // Through the stored bumps, then derived in `Config::vault_addresses`
+    for bumps in [None, Some(([0], [0]))] {
+        if let Some((bump_x, bump_y)) = bumps {
+            pool.with_config_mut(|config| config.set_vault_bumps(bump_x, bump_y));
+        }
+
+        let mut ix = pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000);
+        ix.accounts.swap(2, 3);
+        pool.fail(&ix, ProgramError::InvalidAccountData);
+
+        pool.deposit(&user, 100_000, 1_000_000, 4_000_000);
+    }
+}
+
+#[test]
+fn first_deposit_mints_through_the_config() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+
+    // `MintTo` signed by the config, the mint authority `Initialize` set
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.amount(&user.lp), 1_000_000);
+    assert_eq!(pool.lp_supply(), 1_000_000);
+    let mint = &pool.get(&pool.mint_lp).data;
+    assert_eq!(mint[0..4], 1u32.to_le_bytes());
+    assert_eq!(mint[4..36], pool.config.to_bytes());
+}
+
+#[test]
+fn deposit_transfers_land_whole_on_top_of_a_donation() {
+    let mut pool = Pool::funded();
+    let user = pool.new_user();
+    // A donation already sitting in the X vault
+    pool.set_token_account(pool.vault_x, pool.mint_x, pool.config, 11_000_000);
+
+    let vaults = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+    pool.deposit(&user, 100_000, u64::MAX, u64::MAX);
+    let sent = (
+        BALANCE - pool.amount(&user.x),
+        BALANCE - pool.amount(&user.y),
+    );
+    assert!(sent.0 > 0 && sent.1 > 0);
+    assert_eq!(
+        (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y)),
+        (vaults.0 + sent.0, vaults.1 + sent.1)
+    );
+}
+
+#[test]
+fn deadlines_are_optional_only_where_the_pool_allows_it() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // Swap, deposit and withdraw without their trailing expiration
+    let without_deadline = |pool: &Pool| {
+        [
+            pool.swap_ix(&user, true, 100_000, 1),
+            pool.deposit_ix(&user, 100_000, u64::MAX, u64::MAX),
+            pool.withdraw_ix(&user, 100_000, 0, 0),
+        ]
+        .map(|mut ix| {
+            ix.data.truncate(ix.data.len() - size_of::<i64>());
+            ix
+        })
+    };
+
+    // Required by default, also when spelled out as the `i64::MAX` sentinel
+    for ix in without_deadline(&pool) {
+        pool.fail(&ix, amm_error(AmmError::DeadlineRequired));
+    }
+    let mut ix = pool.swap_ix(&user, true, 100_000, 1);
+    let len = ix.data.len();
+    ix.data[len - 8..].copy_from_slice(&i64::MAX.to_le_bytes());
+    pool.fail(&ix, amm_error(AmmError::DeadlineRequired));
+
+    // Optional once the authority allows it, explicit deadlines keep working
+    pool.update_config(ConfigParam::AllowNoDeadline, 1);
+    for ix in without_deadline(&pool) {
+        pool.run(&ix);
+    }
+    pool.run(&ix);
+    pool.swap(&user, false, 100_000, 1);
+}
+
+#[test]
+fn deposit_over_either_maximum_is_a_slippage_error() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // 100_000 LP takes 1_000_000 X and 4_000_000 Y
+    for (max_x, max_y) in [(999_999, 4_000_000), (1_000_000, 3_999_999)] {
+        let ix = pool.deposit_ix(&user, 100_000, max_x, max_y);
+        pool.fail(&ix, amm_error(AmmError::DepositSlippageExceeded));
+    }
+}
+
+#[test]
+fn deposit_short_of_y_moves_no_x() {
+    let mut pool = Pool::funded();
+    let user = pool.new_user();
+    // Enough X for the 100_000 LP, 1 Y short of the 4_000_000
+    pool.set_token_account(user.y, pool.mint_y, user.key, 3_999_999);
+
+    let ix = pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000);
+    let result = pool.process(&ix);
+    assert!(matches!(
+        result.program_result,
+        ProgramResult::Failure(ref e) if *e == amm_error(AmmError::InsufficientUserBalance)
+    ));
+    for (key, account) in &result.resulting_accounts {
+        if [user.x, pool.vault_x].contains(key) {
+            assert_eq!(account, pool.get(key));
+        }
+    }
+    assert_eq!(pool.amount(&user.x), BALANCE);
+}
+
+#[test]
+fn deposit_signs_with_the_stored_config_bump() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+
+    // Any other bump than the stored one, at offset 107 as pinned by
+    // `config_layout_is_pinned`, derives another signer than the config
+    let mut account = pool.get(&pool.config).clone();
+    account.data[107] = pool.config_bump.wrapping_sub(1);
+    pool.set(pool.config, account);
+
+    let result = pool.process(&pool.deposit_ix(&user, 1_000_000, 10_000_000, 40_000_000));
+    assert!(!matches!(result.program_result, ProgramResult::Success));
+    assert_eq!(pool.lp_supply(), 0);
+}
+
+#[test]
+fn pool_addresses_derive_from_the_mint_pair() {
+    let mut pool = Pool::without_vaults();
+    let (x, y) = (pool.mint_x.to_bytes(), pool.mint_y.to_bytes());
+
+    // Either order of the mints finds the same pool
+    for (a, b) in [(x, y), (y, x)] {
+        let addresses: Vec<_> =
+            get_pools_by_mints(&blueshift_native_amm::ID, &[SEED, SEED + 1], &a, &b).collect();
+        assert_eq!(addresses[0].config, pool.config.to_bytes());
+        assert_eq!(addresses[0].mint_lp, pool.mint_lp.to_bytes());
+        assert_eq!(addresses[0].vault_x, pool.vault_x.to_bytes());
+        assert_eq!(addresses[0].vault_y, pool.vault_y.to_bytes());
+        assert_ne!(addresses[1].config, addresses[0].config);
+    }
+
+    // Initialize creates the config and LP mint there, and the vaults are
+    // the config ATAs
+    pool.initialize();
+    assert_eq!(pool.get(&pool.config).owner, pool.program_id);
+    assert_eq!(pool.get(&pool.mint_lp).owner, TOKEN_PROGRAM);
+    let user = pool.user;
+    for (mint, vault) in [(pool.mint_x, pool.vault_x), (pool.mint_y, pool.vault_y)] {
+        assert_eq!(pool.create_ata(&user.key, &pool.config, &mint), vault);
+    }
+}
+
+#[test]
+fn initialize_requires_canonically_ordered_mints() {
+    let mut pool = Pool::without_vaults();
+    let canonical = (pool.mint_x, pool.mint_y);
+
+    // The reversed pair, at its own config and LP mint addresses
+    (pool.mint_x, pool.mint_y) = (canonical.1, canonical.0);
+    let reversed = get_pool_by_mints(
+        &blueshift_native_amm::ID,
+        SEED,
+        &pool.mint_x.to_bytes(),
+        &pool.mint_y.to_bytes(),
+    );
+    let canonical_accounts = (pool.config, pool.config_bump, pool.mint_lp, pool.lp_bump);
+    pool.config = Pubkey::new_from_array(reversed.config);
+    pool.config_bump = reversed.config_bump;
+    pool.mint_lp = Pubkey::new_from_array(reversed.mint_lp);
+    pool.lp_bump = reversed.lp_bump;
+    pool.set(pool.config, system_account(0));
+    pool.set(pool.mint_lp, system_account(0));
+    pool.fail(
+        &pool.initialize_ix(Some(pool.user.key)),
+        amm_error(AmmError::MintOrder),
+    );
+
+    // The canonical order goes through
+    (pool.mint_x, pool.mint_y) = canonical;
+    (pool.config, pool.config_bump, pool.mint_lp, pool.lp_bump) = canonical_accounts;
+    pool.initialize();
+    assert_eq!(pool.get(&pool.config).owner, pool.program_id);
+}
+
+#[test]
+fn first_deposit_creates_the_lp_ata() {
+    // The vaults don't exist before the first deposit, the depositor
+    // creates them as the config ATAs
+    let mut pool = Pool::without_vaults();
+    pool.initialize();
+    let user = pool.user;
+    for (mint, vault) in [(pool.mint_x, pool.vault_x), (pool.mint_y, pool.vault_y)] {
+        assert_eq!(pool.create_ata(&user.key, &pool.config, &mint), vault);
+    }
+
+    // A depositor without an LP account, created through the optional
+    // system and associated token program accounts
+    let mut other = pool.new_user();
+    other.lp = pool.ata(&other.key, &pool.mint_lp);
+    pool.set(other.lp, system_account(0));
+
+    let mut ix = pool.deposit_ix(&other, 1_000_000, 10_000_000, 40_000_000);
+    ix.accounts.extend([
+        AccountMeta::new_readonly(SYSTEM_PROGRAM, false),
+        AccountMeta::new_readonly(ATA_PROGRAM, false),
+    ]);
+    pool.run(&ix);
+    assert_eq!(pool.get(&other.lp).owner, TOKEN_PROGRAM);
+    assert_eq!(pool.amount(&other.lp), 1_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 10_000_000);
+    assert_eq!(pool.amount(&pool.vault_y), 40_000_000);
+
+    // Idempotent, a second deposit through the same path finds it in place
+    pool.run(&ix);
+    assert_eq!(pool.amount(&other.lp), 2_000_000);
+
+    // Withdrawing everything closes it, its rent to the given destination
+    let lamports = pool.get(&user.key).lamports + pool.get(&other.lp).lamports;
+    let mut ix = pool.withdraw_ix(&other, 2_000_000, 0, 0);
+    ix.accounts.push(AccountMeta::new(user.key, false));
+    pool.run(&ix);
+    assert_eq!(pool.get(&other.lp).lamports, 0);
+    assert_eq!(pool.get(&user.key).lamports, lamports);
+    assert_eq!(pool.lp_supply(), 0);
+}
+
+#[test]
+fn full_lifecycle() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+
+    // The first deposit sets the price and mints the requested LP
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 10_000_000);
+    assert_eq!(pool.amount(&pool.vault_y), 40_000_000);
+    assert_eq!(pool.amount(&user.x), BALANCE - 10_000_000);
+    assert_eq!(pool.amount(&user.y), BALANCE - 40_000_000);
+    assert_eq!(pool.amount(&user.lp), 1_000_000);
+    assert_eq!(pool.lp_supply(), 1_000_000);
+
+    // Later deposits are proportional
+    pool.deposit(&user, 500_000, 5_000_000, 20_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 15_000_000);
+    assert_eq!(pool.amount(&pool.vault_y), 60_000_000);
+    assert_eq!(pool.amount(&user.lp), 1_500_000);
+    assert_eq!(pool.lp_supply(), 1_500_000);
+
+    // Both directions, the curve arguments follow the direction
+    swap_and_check(&mut pool, &user, true, 100_000);
+    swap_and_check(&mut pool, &user, false, 400_000);
+
+    // Withdrawing everything empties the pool into the user's accounts
+    let (vault_x, vault_y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+    let (user_x, user_y) = (pool.amount(&user.x), pool.amount(&user.y));
+
+    pool.withdraw(&user, 1_500_000, vault_x, vault_y);
+    assert_eq!(pool.amount(&pool.vault_x), 0);
+    assert_eq!(pool.amount(&pool.vault_y), 0);
+    assert_eq!(pool.amount(&user.x), user_x + vault_x);
+    assert_eq!(pool.amount(&user.y), user_y + vault_y);
+    assert_eq!(pool.amount(&user.lp), 0);
+    assert_eq!(pool.lp_supply(), 0);
+
+    // The user paid the swap fees to themselves, nothing was created or lost
+    assert_eq!(pool.amount(&user.x), BALANCE);
+    assert_eq!(pool.amount(&user.y), BALANCE);
+}
+
+#[test]
+fn liquidity_fees_reduce_proceeds() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+
+    pool.update_config(ConfigParam::DepositFeeBps, 100);
+    pool.update_config(ConfigParam::WithdrawFeeBps, 100);
+
+    // Pays for 500_000 LP, 1% of it stays unminted
+    let other = pool.new_user();
+    pool.deposit(&other, 500_000, 5_000_000, 20_000_000);
+    assert_eq!(pool.amount(&other.x), BALANCE - 5_000_000);
+    assert_eq!(pool.amount(&other.y), BALANCE - 20_000_000);
+    assert_eq!(pool.amount(&other.lp), 495_000);
+    assert_eq!(pool.lp_supply(), 1_495_000);
+
+    // 1% of the proportional share stays in the vaults
+    pool.withdraw(&other, 495_000, 0, 0);
+    assert_eq!(pool.amount(&other.x), BALANCE - 5_000_000 + 4_916_889);
+    assert_eq!(pool.amount(&other.y), BALANCE - 20_000_000 + 19_667_557);
+    assert_eq!(pool.lp_supply(), 1_000_000);
+}
+
+#[test]
+fn full_withdraw_with_fee_leaves_pool_usable() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    pool.update_config(ConfigParam::WithdrawFeeBps, 100);
+
+    // The last holder takes everything, no fee is left behind
+    pool.withdraw(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 0);
+    assert_eq!(pool.amount(&pool.vault_y), 0);
+    assert_eq!(pool.lp_supply(), 0);
+    pool.with_config(|config| assert_eq!((config.reserve_x(), config.reserve_y()), (0, 0)));
+
+    // So the pool bootstraps again
+    pool.deposit(&user, 2_000_000, 20_000_000, 80_000_000);
+    assert_eq!(pool.amount(&pool.vault_x), 20_000_000);
+    assert_eq!(pool.amount(&pool.vault_y), 80_000_000);
+    assert_eq!(pool.lp_supply(), 2_000_000);
+}
+
+#[test]
+fn locked_liquidity_waits_for_the_cliff() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    let cliff = pool.now() + 3_600;
+    pool.update_config(ConfigParam::LockedUntil, cliff as u64);
+
+    let ix = pool.withdraw_ix(&user, 500_000, 0, 0);
+    pool.fail(&ix, amm_error(AmmError::LiquidityLocked));
+
+    // Deposits and swaps go on meanwhile
+    pool.deposit(&user, 500_000, 5_000_000, 20_000_000);
+    pool.swap(&user, true, 100_000, 1);
+
+    pool.warp(3_600);
+    pool.withdraw(&user, 500_000, 0, 0);
+    assert_eq!(pool.lp_supply(), 1_000_000);
+}
+
+#[test]
+fn locked_liquidity_leaves_in_an_emergency() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    let cliff = pool.now() + 3_600;
+    pool.update_config(ConfigParam::LockedUntil, cliff as u64);
+
+    // The lock can only be extended
+    let ix = pool.update_config_ix(&user.key, ConfigParam::LockedUntil, cliff as u64 - 1);
+    pool.fail(&ix, amm_error(AmmError::LockCannotBeShortened));
+
+    pool.admin_set_state(AmmState::WithdrawOnly);
+    pool.withdraw(&user, 1_000_000, 10_000_000, 40_000_000);
+    assert_eq!(pool.lp_supply(), 0);
+}
+
+#[test]
+fn simulated_withdraw_matches_the_withdraw() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    // Uneven reserves and a withdraw fee, for the rounding to show
+    pool.swap(&user, true, 123_457, 1);
+    pool.update_config(ConfigParam::WithdrawFeeBps, 25);
+
+    // A partial withdraw, then the full exit
+    for full in [false, true] {
+        let amount = match full {
+            false => 333_333,
+            true => pool.lp_supply(),
+        };
+        let result = pool.run(&pool.simulate_withdraw_ix(amount));
+        let preview = decode_simulate_withdraw(&result.return_data).unwrap();
+
+        let before = (pool.amount(&user.x), pool.amount(&user.y));
+        pool.withdraw(&user, amount, 0, 0);
+        let received = (
+            pool.amount(&user.x) - before.0,
+            pool.amount(&user.y) - before.1,
+        );
+        assert_eq!(preview, received);
+    }
+    assert_eq!(pool.amount(&pool.vault_x), 0);
+}
+
+#[test]
+fn pool_health_reports_the_reserve_imbalance() {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+
+    // Empty reserves count as balanced
+    let result = pool.run(&pool.pool_health_ix(0));
+    assert_eq!(decode_pool_health(&result.return_data), Some(0));
+
+    // 10M X against 40M Y, off by 3/5 at 1:1 but balanced at 4 Y per X
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+    let cases = [(0, 6_000), (4 << 32, 0), (1 << 31, 7_777)];
+    for (price, imbalance) in cases {
+        let result = pool.run(&pool.pool_health_ix(price));
+        assert_eq!(decode_pool_health(&result.return_data), Some(imbalance));
+    }
+
+    // A swap of X in moves a 4:1 priced pool off balance
+    pool.swap(&user, true, 1_000_000, 1);
+    let result = pool.run(&pool.pool_health_ix(4 << 32));
+    assert!(decode_pool_health(&result.return_data).unwrap() > 0);
+}
+
+#[test]
+fn deposit_without_an_amount_mints_from_the_binding_side() {
+    let mut pool = Pool::funded();
+    let user = pool.new_user();
+
+    // At 1 X for 4 Y, X binds first then Y, the other side only pulled
+    // in proportion
+    let cases = [
+        ((1_000_000, 10_000_000), 100_000, (1_000_000, 4_000_000)),
+        ((10_000_000, 2_000_000), 50_000, (500_000, 2_000_000)),
+    ];
+    for ((max_x, max_y), lp, (x, y)) in cases {
+        let before = (
+            pool.amount(&user.x),
+            pool.amount(&user.y),
+            pool.amount(&user.lp),
+        );
+        pool.deposit(&user, 0, max_x, max_y);
+        assert_eq!(pool.amount(&user.lp) - before.2, lp);
+        assert_eq!(before.0 - pool.amount(&user.x), x);
+        assert_eq!(before.1 - pool.amount(&user.y), y);
+    }
+
+    // Too little of either side for a single LP
+    let ix = pool.deposit_ix(&user, 0, 1, 1_000_000);
+    pool.fail(&ix, ProgramError::InvalidArgument);
+}
+
+#[test]
+fn truncated_payloads_fail_cleanly() {
+    let pool = Pool::funded();
+    let user = pool.user;
+    let authority = user.key;
+    let instructions = [
+        pool.initialize_ix(Some(authority)),
+        pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+        pool.withdraw_ix(&user, 100_000, 0, 0),
+        pool.swap_ix(&user, true, 100_000, 1),
+        pool.update_config_ix(&authority, ConfigParam::MaxSwapPctBps, 100),
+    ];
+
+    // Every truncation, down to the bare discriminator, is accepted as a
+    // shorter encoding or rejected, never a panic of the program
+    for ix in instructions {
+        for len in 1..ix.data.len() {
+            let mut truncated = ix.clone();
+            truncated.data.truncate(len);
+            let result = pool.mollusk.process_instruction(&truncated, &pool.accounts);
+            assert!(
+                !matches!(result.program_result, ProgramResult::UnknownError(_)),
+                "{len} bytes: {:?}",
+                result.program_result
+            );
+        }
+    }
+}
+
+#[test]
+fn aliased_user_and_vault_accounts_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // The user's X, or LP, account passed as a vault, or LP passed as X
+    let aliases = [
+        (user.x, pool.vault_x),
+        (user.lp, pool.vault_y),
+        (user.x, user.lp),
+    ];
+    for (alias, other) in aliases {
+        let operations = [
+            pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+            pool.withdraw_ix(&user, 100_000, 0, 0),
+        ];
+        for mut ix in operations {
+            for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == alias) {
+                meta.pubkey = other;
+            }
+            pool.fail(&ix, amm_error(AmmError::DuplicateAccount));
+        }
+    }
+}
+
+#[test]
+fn deposits_below_the_floor_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.update_config(ConfigParam::MinDepositLp, 100_000);
+
+    let ix = pool.deposit_ix(&user, 99_999, 1_000_000, 4_000_000);
+    pool.fail(&ix, amm_error(AmmError::AmountTooSmall));
+    // The floor applies to the LP a deposit by maximums mints as well
+    let ix = pool.deposit_ix(&user, 0, 999_990, 4_000_000);
+    pool.fail(&ix, amm_error(AmmError::AmountTooSmall));
+
+    pool.deposit(&user, 100_000, 1_000_000, 4_000_000);
+    assert_eq!(pool.lp_supply(), 1_100_000);
+
+    // No floor at zero
+    pool.update_config(ConfigParam::MinDepositLp, 0);
+    pool.deposit(&user, 1, 10, 40);
+}
+
+/// `WithdrawSingleSided` of `amount` LP paid out in X or Y, with the
+/// accounts of `Withdraw`
+fn withdraw_single_sided_ix(
+    pool: &Pool,
+    user: &User,
+    amount: u64,
+    out_x: bool,
+    min_out: u64,
+) -> Instruction {
+    let mut ix = pool.withdraw_ix(user, amount, 0, 0);
+    let mut data = vec![*WithdrawSingleSided::DISCRIMINATOR];
+    data.extend_from_slice(&amount.to_le_bytes());
+    data.push(out_x as u8);
+    data.extend_from_slice(&min_out.to_le_bytes());
+    data.extend_from_slice(&pool.expiration().to_le_bytes());
+    ix.data = data;
+    ix
+}
+
+#[test]
+fn single_sided_withdraw_matches_withdraw_then_swap() {
+    for out_x in [true, false] {
+        // The same pool twice, exited both ways
+        let mut single = Pool::funded();
+        let user = single.user;
+        let ix = withdraw_single_sided_ix(&single, &user, 100_000, out_x, 1);
+        single.run(&ix);
+
+        let mut manual = Pool::funded();
+        let other = manual.user;
+        manual.withdraw(&other, 100_000, 0, 0);
+        // 100_000 of 1_000_000 LP releases 1_000_000 X and 4_000_000 Y
+        let leg = match out_x {
+            true => 4_000_000,
+            false => 1_000_000,
+        };
+        manual.swap(&other, !out_x, leg, 1);
+
+        let balances = |pool: &Pool, user: &User| (pool.amount(&user.x), pool.amount(&user.y));
+        assert_eq!(balances(&single, &user), balances(&manual, &other));
+        let vaults = |pool: &Pool| (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+        assert_eq!(vaults(&single), vaults(&manual));
+
+        // Only the payout token came back
+        let (x, y) = balances(&single, &user);
+        match out_x {
+            true => assert!(x > BALANCE - 10_000_000 + 1_000_000 && y == BALANCE - 40_000_000),
+            false => assert!(y > BALANCE - 40_000_000 + 4_000_000 && x == BALANCE - 10_000_000),
+        }
+    }
+}
+
+#[test]
+fn single_sided_withdraw_respects_min_out() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // The swapped leg pays the fee, the payout is short of the pair's value
+    let ix = withdraw_single_sided_ix(&pool, &user, 100_000, true, 2_000_000);
+    pool.fail(&ix, amm_error(AmmError::SlippageExceeded));
+}
+
+#[test]
+fn lp_mint_decimals_must_match_the_config() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // An LP mint at other decimals than the pool recorded, as a migrated
+    // or externally created one could be
+    let mut mint = pool.get(&pool.mint_lp).clone();
+    mint.data[44] = Config::LP_DECIMALS + 3;
+    pool.set(pool.mint_lp, mint);
+
+    let ix = pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000);
+    pool.fail(&ix, amm_error(AmmError::DecimalMismatch));
+    let ix = pool.withdraw_ix(&user, 100_000, 0, 0);
+    pool.fail(&ix, amm_error(AmmError::DecimalMismatch));
+
+    // Recorded the same, the amounts are meaningful again
+    pool.with_config_mut(|config| config.set_lp_decimals(Config::LP_DECIMALS + 3));
+    pool.withdraw(&user, 100_000, 0, 0);
+}
+
+#[test]
+fn liquidity_and_swaps_require_the_user_signature() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    let operations = [
+        pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+        pool.withdraw_ix(&user, 100_000, 0, 0),
+        pool.swap_ix(&user, true, 100_000, 1),
+    ];
+    for ix in operations {
+        let mut unsigned = ix.clone();
+        unsigned.accounts[0].is_signer = false;
+        pool.fail(&unsigned, ProgramError::MissingRequiredSignature);
+        pool.run(&ix);
+    }
+}
+
+#[test]
+fn liquidity_and_swaps_require_spl_token() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let token_2022 = Pubkey::new_from_array(TOKEN_2022_PROGRAM_ID);
+    pool.set(token_2022, system_account(0));
+
+    // Token program position of each instruction
+    let operations = [
+        (pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000), 8),
+        (pool.withdraw_ix(&user, 100_000, 0, 0), 8),
+        (pool.swap_ix(&user, true, 100_000, 1), 7),
+    ];
+    for (ix, index) in operations {
+        assert_eq!(ix.accounts[index].pubkey, TOKEN_PROGRAM);
+        for program in [SYSTEM_PROGRAM, token_2022] {
+            let mut other = ix.clone();
+            other.accounts[index].pubkey = program;
+            pool.fail(&other, ProgramError::IncorrectProgramId);
+        }
+    }
+}
+
+#[test]
+fn initialize_stores_the_vault_bumps() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    let bump = |mint: &Pubkey| {
+        let seeds = [pool.config.as_ref(), TOKEN_PROGRAM.as_ref(), mint.as_ref()];
+        [Pubkey::find_program_address(&seeds, &ATA_PROGRAM).1]
+    };
+    assert_eq!(
+        pool.with_config(|config| config.vault_bumps()),
+        (bump(&pool.mint_x), bump(&pool.mint_y))
+    );
+
+    // Deposits and withdrawals rebuild the vaults from them, cheaper than
+    // grinding both again as pools from before the bumps were stored do
+    let operations = [
+        pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+        pool.withdraw_ix(&user, 100_000, 0, 0),
+    ];
+    for ix in operations {
+        let cost = |pool: &Pool| {
+            let result = pool.mollusk.process_instruction(&ix, &pool.accounts);
+            assert!(matches!(result.program_result, ProgramResult::Success));
+            result.compute_units_consumed
+        };
+
+        let stored = cost(&pool);
+        let bumps = pool.with_config(|config| config.vault_bumps());
+        pool.with_config_mut(|config| config.set_vault_bumps([0], [0]));
+        let derived = cost(&pool);
+        pool.with_config_mut(|config| config.set_vault_bumps(bumps.0, bumps.1));
+        assert!(stored < derived, "{stored} >= {derived}");
+    }
+}
+
+/// `DepositSingleSided` of `amount_in` X or Y, with the accounts of `Deposit`
+fn deposit_single_sided_ix(
+    pool: &Pool,
+    user: &User,
+    amount_in: u64,
+    is_x: bool,
+    min_lp_out: u64,
+) -> Instruction {
+    let mut data = vec![*DepositSingleSided::DISCRIMINATOR];
+    data.extend_from_slice(&amount_in.to_le_bytes());
+    data.push(is_x as u8);
+    data.extend_from_slice(&min_lp_out.to_le_bytes());
+    data.extend_from_slice(&pool.expiration().to_le_bytes());
+    pool.instruction(data, pool.liquidity_metas(user))
+}
+
+#[test]
+fn single_sided_deposit_swaps_then_deposits_both_legs() {
+    // 1M X or the 4M Y it's worth, each about 48_735 LP past the fee
+    for (is_x, amount_in) in [(true, 1_000_000), (false, 4_000_000)] {
+        let mut pool = Pool::funded();
+        let user = pool.user;
+        let balances = |pool: &Pool| {
+            (
+                pool.amount(&user.x),
+                pool.amount(&user.y),
+                pool.amount(&user.lp),
+            )
+        };
+        let (x, y, lp) = balances(&pool);
+
+        let ix = deposit_single_sided_ix(&pool, &user, amount_in, is_x, 50_000);
+        pool.fail(&ix, amm_error(AmmError::SlippageExceeded));
+        pool.run(&deposit_single_sided_ix(
+            &pool, &user, amount_in, is_x, 48_000,
+        ));
+
+        let (x_after, y_after, lp_after) = balances(&pool);
+        assert!((48_700..=48_735).contains(&(lp_after - lp)));
+
+        // All of the input went in, only dust of the swap output came back
+        let (spent, dust) = match is_x {
+            true => (x - x_after, y_after - y),
+            false => (y - y_after, x_after - x),
+        };
+        assert!(spent <= amount_in && amount_in - spent < 100, "{spent}");
+        assert!(dust < 100, "{dust}");
+    }
+}
+
+#[test]
+fn single_sided_deposit_requires_an_initialized_constant_product_pool() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let ix = deposit_single_sided_ix(&pool, &user, 1_000_000, true, 1);
+
+    pool.admin_set_state(AmmState::WithdrawOnly);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    pool.admin_set_state(AmmState::Initialized);
+    pool.with_config_mut(|config| config.set_curve(Config::CURVE_WEIGHTED, 5_000).unwrap());
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+
+    pool.with_config_mut(|config| {
+        config
+            .set_curve(Config::CURVE_CONSTANT_PRODUCT, 5_000)
+            .unwrap()
+    });
+    pool.run(&ix);
+}
@@ -0,0 +1,88 @@
+// Upgrade of config accounts written by the first release to the current
+// layout through `MigrateConfig`, against the runtime. The golden layouts
+// themselves are pinned by the `config_v*` fixtures in `src/state.rs`.
+//
+// Runs the SBF build of the program through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils --test migration
+
+mod common;
+
+use blueshift_native_amm::{AmmError, Config};
+use common::{amm_error, Pool, BALANCE};
+use core::ops::Range;
+use solana_program_error::ProgramError;
+
+/// Fields `MigrateConfig` can't recover, left zeroed: `created_at`, and
+/// `liquidity_seconds` with `last_accrual_ts`. Offsets as pinned by
+/// `config_layout_is_pinned`
+const UNRECOVERABLE: [Range<usize>; 2] = [237..245, 283..307];
+
+/// Pool with liquidity, its config rolled back to v0
+fn v0_pool() -> (Pool, Vec<u8>) {
+    let mut pool = Pool::new();
+    pool.initialize();
+    let user = pool.user;
+    pool.deposit(&user, 1_000_000, 10_000_000, 40_000_000);
+
+    let current = pool.config_data();
+    pool.downgrade_config_to_v0();
+    (pool, current)
+}
+
+#[test]
+fn v0_config_needs_migration() {
+    let (mut pool, _) = v0_pool();
+    let user = pool.user;
+
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+    pool.fail(&ix, amm_error(AmmError::ConfigNeedsMigration));
+    let ix = pool.withdraw_ix(&user, 1_000_000, 0, 0);
+    pool.fail(&ix, amm_error(AmmError::ConfigNeedsMigration));
+}
+
+#[test]
+fn v0_config_migrates_to_the_current_layout() {
+    let (mut pool, mut expected) = v0_pool();
+    let user = pool.user;
+
+    pool.run(&pool.migrate_config_ix(&user.key, pool.lp_bump));
+
+    let account = pool.get(&pool.config);
+    assert_eq!(
+        account.lamports,
+        pool.mollusk.sysvars.rent.minimum_balance(Config::LEN)
+    );
+    // Byte for byte what the current program wrote, but for what v0 never recorded
+    for range in UNRECOVERABLE {
+        expected[range].fill(0);
+    }
+    assert_eq!(account.data, expected);
+
+    // And the pool runs on
+    pool.swap(&user, true, 100_000, 1);
+    let (vault_x, vault_y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+    pool.withdraw(&user, 1_000_000, vault_x, vault_y);
+    assert_eq!(pool.amount(&user.x), BALANCE);
+    assert_eq!(pool.amount(&user.y), BALANCE);
+}
+
+#[test]
+fn migration_runs_once() {
+    let (mut pool, _) = v0_pool();
+    let user = pool.user;
+    pool.run(&pool.migrate_config_ix(&user.key, pool.lp_bump));
+
+    let ix = pool.migrate_config_ix(&user.key, pool.lp_bump);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+}
+
+#[test]
+fn migration_rejects_another_lp_mint() {
+    let (mut pool, _) = v0_pool();
+    let user = pool.user;
+
+    // Any other bump derives another address than the LP mint's
+    let ix = pool.migrate_config_ix(&user.key, pool.lp_bump.wrapping_sub(1));
+    pool.fail(&ix, ProgramError::InvalidSeeds);
+}
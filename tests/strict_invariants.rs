@@ -0,0 +1,27 @@
+// LP supply invariants against the runtime: deposits and withdrawals on a
+// regular LP mint move the supply by exactly the LP minted or burned, and
+// pass the `strict-invariants` checks.
+//
+// Runs the SBF build of the program with the feature through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf --features strict-invariants
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils,strict-invariants --test strict_invariants
+
+mod common;
+
+use common::Pool;
+
+#[test]
+fn supply_moves_by_the_minted_and_burned_amounts() {
+    let mut pool = Pool::funded();
+    let user = pool.new_user();
+
+    pool.deposit(&user, 500_000, 5_000_000, 20_000_000);
+    assert_eq!(pool.lp_supply(), 1_500_000);
+
+    pool.withdraw(&user, 200_000, 0, 0);
+    assert_eq!(pool.lp_supply(), 1_300_000);
+
+    // Derived from the binding side
+    pool.deposit(&user, 0, 1_000_000, 10_000_000);
+    assert_eq!(pool.lp_supply(), 1_400_000);
+}
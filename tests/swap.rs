@@ -0,0 +1,627 @@
+// Swaps against the runtime: the limits a pool puts on them, the curves and
+// fee modes pricing them, and the accounts they accept.
+//
+// Runs the SBF build of the program through mollusk:
+//   AMM_ADMIN=<pubkey> cargo build-sbf
+//   AMM_ADMIN=<pubkey> cargo test --features client,test-utils --test swap
+
+mod common;
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use blueshift_native_amm::math::quote_swap;
+use blueshift_native_amm::test_utils::token_account_data_with_delegate;
+use blueshift_native_amm::utils::TOKEN_2022_PROGRAM_ID;
+use blueshift_native_amm::{
+    decode_accrued_fees, decode_quote, AmmError, ConfigParam, FlashSwap, SwapEvent, SwapToRatio,
+};
+use common::{amm_error, Pool, User, BALANCE, FEE, TOKEN_PROGRAM};
+use mollusk_svm::result::ProgramResult;
+use solana_instruction::{AccountMeta, Instruction};
+use solana_log_collector::LogCollector;
+use solana_program_error::ProgramError;
+use solana_pubkey::Pubkey;
+
+/// `SwapToRatio` toward `ratio_bps`, spending at most `max_in`
+fn swap_to_ratio_ix(pool: &Pool, user: &User, ratio_bps: u64, max_in: u64) -> Instruction {
+    let mut ix = pool.swap_ix(user, true, 1, 1);
+    let mut data = vec![*SwapToRatio::DISCRIMINATOR];
+    data.extend_from_slice(&ratio_bps.to_le_bytes());
+    data.extend_from_slice(&max_in.to_le_bytes());
+    data.extend_from_slice(&1u64.to_le_bytes());
+    data.extend_from_slice(&pool.expiration().to_le_bytes());
+    // Same accounts as `Swap`
+    ix.data = data;
+    ix
+}
+
+/// `FlashSwap` of `amount` X, whose callback is the token program moving
+/// `repay` X from the user to the vault
+fn flash_swap_ix(pool: &Pool, user: &User, amount: u64, min: u64, repay: u64) -> Instruction {
+    let mut ix = pool.swap_ix(user, true, amount, min);
+    ix.data[0] = *FlashSwap::DISCRIMINATOR;
+    // SPL token `Transfer`
+    ix.data.push(3);
+    ix.data.extend_from_slice(&repay.to_le_bytes());
+    ix.accounts.extend([
+        AccountMeta::new_readonly(TOKEN_PROGRAM, false),
+        AccountMeta::new(user.x, false),
+        AccountMeta::new(pool.vault_x, false),
+        AccountMeta::new_readonly(user.key, true),
+    ]);
+    ix
+}
+
+/// `reserve_x / reserve_y` of the pool in basis points
+fn ratio_bps(pool: &Pool) -> u64 {
+    pool.amount(&pool.vault_x) * 10_000 / pool.amount(&pool.vault_y)
+}
+
+#[test]
+fn reserve_breaker_stops_swaps_over_the_percentage() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    // 1% of the 10_000_000 X reserve
+    pool.update_config(ConfigParam::MaxSwapPctBps, 100);
+
+    let ix = pool.swap_ix(&user, true, 100_001, 1);
+    pool.fail(&ix, amm_error(AmmError::SwapExceedsReserveLimit));
+
+    // Right at the limit, the output within 1% of the Y reserve as well
+    let out = quote_swap(10_000_000, 40_000_000, 100_000, FEE).unwrap();
+    assert!(out <= 400_000);
+    pool.swap(&user, true, 100_000, 1);
+    assert_eq!(pool.amount(&pool.vault_x), 10_100_000);
+    assert_eq!(pool.amount(&pool.vault_y), 40_000_000 - out);
+}
+
+#[test]
+fn reserve_breaker_is_off_at_zero() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.update_config(ConfigParam::MaxSwapPctBps, 100);
+    pool.update_config(ConfigParam::MaxSwapPctBps, 0);
+
+    // A tenth of the reserve
+    pool.swap(&user, true, 1_000_000, 1);
+}
+
+#[test]
+fn fee_credits_add_up_to_the_fee_on_volume() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let swaps = [
+        (true, 10_000),
+        (false, 250_000),
+        (true, 77_777),
+        (false, 1_000_003),
+    ];
+
+    let (mut volume_x, mut volume_y) = (0u64, 0u64);
+    for (is_x, amount) in swaps {
+        pool.swap(&user, is_x, amount, 1);
+        match is_x {
+            true => volume_x += amount,
+            false => volume_y += amount,
+        }
+    }
+
+    // Each swap rounds its fee to a whole unit
+    let (fee_x, fee_y) = pool.with_config(|config| (config.total_fee_x(), config.total_fee_y()));
+    let expected_x = volume_x * FEE as u64 / 10_000;
+    let expected_y = volume_y * FEE as u64 / 10_000;
+    assert!(fee_x.abs_diff(expected_x) <= 2, "{fee_x} vs {expected_x}");
+    assert!(fee_y.abs_diff(expected_y) <= 2, "{fee_y} vs {expected_y}");
+
+    // The credits stay in the reserves, with the principal
+    pool.with_config(|config| {
+        assert_eq!(config.reserve_x(), pool.amount(&pool.vault_x));
+        assert_eq!(config.reserve_y(), pool.amount(&pool.vault_y));
+    });
+}
+
+#[test]
+fn aliased_user_and_vault_accounts_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // The input or the output account of the user passed as a vault
+    for (alias, vault) in [
+        (user.x, pool.vault_x),
+        (user.y, pool.vault_y),
+        (user.x, pool.vault_y),
+    ] {
+        let mut ix = pool.swap_ix(&user, true, 100_000, 1);
+        for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == alias) {
+            meta.pubkey = vault;
+        }
+        pool.fail(&ix, amm_error(AmmError::DuplicateAccount));
+    }
+}
+
+#[test]
+fn swaps_below_the_floor_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.update_config(ConfigParam::MinSwapAmount, 10_000);
+
+    let ix = pool.swap_ix(&user, true, 9_999, 1);
+    pool.fail(&ix, amm_error(AmmError::AmountTooSmall));
+    pool.swap(&user, true, 10_000, 1);
+    pool.swap(&user, false, 10_000, 1);
+
+    // No floor at zero
+    pool.update_config(ConfigParam::MinSwapAmount, 0);
+    pool.swap(&user, true, 1_000, 1);
+}
+
+#[test]
+fn fee_growth_follows_the_fees_per_lp() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let supply = pool.lp_supply() as u128;
+
+    // Each swap grows the accumulator of its input by its fee per LP
+    let fees =
+        |pool: &Pool| pool.with_config(|config| (config.total_fee_x(), config.total_fee_y()));
+    let mut expected = (0u128, 0u128);
+    for (is_x, amount) in [
+        (true, 1_000),
+        (true, 333_333),
+        (false, 50_000),
+        (false, 4_000_001),
+    ] {
+        let before = fees(&pool);
+        pool.swap(&user, is_x, amount, 1);
+        let after = fees(&pool);
+
+        expected.0 += (((after.0 - before.0) as u128) << 64) / supply;
+        expected.1 += (((after.1 - before.1) as u128) << 64) / supply;
+        let growth =
+            pool.with_config(|config| (config.fee_growth_global_x(), config.fee_growth_global_y()));
+        assert_eq!(growth, expected);
+    }
+    assert!(expected.0 > 0 && expected.1 > 0);
+}
+
+#[test]
+fn swap_to_ratio_drives_the_pool_to_the_target() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    assert_eq!(ratio_bps(&pool), 2_500);
+
+    // X in, the fee left in the reserves overshoots by a few basis points
+    pool.run(&swap_to_ratio_ix(&pool, &user, 5_000, u64::MAX));
+    assert!(
+        (5_000..=5_005).contains(&ratio_bps(&pool)),
+        "{}",
+        ratio_bps(&pool)
+    );
+
+    // And back, Y in
+    pool.run(&swap_to_ratio_ix(&pool, &user, 2_500, u64::MAX));
+    assert!(
+        (2_495..=2_500).contains(&ratio_bps(&pool)),
+        "{}",
+        ratio_bps(&pool)
+    );
+}
+
+#[test]
+fn swap_to_ratio_spends_at_most_max_in() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // Short of the 4_154_599 X it would take
+    pool.run(&swap_to_ratio_ix(&pool, &user, 5_000, 1_000_000));
+    assert_eq!(pool.amount(&user.x), BALANCE - 10_000_000 - 1_000_000);
+    assert!(ratio_bps(&pool) < 5_000);
+
+    // At the target, nothing left to swap
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let before = pool.amount(&user.x);
+    pool.run(&swap_to_ratio_ix(&pool, &user, 2_500, u64::MAX));
+    assert_eq!(pool.amount(&user.x), before);
+}
+
+#[test]
+fn delegated_swap_is_bound_by_the_allowance() {
+    let mut pool = Pool::funded();
+    let owner = pool.new_user();
+    let delegate = pool.new_user();
+    let data = token_account_data_with_delegate(
+        &pool.mint_x.to_bytes(),
+        &owner.key.to_bytes(),
+        BALANCE,
+        Some(&delegate.key.to_bytes()),
+        50_000,
+    );
+    let account = pool.token_program_account(&data);
+    pool.set(owner.x, account);
+
+    // The delegate signs for the owner's accounts
+    let signer = User {
+        key: delegate.key,
+        ..owner
+    };
+    let ix = pool.swap_ix(&signer, true, 50_001, 1);
+    pool.fail(&ix, amm_error(AmmError::InsufficientDelegation));
+
+    // Anyone else has no allowance at all
+    let stranger = User {
+        key: pool.new_user().key,
+        ..owner
+    };
+    let ix = pool.swap_ix(&stranger, true, 1_000, 1);
+    pool.fail(&ix, amm_error(AmmError::InsufficientDelegation));
+
+    pool.swap(&signer, true, 50_000, 1);
+    assert_eq!(pool.amount(&owner.x), BALANCE - 50_000);
+}
+
+#[test]
+fn spoofed_vaults_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // Token accounts of the right mints, owned by the config, and even
+    // holding the reserves, but not its ATAs
+    let (spoof_x, spoof_y) = (Pubkey::new_unique(), Pubkey::new_unique());
+    pool.set_token_account(spoof_x, pool.mint_x, pool.config, 10_000_000);
+    pool.set_token_account(spoof_y, pool.mint_y, pool.config, 40_000_000);
+
+    for (vault, spoof) in [(pool.vault_x, spoof_x), (pool.vault_y, spoof_y)] {
+        let mut ix = pool.swap_ix(&user, true, 100_000, 1);
+        for meta in ix.accounts.iter_mut().filter(|meta| meta.pubkey == vault) {
+            meta.pubkey = spoof;
+        }
+        pool.fail(&ix, ProgramError::InvalidAccountData);
+    }
+
+    // Nor are the vaults swapped for one another
+    let mut ix = pool.swap_ix(&user, true, 100_000, 1);
+    ix.accounts.swap(3, 4);
+    pool.fail(&ix, ProgramError::InvalidAccountData);
+}
+
+#[test]
+fn stored_vault_bumps_save_the_derivation() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+
+    let cost = |pool: &Pool| {
+        let result = pool.mollusk.process_instruction(&ix, &pool.accounts);
+        assert!(matches!(result.program_result, ProgramResult::Success));
+        result.compute_units_consumed
+    };
+
+    // Pools from before the bumps were stored grind both vault addresses
+    let stored = cost(&pool);
+    pool.with_config_mut(|config| config.set_vault_bumps([0], [0]));
+    let derived = cost(&pool);
+    assert!(stored < derived, "{stored} >= {derived}");
+}
+
+/// Q32.32 price of X in Y the reserves of the funded pool move to once
+/// `amount` is swapped in
+fn price_after(is_x: bool, amount: u64) -> u64 {
+    let (reserve_x, reserve_y) = (10_000_000u128, 40_000_000u128);
+    let (x, y) = match is_x {
+        true => {
+            let out = quote_swap(10_000_000, 40_000_000, amount, FEE).unwrap() as u128;
+            (reserve_x + amount as u128, reserve_y - out)
+        }
+        false => {
+            let out = quote_swap(40_000_000, 10_000_000, amount, FEE).unwrap() as u128;
+            (reserve_x - out, reserve_y + amount as u128)
+        }
+    };
+    ((y << 32) / x) as u64
+}
+
+#[test]
+fn swaps_stop_at_the_price_floor() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.update_config(ConfigParam::PriceFloor, price_after(true, 100_000));
+
+    // Selling X past the floor fails, right down to it passes
+    let ix = pool.swap_ix(&user, true, 100_001, 1);
+    pool.fail(&ix, amm_error(AmmError::PriceOutOfBand));
+    pool.swap(&user, true, 100_000, 1);
+
+    // At the edge, only the way back is open
+    let ix = pool.swap_ix(&user, true, 1_000, 1);
+    pool.fail(&ix, amm_error(AmmError::PriceOutOfBand));
+    pool.swap(&user, false, 1_000, 1);
+}
+
+#[test]
+fn swaps_stop_at_the_price_ceiling() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    pool.update_config(ConfigParam::PriceCeiling, price_after(false, 100_000));
+
+    // Buying X past the ceiling fails, right up to it passes
+    let ix = pool.swap_ix(&user, false, 100_001, 1);
+    pool.fail(&ix, amm_error(AmmError::PriceOutOfBand));
+    pool.swap(&user, false, 100_000, 1);
+
+    let ix = pool.swap_ix(&user, false, 1_000, 1);
+    pool.fail(&ix, amm_error(AmmError::PriceOutOfBand));
+    pool.swap(&user, true, 1_000, 1);
+
+    // No band at zero
+    pool.update_config(ConfigParam::PriceCeiling, 0);
+    pool.swap(&user, false, 1_000_000, 1);
+}
+
+#[test]
+fn swaps_without_protection_are_rejected_when_required() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // Off by default, min = 0 still goes through
+    pool.swap(&user, true, 100_000, 0);
+
+    // A 1% tolerance at most
+    pool.update_config(ConfigParam::RequireMinSlippageBps, 100);
+    let (reserve_x, reserve_y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+    let out = quote_swap(reserve_x, reserve_y, 100_000, FEE).unwrap();
+    let required = (out * 9_900).div_ceil(10_000);
+    for min in [0, 1, required - 1] {
+        pool.fail(
+            &pool.swap_ix(&user, true, 100_000, min),
+            amm_error(AmmError::SlippageProtectionRequired),
+        );
+    }
+
+    // Within the tolerance, the swap runs
+    pool.swap(&user, true, 100_000, required);
+
+    // Zero disables the requirement again
+    pool.update_config(ConfigParam::RequireMinSlippageBps, 0);
+    pool.swap(&user, true, 100_000, 0);
+}
+
+#[test]
+fn inconsistent_vault_balances_are_rejected() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let ix = pool.swap_ix(&user, true, 100_000, 1);
+
+    // An empty vault with LP outstanding, or a vault short of the tracked
+    // reserve
+    let stale = [
+        (pool.vault_y, pool.mint_y, 0, 40_000_000),
+        (pool.vault_x, pool.mint_x, 9_999_999, 10_000_000),
+        (pool.vault_y, pool.mint_y, 39_999_999, 40_000_000),
+    ];
+    for (vault, mint, amount, tracked) in stale {
+        pool.set_token_account(vault, mint, pool.config, amount);
+        pool.fail(&ix, amm_error(AmmError::StaleReserves));
+        pool.set_token_account(vault, mint, pool.config, tracked);
+    }
+
+    // A donation on top of the tracked reserve is fine
+    pool.set_token_account(pool.vault_x, pool.mint_x, pool.config, 10_000_001);
+    pool.run(&ix);
+}
+
+#[test]
+fn flash_swap_repaid_by_the_callback() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let out = quote_swap(10_000_000, 40_000_000, 100_000, FEE).unwrap();
+
+    pool.run(&flash_swap_ix(&pool, &user, 100_000, out, 100_000));
+    assert_eq!(pool.amount(&user.x), BALANCE - 10_000_000 - 100_000);
+    assert_eq!(pool.amount(&user.y), BALANCE - 40_000_000 + out);
+    assert_eq!(pool.amount(&pool.vault_x), 10_100_000);
+    assert_eq!(pool.amount(&pool.vault_y), 40_000_000 - out);
+    assert_eq!(
+        pool.with_config(|config| (config.reserve_x(), config.reserve_y())),
+        (10_100_000, 40_000_000 - out)
+    );
+}
+
+#[test]
+fn flash_swap_not_repaid_reverts() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    // Short by one, or nothing at all
+    for repay in [99_999, 0] {
+        let ix = flash_swap_ix(&pool, &user, 100_000, 1, repay);
+        pool.fail(&ix, amm_error(AmmError::FlashSwapNotRepaid));
+    }
+
+    // The pool can't call itself back
+    let mut ix = flash_swap_ix(&pool, &user, 100_000, 1, 100_000);
+    ix.accounts[9] = AccountMeta::new_readonly(pool.program_id, false);
+    pool.fail(&ix, ProgramError::InvalidArgument);
+}
+
+#[test]
+fn fee_on_output_comes_off_the_proceeds() {
+    let swap = |fee_on_output: u64| {
+        let mut pool = Pool::funded();
+        let user = pool.user;
+        pool.update_config(ConfigParam::FeeOnOutput, fee_on_output);
+        pool.swap(&user, true, 100_000, 1);
+
+        // `k` grows either way
+        let (x, y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+        assert!(x as u128 * y as u128 > 10_000_000u128 * 40_000_000);
+        pool.amount(&user.y) - (BALANCE - 40_000_000)
+    };
+
+    // On the input, the curve prices the input net of the fee
+    let on_input = quote_swap(10_000_000, 40_000_000, 100_000, FEE).unwrap();
+    assert_eq!(swap(0), on_input);
+
+    // On the output, the whole input is priced and the fee, rounded up,
+    // kept out of the proceeds
+    let gross = quote_swap(10_000_000, 40_000_000, 100_000, 0).unwrap();
+    let on_output = gross - (gross * FEE as u64).div_ceil(10_000);
+    assert_eq!(swap(1), on_output);
+
+    // The fee is charged on the larger, undiscounted output
+    assert!(on_output < on_input);
+}
+
+#[test]
+fn accrued_fees_add_up_across_swaps() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let totals = |pool: &mut Pool| {
+        let result = pool.run(&pool.accrued_fees_ix());
+        decode_accrued_fees(&result.return_data).unwrap()
+    };
+    assert_eq!(totals(&mut pool), (0, 0));
+
+    // 30 bps of each input, on its own side
+    pool.swap(&user, true, 100_000, 1);
+    assert_eq!(totals(&mut pool), (300, 0));
+    pool.swap(&user, false, 200_000, 1);
+    pool.swap(&user, true, 50_000, 1);
+    assert_eq!(totals(&mut pool), (450, 600));
+
+    // Liquidity moving in and out leaves them alone
+    pool.deposit(&user, 100_000, u64::MAX, u64::MAX);
+    pool.withdraw(&user, 100_000, 0, 0);
+    assert_eq!(totals(&mut pool), (450, 600));
+}
+
+/// Marks the token account at `key` frozen
+fn freeze(pool: &mut Pool, key: Pubkey) {
+    let mut account = pool.get(&key).clone();
+    // AccountState::Frozen
+    account.data[108] = 2;
+    pool.set(key, account);
+}
+
+#[test]
+fn frozen_accounts_are_rejected_before_any_transfer() {
+    // A frozen user ATA, then a frozen vault
+    for user_side in [true, false] {
+        let mut pool = Pool::funded();
+        let user = pool.user;
+        let key = match user_side {
+            true => user.x,
+            false => pool.vault_y,
+        };
+        freeze(&mut pool, key);
+
+        let operations = [
+            pool.swap_ix(&user, true, 100_000, 1),
+            pool.deposit_ix(&user, 100_000, 1_000_000, 4_000_000),
+            pool.withdraw_ix(&user, 100_000, 0, 0),
+        ];
+        for ix in operations {
+            pool.fail(&ix, amm_error(AmmError::AccountFrozen));
+        }
+    }
+}
+
+#[test]
+fn quote_impact_grows_with_the_trade() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+
+    let mut quote = |amount: u64| {
+        let result = pool.run(&pool.quote_ix(true, amount));
+        let quote = decode_quote(&result.return_data).unwrap();
+        assert_eq!(
+            quote.amount_out,
+            quote_swap(10_000_000, 40_000_000, amount, FEE).unwrap()
+        );
+        assert_eq!(
+            quote.effective_price,
+            (((quote.amount_out as u128) << 32) / amount as u128) as u64
+        );
+        quote
+    };
+
+    // A small trade barely moves past the fee, a large one moves the price
+    let small = quote(1_000);
+    assert!((30..40).contains(&small.price_impact_bps));
+    let large = quote(5_000_000);
+    assert!(large.price_impact_bps > 3_000);
+    assert!(large.effective_price < small.effective_price);
+
+    // The quote is what the swap then pays
+    let before = pool.amount(&user.y);
+    pool.swap(&user, true, 1_000, 1);
+    assert_eq!(pool.amount(&user.y) - before, small.amount_out);
+}
+
+#[test]
+fn swap_rejects_token_accounts_of_another_token_program() {
+    // A Token-2022 user ATA, then a Token-2022 vault, under legacy Token
+    for user_side in [true, false] {
+        let mut pool = Pool::funded();
+        let user = pool.user;
+        let key = match user_side {
+            true => user.x,
+            false => pool.vault_y,
+        };
+        let mut account = pool.get(&key).clone();
+        account.owner = Pubkey::new_from_array(TOKEN_2022_PROGRAM_ID);
+        pool.set(key, account);
+
+        pool.fail(
+            &pool.swap_ix(&user, true, 100_000, 1),
+            amm_error(AmmError::TokenProgramMismatch),
+        );
+    }
+}
+
+#[test]
+fn swap_event_reports_the_reserves_left_in_the_vaults() {
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    let logs = LogCollector::new_ref();
+    pool.mollusk.logger = Some(logs.clone());
+
+    pool.swap(&user, true, 100_000, 1);
+
+    let recorded = logs.borrow().get_recorded_content().to_vec();
+    let event = recorded
+        .iter()
+        .filter_map(|log| log.strip_prefix("Program data: "))
+        .find_map(|data| SwapEvent::decode(&BASE64_STANDARD.decode(data).ok()?))
+        .unwrap();
+
+    // No protocol fee or donation, the vaults hold the reserves exactly
+    assert!(event.is_x);
+    assert_eq!(event.amount_in + event.fee, 100_000);
+    assert_eq!(event.reserve_x_after, pool.amount(&pool.vault_x));
+    assert_eq!(event.reserve_y_after, pool.amount(&pool.vault_y));
+    assert_eq!(
+        event.spot_price_after(),
+        Some((event.reserve_y_after << 32) / event.reserve_x_after)
+    );
+}
+
+#[test]
+fn swap_pays_the_hand_computed_constant_product() {
+    // 10M X, 40M Y and 1M LP: neither reserve matches the LP supply
+    let mut pool = Pool::funded();
+    let user = pool.user;
+    assert_ne!(pool.lp_supply(), pool.amount(&pool.vault_x));
+
+    // 99_700 X in after the fee: 40M - ceil(4e14 / 10_099_700)
+    let before = pool.amount(&user.y);
+    pool.swap(&user, true, 100_000, 1);
+    assert_eq!(pool.amount(&user.y) - before, 394_863);
+
+    // 398_800 Y in after the fee, against the reserves the first swap left
+    let (x, y) = (pool.amount(&pool.vault_x), pool.amount(&pool.vault_y));
+    assert_eq!((x, y), (10_100_000, 39_605_137));
+    let expected = 398_800 * x / (y + 398_800);
+    let before = pool.amount(&user.x);
+    pool.swap(&user, false, 400_000, 1);
+    assert_eq!(pool.amount(&user.x) - before, expected);
+}